@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS markov_filter_settings
+(
+    server_id          BIGINT PRIMARY KEY NOT NULL,
+    disabled_stages    TEXT[] NOT NULL DEFAULT '{}',
+    min_words          INT NOT NULL DEFAULT 5
+);
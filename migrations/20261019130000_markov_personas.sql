@@ -0,0 +1,9 @@
+-- chains trained on uploaded text files, the chains themselves are stored in data/markov data/personas
+CREATE TABLE IF NOT EXISTS markov_personas
+(
+    id                 SERIAL PRIMARY KEY,
+    server_id          BIGINT NOT NULL,
+    name               TEXT NOT NULL,
+    creator_id         BIGINT NOT NULL,
+    UNIQUE(server_id, name)
+);
@@ -0,0 +1,11 @@
+-- how big the persona chains of a server are allowed to grow
+CREATE TABLE IF NOT EXISTS markov_chain_limits
+(
+    server_id               BIGINT PRIMARY KEY,
+    min_transition_count    INT NOT NULL DEFAULT 1,
+    max_states              INT NOT NULL DEFAULT 500000
+);
+
+ALTER TABLE markov_personas
+    ADD COLUMN state_count INT NOT NULL DEFAULT 0,
+    ADD COLUMN estimated_bytes BIGINT NOT NULL DEFAULT 0;
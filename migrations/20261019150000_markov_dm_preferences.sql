@@ -0,0 +1,6 @@
+-- users who let the bot learn from their direct messages and which chain the messages go to
+CREATE TABLE IF NOT EXISTS markov_dm_preferences
+(
+    user_id            BIGINT PRIMARY KEY,
+    learning_mode      TEXT NOT NULL
+);
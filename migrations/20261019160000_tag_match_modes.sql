@@ -0,0 +1,8 @@
+-- how the listener of a tag is compared to messages, see TagMatchMode
+ALTER TABLE tags
+ADD COLUMN match_mode TEXT NOT NULL DEFAULT 'word';
+
+-- tags with multiple words were always matched as phrases
+UPDATE tags
+SET match_mode = 'phrase'
+WHERE listener LIKE '% %';
@@ -0,0 +1,15 @@
+-- how many seconds a tag waits before responding again anywhere, in the same channel and to the same user
+-- NULL uses the default of the server
+ALTER TABLE tags
+ADD COLUMN cooldown_seconds INT,
+ADD COLUMN channel_cooldown_seconds INT,
+ADD COLUMN user_cooldown_seconds INT;
+
+-- settings for all of the tags of a server
+CREATE TABLE IF NOT EXISTS tag_settings
+(
+    server_id                   BIGINT PRIMARY KEY,
+    cooldown_seconds            INT NOT NULL DEFAULT 0,
+    channel_cooldown_seconds    INT NOT NULL DEFAULT 0,
+    user_cooldown_seconds       INT NOT NULL DEFAULT 0
+);
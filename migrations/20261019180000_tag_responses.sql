@@ -0,0 +1,11 @@
+-- responses a tag picks from at random besides the response in the tags table
+ALTER TABLE tags
+ADD CONSTRAINT tags_id_key UNIQUE (id);
+
+CREATE TABLE IF NOT EXISTS tag_responses
+(
+    id          SERIAL PRIMARY KEY,
+    tag_id      INT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    response    TEXT NOT NULL,
+    weight      INT NOT NULL DEFAULT 1
+);
@@ -0,0 +1,7 @@
+-- what kind of message a tag responds with, see TagResponseType
+-- attachments are stored in data/tag attachments
+ALTER TABLE tags
+ADD COLUMN response_type TEXT NOT NULL DEFAULT 'text',
+ADD COLUMN embed_title TEXT,
+ADD COLUMN embed_colour INT,
+ADD COLUMN attachment_file TEXT;
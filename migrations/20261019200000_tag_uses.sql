@@ -0,0 +1,20 @@
+-- every time a tag is tripped off, used for /tag info and /tag top
+CREATE TABLE IF NOT EXISTS tag_uses
+(
+    id          SERIAL PRIMARY KEY,
+    tag_id      INT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    server_id   BIGINT NOT NULL,
+    channel_id  BIGINT NOT NULL,
+    user_id     BIGINT NOT NULL,
+    used_at     TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS tag_uses_tag_id_used_at ON tag_uses (tag_id, used_at);
+CREATE INDEX IF NOT EXISTS tag_uses_server_id_used_at ON tag_uses (server_id, used_at);
+
+-- tags created before this migration don't have a creation date
+ALTER TABLE tags
+ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+
+ALTER TABLE tags
+ALTER COLUMN created_at SET DEFAULT now();
@@ -0,0 +1,12 @@
+-- limit tags to an allow-list or deny-list of channels and keep them out of nsfw and announcement channels
+ALTER TABLE tags
+ADD COLUMN IF NOT EXISTS channel_scope TEXT NOT NULL DEFAULT 'everywhere',
+ADD COLUMN IF NOT EXISTS disabled_in_nsfw BOOLEAN NOT NULL DEFAULT false,
+ADD COLUMN IF NOT EXISTS disabled_in_announcements BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS tag_scope_channels
+(
+    tag_id      INT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    channel_id  BIGINT NOT NULL,
+    PRIMARY KEY(tag_id, channel_id)
+);
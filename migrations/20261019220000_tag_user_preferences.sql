@@ -0,0 +1,16 @@
+-- whether the bot pings a user or responds to them at all when they trip off a tag,
+-- either in one server or in every server when server_id is 0
+CREATE TABLE IF NOT EXISTS tag_user_preferences
+(
+    user_id     BIGINT NOT NULL,
+    server_id   BIGINT NOT NULL,
+    preference  TEXT NOT NULL,
+    PRIMARY KEY(user_id, server_id)
+);
+
+-- everyone who pressed "Stop pinging me" before asked not to be pinged anywhere
+INSERT INTO tag_user_preferences ( user_id, server_id, preference )
+SELECT user_id, 0, 'no-ping' FROM tag_blacklisted_users
+ON CONFLICT DO NOTHING;
+
+DROP TABLE tag_blacklisted_users;
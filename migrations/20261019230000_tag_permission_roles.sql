@@ -0,0 +1,9 @@
+-- the roles allowed to create, edit, remove or ban in a server,
+-- an action without roles keeps the default permissions
+CREATE TABLE IF NOT EXISTS tag_permission_roles
+(
+    server_id   BIGINT NOT NULL,
+    action      TEXT NOT NULL,
+    role_id     BIGINT NOT NULL,
+    PRIMARY KEY(server_id, action, role_id)
+);
@@ -0,0 +1,22 @@
+-- bans keep why and by whom they were issued and can run out,
+-- bans from before this have no reason and never run out
+ALTER TABLE tag_bans ADD COLUMN IF NOT EXISTS reason TEXT;
+ALTER TABLE tag_bans ADD COLUMN IF NOT EXISTS banned_by BIGINT;
+ALTER TABLE tag_bans ADD COLUMN IF NOT EXISTS banned_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE tag_bans ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+-- everything done to the tags of a server and who did it
+CREATE TABLE IF NOT EXISTS tag_audit_log
+(
+    id              SERIAL PRIMARY KEY,
+    server_id       BIGINT NOT NULL,
+    -- 0 when the bot did it on its own, like when a ban runs out
+    user_id         BIGINT NOT NULL,
+    action          TEXT NOT NULL,
+    tag_listener    TEXT,
+    target_user_id  BIGINT,
+    details         TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS tag_audit_log_server_id_created_at_idx ON tag_audit_log (server_id, created_at);
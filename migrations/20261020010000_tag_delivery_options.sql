@@ -0,0 +1,10 @@
+-- how the response of a tag is posted and how many seconds until it's deleted
+-- NULL uses the default of the server
+ALTER TABLE tags
+ADD COLUMN delivery_mode TEXT,
+ADD COLUMN delete_after_seconds INT;
+
+-- responses are sent as messages and kept unless the server chooses otherwise, 0 never deletes them
+ALTER TABLE tag_settings
+ADD COLUMN delivery_mode TEXT NOT NULL DEFAULT 'message',
+ADD COLUMN delete_after_seconds INT NOT NULL DEFAULT 0;
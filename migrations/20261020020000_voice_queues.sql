@@ -0,0 +1,25 @@
+-- the queue of every server the bot is playing in so it can be restored after a restart
+CREATE TABLE IF NOT EXISTS voice_queues
+(
+    server_id           BIGINT PRIMARY KEY,
+    voice_channel_id    BIGINT NOT NULL,
+    text_channel_id     BIGINT NOT NULL,
+    -- how far into the currently playing track the bot was
+    position_ms         BIGINT NOT NULL DEFAULT 0,
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS voice_queue_tracks
+(
+    server_id   BIGINT NOT NULL REFERENCES voice_queues(server_id) ON DELETE CASCADE,
+    -- 0 is the currently playing track
+    position    INT NOT NULL,
+    source_url  TEXT NOT NULL,
+    title       TEXT,
+    artist      TEXT,
+    channel     TEXT,
+    duration_ms BIGINT,
+    thumbnail   TEXT,
+    queued_by   TEXT NOT NULL,
+    PRIMARY KEY (server_id, position)
+);
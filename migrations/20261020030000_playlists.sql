@@ -0,0 +1,21 @@
+-- queues saved by users with /playlist save
+CREATE TABLE IF NOT EXISTS playlists
+(
+    id                  SERIAL PRIMARY KEY,
+    owner_id            BIGINT NOT NULL,
+    name                TEXT NOT NULL,
+    -- the server everyone can load the playlist in, NULL if only the owner can load it
+    shared_server_id    BIGINT,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (owner_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS playlists_shared_server_id ON playlists (shared_server_id);
+
+CREATE TABLE IF NOT EXISTS playlist_tracks
+(
+    playlist_id INT NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+    position    INT NOT NULL,
+    source_url  TEXT NOT NULL,
+    PRIMARY KEY (playlist_id, position)
+);
@@ -0,0 +1,10 @@
+-- uses older than the retention of tag_uses are folded into one row per tag
+-- so /tag info and /tag top keep counting them
+CREATE TABLE IF NOT EXISTS tag_use_totals
+(
+    tag_id      INT PRIMARY KEY REFERENCES tags(id) ON DELETE CASCADE,
+    uses        BIGINT NOT NULL,
+    last_used   TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS tag_uses_used_at ON tag_uses (used_at);
@@ -0,0 +1,4 @@
+-- the track position_ms belongs to, the saved tracks are renumbered
+-- when tracks that can't be saved are left out so position 0 isn't always the playing one
+ALTER TABLE voice_queues
+ADD COLUMN playing_source_url TEXT;
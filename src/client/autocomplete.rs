@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, Context, CreateAutocompleteResponse,
+    CreateInteractionResponse,
+};
+use sqlx::PgPool;
+use tracing::{Instrument, error, info_span};
+
+use super::{
+    helper_funcs::get_full_command_name,
+    memes::meme_category_choices,
+    slash_commands::UserCommand,
+    tags::tag_listener_choices,
+    voice::{autocomplete::track_choices, playlists::playlist_choices},
+};
+
+/// Discord doesn't show more choices than this
+pub const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+/// Check which option the user is typing in and suggest choices for it
+#[tracing::instrument(name = "Autocomplete", skip(ctx, pool, command))]
+pub async fn autocomplete_responses(command: &CommandInteraction, ctx: &Context, pool: &PgPool) {
+    let Some(focused_option) = command.data.autocomplete() else {
+        return;
+    };
+
+    let full_command_name = get_full_command_name(command);
+
+    let Ok(user_command) = UserCommand::from_str(&full_command_name) else {
+        error!("Cannot autocomplete unknown command {full_command_name}");
+        return;
+    };
+
+    let typed = focused_option.value;
+
+    let mut choices = match (user_command, focused_option.name) {
+        (UserCommand::remove_tag, "tag") => {
+            tag_listener_choices(ctx, command.guild_id, typed, pool).await
+        }
+        (UserCommand::meme_post, "category") => meme_category_choices(typed, pool)
+            .await
+            .unwrap_or_else(|why| {
+                error!("Couldn't get the meme categories {why:?}");
+                Vec::new()
+            }),
+        (UserCommand::skip, "number" | "until")
+        | (UserCommand::swap_songs, "first-track" | "second-track") => {
+            track_choices(ctx, command.guild_id, typed).await
+        }
+        (UserCommand::playlist_load, "name") => {
+            playlist_choices(command.user.id, command.guild_id, typed, true, pool).await
+        }
+        (
+            UserCommand::playlist_delete
+            | UserCommand::playlist_rename
+            | UserCommand::playlist_share,
+            "name",
+        ) => playlist_choices(command.user.id, command.guild_id, typed, false, pool).await,
+        (_, option) => {
+            error!("No autocomplete for the option {option} of {full_command_name}");
+            return;
+        }
+    };
+    choices.truncate(MAX_AUTOCOMPLETE_CHOICES);
+
+    autocomplete_response(command, ctx, choices).await;
+}
+
+async fn autocomplete_response(
+    command: &CommandInteraction,
+    ctx: &Context,
+    choices: Vec<AutocompleteChoice<'static>>,
+) {
+    if let Err(why) = command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Autocomplete(
+                CreateAutocompleteResponse::new().set_choices(choices),
+            ),
+        )
+        .instrument(info_span!("Sending autocomplete choices"))
+        .await
+    {
+        error!("Couldn't send the autocomplete choices {why:?}");
+    }
+}
@@ -1,7 +1,11 @@
 use anyhow::Result;
 use std::{fs, path::Path};
 
-use crate::client::memes::{MEME_IMPORT_FOLDER, MEMES_FOLDER};
+use crate::client::{
+    markov::model::{MARKOV_DMS_FOLDER, MARKOV_PERSONAS_FOLDER},
+    memes::{MEME_IMPORT_FOLDER, MEMES_FOLDER},
+    tags::TAG_ATTACHMENTS_FOLDER,
+};
 
 /// Checks if a file exists and if it doesn't it initializes it.
 /// Otherwise it just returns the path back
@@ -17,11 +21,30 @@ pub fn create_data_folders() {
         fs::create_dir_all("data/markov data").expect("Couldn't create directory data/markov data");
     }
 
+    if !Path::new(MARKOV_PERSONAS_FOLDER).exists() {
+        fs::create_dir_all(MARKOV_PERSONAS_FOLDER).expect(&format!(
+            "Couldn't create directory {MARKOV_PERSONAS_FOLDER}"
+        ));
+    }
+
+    if !Path::new(MARKOV_DMS_FOLDER).exists() {
+        fs::create_dir_all(MARKOV_DMS_FOLDER)
+            .expect(&format!("Couldn't create directory {MARKOV_DMS_FOLDER}"));
+    }
+
     if !Path::new(MEMES_FOLDER).exists() {
-        fs::create_dir_all(MEMES_FOLDER).expect(&format!("Couldn't create directory {MEMES_FOLDER}"));
+        fs::create_dir_all(MEMES_FOLDER)
+            .expect(&format!("Couldn't create directory {MEMES_FOLDER}"));
     }
 
     if !Path::new(MEME_IMPORT_FOLDER).exists() {
-        fs::create_dir_all(MEME_IMPORT_FOLDER).expect(&format!("Couldn't create directory {MEME_IMPORT_FOLDER}"));
+        fs::create_dir_all(MEME_IMPORT_FOLDER)
+            .expect(&format!("Couldn't create directory {MEME_IMPORT_FOLDER}"));
+    }
+
+    if !Path::new(TAG_ATTACHMENTS_FOLDER).exists() {
+        fs::create_dir_all(TAG_ATTACHMENTS_FOLDER).expect(&format!(
+            "Couldn't create directory {TAG_ATTACHMENTS_FOLDER}"
+        ));
     }
 }
@@ -1,49 +1,136 @@
-use serenity::all::{CommandData, CommandDataOptionValue};
+use serenity::all::{
+    AttachmentId, CommandData, CommandDataOption, CommandDataOptionValue, RoleId, UserId,
+};
 
 pub trait GetOptionFromCommand {
     fn get_string(&self, name: &str) -> String;
+    fn get_optional_string(&self, name: &str) -> Option<String>;
     fn get_optional_bool(&self, name: &str) -> Option<bool>;
     fn get_optional_int(&self, name: &str) -> Option<i64>;
+    fn get_optional_attachment(&self, name: &str) -> Option<AttachmentId>;
+    fn get_optional_user(&self, name: &str) -> Option<UserId>;
+    fn get_optional_role(&self, name: &str) -> Option<RoleId>;
+}
+
+/// The options of the subcommand that was called, which can be inside of a subcommand group.
+/// Commands without subcommands have their options at the top level.
+fn sub_command_options(data: &CommandData) -> &[CommandDataOption] {
+    match data.options.first().map(|o| &o.value) {
+        Some(CommandDataOptionValue::SubCommand(command_data_options)) => command_data_options,
+        Some(CommandDataOptionValue::SubCommandGroup(sub_commands)) => {
+            match &sub_commands.first().unwrap().value {
+                CommandDataOptionValue::SubCommand(command_data_options) => command_data_options,
+                _ => panic!("unknown option"),
+            }
+        }
+        _ => &data.options,
+    }
+}
+
+fn find_option<'a>(data: &'a CommandData, name: &str) -> Option<&'a CommandDataOptionValue> {
+    sub_command_options(data)
+        .iter()
+        .find(|o| o.name == name)
+        .map(|o| &o.value)
 }
 
 impl GetOptionFromCommand for CommandData {
     fn get_string(&self, name: &str) -> String {
-        match self.options.first().cloned().unwrap().value {
-            CommandDataOptionValue::SubCommand(command_data_options) => command_data_options
-                .iter()
-                .find(|o| o.name == name)
-                .unwrap()
-                .value
-                .as_str()
-                .unwrap()
-                .to_string(),
-            _ => panic!("unknown option"),
-        }
+        find_option(self, name)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn get_optional_string(&self, name: &str) -> Option<String> {
+        Some(find_option(self, name)?.as_str()?.to_string())
     }
 
     fn get_optional_bool(&self, name: &str) -> Option<bool> {
-        match self.options.first().cloned().unwrap().value {
-            CommandDataOptionValue::SubCommand(command_data_options) => Some(
-                command_data_options
-                    .iter()
-                    .find(|o| o.name == name)?
-                    .value
-                    .as_bool()?,
-            ),
-            _ => panic!("unknown option"),
-        }
+        find_option(self, name)?.as_bool()
     }
 
     fn get_optional_int(&self, name: &str) -> Option<i64> {
-        match self.options.first().cloned().unwrap().value {
-            CommandDataOptionValue::SubCommand(command_data_options) => Some(
-                command_data_options
-                    .iter()
-                    .find(|o| o.name == name)?
-                    .value
-                    .as_i64()?,
-            ),
-            _ => panic!("unknown option"),
-        }
+        find_option(self, name)?.as_i64()
+    }
+
+    fn get_optional_attachment(&self, name: &str) -> Option<AttachmentId> {
+        find_option(self, name)?.as_attachment_id()
+    }
+
+    fn get_optional_user(&self, name: &str) -> Option<UserId> {
+        find_option(self, name)?.as_user_id()
+    }
+
+    fn get_optional_role(&self, name: &str) -> Option<RoleId> {
+        find_option(self, name)?.as_role_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn command_data(options: serde_json::Value) -> CommandData {
+        serde_json::from_value(json!({
+            "id": "1",
+            "name": "command",
+            "type": 1,
+            "options": options,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_top_level_options() {
+        let data = command_data(json!([
+            { "name": "stage", "type": 3, "value": "lowercase" },
+            { "name": "enabled", "type": 5, "value": false },
+            { "name": "min-words", "type": 4, "value": 3 },
+        ]));
+
+        assert_eq!(
+            data.get_optional_string("stage").as_deref(),
+            Some("lowercase")
+        );
+        assert_eq!(data.get_optional_bool("enabled"), Some(false));
+        assert_eq!(data.get_optional_int("min-words"), Some(3));
+        assert_eq!(data.get_optional_int("missing"), None);
+    }
+
+    #[test]
+    fn commands_without_options_have_none() {
+        let data = command_data(json!([]));
+
+        assert_eq!(data.get_optional_string("stage"), None);
+    }
+
+    #[test]
+    fn reads_subcommand_options() {
+        let data = command_data(json!([{
+            "name": "edit",
+            "type": 1,
+            "options": [{ "name": "tag", "type": 3, "value": "hello" }],
+        }]));
+
+        assert_eq!(data.get_string("tag"), "hello");
+    }
+
+    #[test]
+    fn reads_subcommand_group_options() {
+        let data = command_data(json!([{
+            "name": "response",
+            "type": 2,
+            "options": [{
+                "name": "add",
+                "type": 1,
+                "options": [{ "name": "weight", "type": 4, "value": 2 }],
+            }],
+        }]));
+
+        assert_eq!(data.get_optional_int("weight"), Some(2));
     }
 }
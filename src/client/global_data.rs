@@ -1,17 +1,16 @@
 use crate::client::{
-    markov::MARKOV_STATE_SIZE,
+    markov::{FilterPipeline, PersonalChain, create_default_chain, markov_chain::MarkovChain},
+    tags::{TagCooldowns, TagMatcher, TagUserPreferences},
     voice::model::{QueueData, VoiceMessages},
 };
 
 use super::markov::init_markov_data;
-use markov_str::MarkovChain;
-use regex::Regex;
-use serenity::all::Context;
+use serenity::all::{Context, GuildId, UserId};
 use songbird::{
     Config, Songbird,
     driver::retry::{Retry, Strategy},
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 
 pub const HELP_MESSAGE: &str = "All of my commands are slash commands.
@@ -19,11 +18,38 @@ pub const HELP_MESSAGE: &str = "All of my commands are slash commands.
 /id: gives you the user id of the selected user
 /stop-saving-my-messages: tell the bot not to store your messages and not to learn from them
 /continue-saving-my-messages: tell the bot to save and learn from your messages
-/tag create: create a tag that the bot will listen for and then respond to when it is said
-/tag remove: remove a tag
-/tag list: list out the current tags
-/tag stop-pinging-me: tell the bot not to ping you if you trip off a tag
+/markov generate: generate a sentence, optionally from one of this server's personas
+/markov personas: list the personas of this server
+/markov-persona upload: for admins only, teach the bot a new persona from a .txt file
+/markov-persona delete: for admins only, delete a persona
+/markov dms: choose whether the bot learns from the messages you send it in DMs
+/markov stats: check how much memory the bot's chains take up
+/markov-limits: for admins only, choose how big the personas of this server can grow
+/markov-filter: for admins only, choose how messages in this server are filtered before the bot learns from them
+/tag create: create a tag that the bot will listen for and then respond to when it is said, match-mode chooses whether it looks for words, phrases, prefixes, substrings, globs or regexes
+/tag create response: leave it out to write it in a text box that can hold line breaks, /tag edit does the same with multi-line, it can use {user}, {user.mention}, {channel}, {server}, {args} for the text after the tag, {random:a|b|c} and {markov}
+/tag create and /tag edit channel options: limit a tag to an allow-list or deny-list of channels and keep it out of nsfw or announcement channels
+/tag remove: remove a tag you own, moderators can remove any tag
+/tag edit: change the listener, response, match mode or channels of a tag you own
+/tag transfer: give a tag you own to someone else
+/tag response add/remove/list: give a tag you own more responses, one of them is picked at random by their weights
+/tag response type: make a tag you own respond with text, an embed, a file or a reaction
+/tag list: list out the current tags, they can be searched and sorted by name, creator or usage
+/tag info: show who made a tag and how often it's used
+/tag top: list the most used tags in this server
+/tag export: get a file of every tag in this server
+/tag import: create the tags of a file from /tag export as your own, overwriting tags also needs the permission to edit them
+/tag stop-pinging-me: tell the bot not to ping you if you trip off a tag in this server, or everywhere
+/tag ignore-me: tell the bot not to respond to your messages at all if they trip off a tag in this server, or everywhere
+/tag ban and /tag unban: ban a user from editing tags, for good or for some days and hours, with a reason
+/tag bans: list the users banned from editing tags
+/tag audit-log: see who created, edited and removed tags and who was banned
+/tag permissions: see which roles can create, edit, remove tags and ban from editing them, admins can choose the roles
 /tag response-channel: for admins only, set the channel where the bot will respond to tags
+/tag cooldown: for moderators only, choose how long a tag waits before responding again overall, per channel and per user
+/tag default-cooldown: for moderators only, choose the cooldowns of tags that don't have their own
+/tag delivery: choose if a tag responds with a message, a reply or in a thread and after how many seconds the response is deleted
+/tag default-delivery: for moderators only, choose how tags that don't choose themselves are posted
 /play: play a song from youtube in VC. Accepts both song titles and youtube links
 /skip: skip a song
 /stop: stop the current song and clear the queue
@@ -32,11 +58,25 @@ pub const HELP_MESSAGE: &str = "All of my commands are slash commands.
 /queue-shuffle: shuffle all the songs in the queue
 /loop: loop the current song
 /swap_songs: swap 2 songs positions in the queue
+/playlist save and /playlist load: save the queue as one of your playlists and play it again later
+/playlist list, rename and delete: manage your playlists
+/playlist share: let everyone in this server load one of your playlists
 /version: check the version of the bot";
 
 pub type BotState = RwLock<InnerBotState>;
 pub struct InnerBotState {
     pub markov_chain: MarkovChain,
+    /// Persona chains that have been used since the bot started, keyed by guild and persona name
+    pub persona_chains: HashMap<(GuildId, String), MarkovChain>,
+    /// Personal chains of users who let the bot learn from their DMs and have sent one recently
+    pub dm_chains: HashMap<UserId, PersonalChain>,
+    /// Markov filters of the guilds that have received messages since the bot started
+    pub markov_filters: HashMap<GuildId, Arc<FilterPipeline>>,
+    /// Compiled tags of the guilds that have received messages since the bot started
+    pub tag_matchers: HashMap<GuildId, Arc<TagMatcher>>,
+    /// Whether users want to be pinged or responded to when they trip off a tag, [`None`] until it's first read
+    pub tag_user_preferences: Option<TagUserPreferences>,
+    pub tag_cooldowns: TagCooldowns,
     pub voice_messages: VoiceMessages,
     pub queue_data: QueueData,
     pub songbird: Arc<Songbird>,
@@ -45,10 +85,13 @@ pub struct InnerBotState {
 impl Default for InnerBotState {
     fn default() -> Self {
         Self {
-            markov_chain: MarkovChain::new(
-                MARKOV_STATE_SIZE,
-                Regex::new(markov_str::WORD_REGEX).unwrap(),
-            ),
+            markov_chain: create_default_chain(),
+            persona_chains: HashMap::default(),
+            dm_chains: HashMap::default(),
+            markov_filters: HashMap::default(),
+            tag_matchers: HashMap::default(),
+            tag_user_preferences: None,
+            tag_cooldowns: TagCooldowns::default(),
             voice_messages: Default::default(),
             queue_data: Default::default(),
             songbird: Songbird::serenity(),
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     path::Path,
     process::{Output, Stdio},
     time::Duration,
@@ -8,8 +9,9 @@ use chrono::{Datelike, TimeZone, Utc};
 use file_format::{FileFormat, Kind};
 use serenity::{
     all::{
-        CommandDataOptionValue, CommandInteraction, CommandOptionType, Context, CreateAttachment,
-        CreateInteractionResponseMessage, EditInteractionResponse, Message,
+        CommandDataOption, CommandDataOptionValue, CommandInteraction, Context,
+        CreateAllowedMentions, CreateAttachment, CreateInteractionResponseMessage,
+        EditInteractionResponse, Message,
     },
     builder::CreateInteractionResponse,
     model::{
@@ -111,28 +113,91 @@ pub enum GetGuildChannelError {
     ChannelNotInGuild,
 }
 
-pub fn get_full_command_name(command: &CommandInteraction) -> String {
-    let mut sub_command_group = None;
-    let mut sub_command = None;
+/// Responds to the command with a text that doesn't ping anyone mentioned in it
+pub async fn command_response<'a>(
+    ctx: &Context,
+    command: &CommandInteraction,
+    response: impl Into<Cow<'a, str>>,
+) -> anyhow::Result<()> {
+    command_response_message(
+        ctx,
+        command,
+        CreateInteractionResponseMessage::new().content(response),
+    )
+    .await
+}
 
-    for option in &command.data.options {
-        match option.kind() {
-            CommandOptionType::SubCommand => {
-                sub_command_group = Some(&option.name);
-            }
-            CommandOptionType::SubCommandGroup => {
-                sub_command = Some(&option.name);
+/// Responds to the command with a text only the user who used it can see
+pub async fn ephemeral_command_response<'a>(
+    ctx: &Context,
+    command: &CommandInteraction,
+    response: impl Into<Cow<'a, str>>,
+) -> anyhow::Result<()> {
+    command_response_message(
+        ctx,
+        command,
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content(response),
+    )
+    .await
+}
+
+/// Responds to the command with a message that doesn't ping anyone mentioned in it
+pub async fn command_response_message(
+    ctx: &Context,
+    command: &CommandInteraction,
+    response: CreateInteractionResponseMessage<'_>,
+) -> anyhow::Result<()> {
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                response.allowed_mentions(CreateAllowedMentions::new()),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+/// Replaces the response of a deferred command with a text that doesn't ping anyone mentioned in it
+pub async fn edit_command_response<'a>(
+    ctx: &Context,
+    command: &CommandInteraction,
+    response: impl Into<Cow<'a, str>>,
+) -> anyhow::Result<()> {
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new()
+                .content(response)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+/// The name of the command followed by the names of its subcommand group and subcommand
+pub fn get_full_command_name(command: &CommandInteraction) -> String {
+    let mut full_command_name = command.data.name.to_string();
+    let mut options: &[CommandDataOption] = &command.data.options;
+
+    while let Some(option) = options.first() {
+        match &option.value {
+            CommandDataOptionValue::SubCommandGroup(sub_options)
+            | CommandDataOptionValue::SubCommand(sub_options) => {
+                full_command_name = full_command_name + " " + &option.name;
+                options = sub_options;
             }
-            _ => {}
+            _ => break,
         }
     }
 
-    match (sub_command_group, sub_command) {
-        (None, None) => command.data.name.to_string(),
-        (None, Some(b)) => command.data.name.to_string() + " " + b,
-        (Some(a), None) => command.data.name.to_string() + " " + a,
-        (Some(a), Some(b)) => command.data.name.to_string() + " " + a + " " + b,
-    }
+    full_command_name
 }
 
 #[tracing::instrument(skip(ctx, command, message))]
@@ -1,10 +1,13 @@
 use serenity::{
-    all::{CreateCommand, InteractionContext},
+    all::{CommandOptionType, CreateCommand, CreateCommandOption, InteractionContext},
     model::Permissions,
 };
+use strum::{EnumProperty, IntoEnumIterator};
 
 use crate::client::slash_commands::UserCommand;
 
+use super::{filter::FilterStage, model::DmLearningMode};
+
 pub fn create_markov_commands() -> Vec<CreateCommand<'static>> {
     vec![
         CreateCommand::new(UserCommand::stop_saving_my_messages.to_string())
@@ -19,6 +22,176 @@ pub fn create_markov_commands() -> Vec<CreateCommand<'static>> {
         .default_member_permissions(Permissions::ADMINISTRATOR),
         CreateCommand::new(UserCommand::continue_saving_my_messages.to_string()).description(
             "Remove yourself from the blacklist if you want me to save and learn from your messages",
-        )
+        ),
+        create_markov_filter_command(),
+        create_markov_command(),
+        create_markov_persona_command(),
+        create_markov_limits_command(),
     ]
 }
+
+fn create_markov_command() -> CreateCommand<'static> {
+    CreateCommand::new("markov")
+        .description("Generate sentences from what I've learned")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::markov_generate.get_str("SubCommand").unwrap(),
+                "Generate a sentence",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "persona",
+                    "The persona to generate the sentence from",
+                )
+                .required(false),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::markov_personas.get_str("SubCommand").unwrap(),
+            "List the personas of this server",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::markov_stats.get_str("SubCommand").unwrap(),
+            "Check how big my chains are",
+        ))
+        .add_option(create_markov_dms_subcommand())
+}
+
+fn create_markov_dms_subcommand() -> CreateCommandOption<'static> {
+    let mut learn_option = CreateCommandOption::new(
+        CommandOptionType::String,
+        "learn",
+        "Which chain learns from your DMs",
+    )
+    .required(true)
+    .add_string_choice("off", "off");
+
+    for mode in DmLearningMode::iter() {
+        learn_option = learn_option.add_string_choice(mode.to_string(), mode.to_string());
+    }
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::markov_dms.get_str("SubCommand").unwrap(),
+        "Choose whether I learn from the messages you send me in DMs",
+    )
+    .add_sub_option(learn_option)
+}
+
+fn create_markov_limits_command() -> CreateCommand<'static> {
+    CreateCommand::new(UserCommand::markov_limits.to_string())
+        .description("Choose how big the personas of this server can grow")
+        .add_context(InteractionContext::Guild)
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "min-count",
+                "Forget word sequences that were seen fewer times than this",
+            )
+            .min_int_value(1)
+            .max_int_value(100)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "max-states",
+                "The most states all personas of this server can have together",
+            )
+            .min_int_value(1000)
+            .max_int_value(5_000_000)
+            .required(false),
+        )
+}
+
+fn create_markov_persona_command() -> CreateCommand<'static> {
+    CreateCommand::new("markov-persona")
+        .description("Manage the personas of this server")
+        .add_context(InteractionContext::Guild)
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::markov_persona_upload
+                    .get_str("SubCommand")
+                    .unwrap(),
+                "Teach me a new persona from a text file. Replaces the persona if it already exists",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "name",
+                    "The name of the persona",
+                )
+                .max_length(32)
+                .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Attachment,
+                    "file",
+                    "A .txt file to learn from",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::markov_persona_delete
+                    .get_str("SubCommand")
+                    .unwrap(),
+                "Delete a persona",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "name",
+                    "The name of the persona",
+                )
+                .required(true),
+            ),
+        )
+}
+
+fn create_markov_filter_command() -> CreateCommand<'static> {
+    let mut stage_option = CreateCommandOption::new(
+        CommandOptionType::String,
+        "stage",
+        "The filter stage to turn on or off",
+    )
+    .required(false);
+
+    for stage in FilterStage::iter() {
+        stage_option = stage_option.add_string_choice(stage.to_string(), stage.to_string());
+    }
+
+    CreateCommand::new(UserCommand::markov_filter.to_string())
+        .description("Choose how messages in this server are filtered before I learn from them")
+        .add_context(InteractionContext::Guild)
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(stage_option)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether the stage should be on. Toggles the stage if left empty",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "min-words",
+                "The minimum number of words a message needs for me to learn from it",
+            )
+            .min_int_value(1)
+            .max_int_value(50)
+            .required(false),
+        )
+}
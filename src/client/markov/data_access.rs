@@ -1,8 +1,11 @@
-use sqlx::{PgPool, postgres::PgQueryResult, query, query_as};
+use sqlx::{PgPool, postgres::PgQueryResult, query, query_as, query_scalar};
 
 use crate::client::markov::model::MarkovBlacklistedServer;
 
-use super::model::{MarkovBlacklistedChannel, MarkovBlacklistedUser};
+use super::model::{
+    MarkovBlacklistedChannel, MarkovBlacklistedUser, MarkovChainLimits, MarkovDmPreference,
+    MarkovFilterSettings, MarkovPersona,
+};
 
 pub async fn get_markov_blacklisted_server(
     server_id: i64,
@@ -141,3 +144,222 @@ pub async fn delete_markov_blacklisted_channel(
     .execute(pool)
     .await?)
 }
+
+pub async fn get_markov_filter_settings(
+    server_id: i64,
+    pool: &PgPool,
+) -> Option<MarkovFilterSettings> {
+    query_as!(
+        MarkovFilterSettings,
+        "
+		SELECT * FROM markov_filter_settings where server_id = $1
+		",
+        server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_markov_filter_settings(
+    server_id: i64,
+    disabled_stages: &[String],
+    min_words: i32,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		INSERT INTO markov_filter_settings (server_id, disabled_stages, min_words)
+		VALUES ($1, $2, $3)
+		ON CONFLICT(server_id)
+		DO UPDATE SET
+			disabled_stages = EXCLUDED.disabled_stages,
+			min_words = EXCLUDED.min_words
+		"#,
+        server_id,
+        disabled_stages,
+        min_words
+    )
+    .execute(pool)
+    .await?)
+}
+
+pub async fn get_markov_persona(
+    server_id: i64,
+    name: &str,
+    pool: &PgPool,
+) -> Option<MarkovPersona> {
+    query_as!(
+        MarkovPersona,
+        "
+		SELECT * FROM markov_personas where server_id = $1 AND name = $2
+		",
+        server_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_markov_personas_by_server_id(server_id: i64, pool: &PgPool) -> Vec<MarkovPersona> {
+    query_as!(
+        MarkovPersona,
+        "
+		SELECT * FROM markov_personas where server_id = $1
+		ORDER BY name
+		",
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_markov_persona(
+    server_id: i64,
+    name: &str,
+    creator_id: i64,
+    state_count: i32,
+    estimated_bytes: i64,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		INSERT INTO markov_personas (server_id, name, creator_id, state_count, estimated_bytes)
+		VALUES ($1, $2, $3, $4, $5)
+		ON CONFLICT(server_id, name)
+		DO UPDATE SET
+			creator_id = EXCLUDED.creator_id,
+			state_count = EXCLUDED.state_count,
+			estimated_bytes = EXCLUDED.estimated_bytes
+		"#,
+        server_id,
+        name,
+        creator_id,
+        state_count,
+        estimated_bytes
+    )
+    .execute(pool)
+    .await?)
+}
+
+/// The number of states the server's personas use, not counting the persona with the given name
+pub async fn get_markov_persona_state_count(
+    server_id: i64,
+    excluded_name: &str,
+    pool: &PgPool,
+) -> i64 {
+    query_scalar!(
+        r#"
+		SELECT COALESCE(SUM(state_count), 0) as "state_count!" FROM markov_personas
+		WHERE server_id = $1 AND name != $2
+		"#,
+        server_id,
+        excluded_name
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn delete_markov_persona(
+    server_id: i64,
+    name: &str,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		DELETE FROM markov_personas
+		WHERE server_id = $1 AND name = $2
+		"#,
+        server_id,
+        name
+    )
+    .execute(pool)
+    .await?)
+}
+
+pub async fn get_markov_chain_limits(server_id: i64, pool: &PgPool) -> Option<MarkovChainLimits> {
+    query_as!(
+        MarkovChainLimits,
+        "
+		SELECT * FROM markov_chain_limits where server_id = $1
+		",
+        server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_markov_chain_limits(
+    server_id: i64,
+    min_transition_count: i32,
+    max_states: i32,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		INSERT INTO markov_chain_limits (server_id, min_transition_count, max_states)
+		VALUES ($1, $2, $3)
+		ON CONFLICT(server_id)
+		DO UPDATE SET
+			min_transition_count = EXCLUDED.min_transition_count,
+			max_states = EXCLUDED.max_states
+		"#,
+        server_id,
+        min_transition_count,
+        max_states
+    )
+    .execute(pool)
+    .await?)
+}
+
+pub async fn get_markov_dm_preference(user_id: i64, pool: &PgPool) -> Option<MarkovDmPreference> {
+    query_as!(
+        MarkovDmPreference,
+        "
+		SELECT * FROM markov_dm_preferences where user_id = $1
+		",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_markov_dm_preference(
+    user_id: i64,
+    learning_mode: &str,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		INSERT INTO markov_dm_preferences (user_id, learning_mode)
+		VALUES ($1, $2)
+		ON CONFLICT(user_id)
+		DO UPDATE SET
+			learning_mode = EXCLUDED.learning_mode
+		"#,
+        user_id,
+        learning_mode
+    )
+    .execute(pool)
+    .await?)
+}
+
+pub async fn delete_markov_dm_preference(
+    user_id: i64,
+    pool: &PgPool,
+) -> anyhow::Result<PgQueryResult> {
+    Ok(query!(
+        r#"
+		DELETE FROM markov_dm_preferences
+		WHERE user_id = $1
+		"#,
+        user_id
+    )
+    .execute(pool)
+    .await?)
+}
@@ -0,0 +1,227 @@
+// purpose: to let users teach the bot through its DMs if they want to
+//
+// behavior:
+// - by default the bot doesn't learn anything from DMs
+// - `/markov dms learn:personal` saves the user's DMs to `data/markov data/dms/{user_id}.txt`
+//   and the bot replies to their DMs with a chain built only from that file
+// - `/markov dms learn:global` adds the user's DMs to the same data set the servers teach
+// - `/markov dms learn:off` stops learning and deletes the personal data set
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    Message, UserId,
+};
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+use tracing::{Instrument, info_span, instrument};
+
+use crate::client::{get_option_from_command::GetOptionFromCommand, global_data::GetBotState};
+
+use super::{
+    MARKOV_STATE_SIZE, create_default_chain,
+    data_access::{
+        delete_markov_dm_preference, get_markov_blacklisted_user, get_markov_dm_preference,
+        upsert_markov_dm_preference,
+    },
+    file_operations::append_to_markov_file,
+    filter::FilterPipeline,
+    generate_from_chain, generate_sentence,
+    markov_chain::MarkovChain,
+    model::{DmLearningMode, MARKOV_DMS_FOLDER},
+};
+
+/// How long a personal chain stays in memory after its user last sent the bot a DM
+const PERSONAL_CHAIN_IDLE_TIME: Duration = Duration::from_mins(60);
+
+/// The personal chain of a user, built from their data set the first time it's needed.
+///
+/// Every chain has its own lock so building it or adding a DM to it doesn't hold up the bot state.
+pub struct PersonalChain {
+    chain: Arc<Mutex<Option<MarkovChain>>>,
+    last_used: Instant,
+}
+
+impl Default for PersonalChain {
+    fn default() -> Self {
+        Self {
+            chain: Arc::default(),
+            last_used: Instant::now(),
+        }
+    }
+}
+
+fn dm_data_set_path(user_id: UserId) -> PathBuf {
+    Path::new(MARKOV_DMS_FOLDER).join(format!("{user_id}.txt"))
+}
+
+/// Finds the personal chain of the user and drops the chains nobody has used in a while
+async fn personal_chain(ctx: &Context, user_id: UserId) -> Arc<Mutex<Option<MarkovChain>>> {
+    let state_lock = ctx.bot_state();
+    let mut state = state_lock.write().await;
+    let now = Instant::now();
+
+    state
+        .dm_chains
+        .retain(|_, c| now.duration_since(c.last_used) < PERSONAL_CHAIN_IDLE_TIME);
+
+    let personal_chain = state.dm_chains.entry(user_id).or_default();
+    personal_chain.last_used = now;
+    personal_chain.chain.clone()
+}
+
+/// Which chain the user's DMs are learned by, [`None`] if the bot doesn't learn from them
+pub async fn get_dm_learning_mode(user_id: UserId, pool: &PgPool) -> Option<DmLearningMode> {
+    get_markov_dm_preference(user_id.get() as i64, pool)
+        .await
+        .and_then(|p| DmLearningMode::from_str(&p.learning_mode).ok())
+}
+
+/// Adds a DM to the chain the user chose. DMs of users who didn't opt in are ignored.
+pub async fn add_direct_message_to_chain(
+    msg: &Message,
+    mode: Option<DmLearningMode>,
+    ctx: &Context,
+    pool: &PgPool,
+) -> Result<bool, std::io::Error> {
+    let Some(mode) = mode else {
+        return Ok(false);
+    };
+
+    if get_markov_blacklisted_user(msg.author.id.get() as i64, pool)
+        .await
+        .is_some()
+    {
+        return Ok(false);
+    }
+
+    let Some(filtered_message) = FilterPipeline::default().filter_message(msg) else {
+        return Ok(false);
+    };
+
+    match mode {
+        DmLearningMode::Global => append_to_markov_file(&filtered_message)?,
+        DmLearningMode::Personal => {
+            // Held while writing so a chain that's being built doesn't learn the message twice
+            let chain_lock = personal_chain(ctx, msg.author.id).await;
+            let mut chain = chain_lock.lock().await;
+
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dm_data_set_path(msg.author.id))?;
+            writeln!(file, "{filtered_message}\n")?;
+
+            // Chains that haven't been built yet will read the message from the file
+            if let Some(chain) = chain.as_mut() {
+                chain.add_text(&filtered_message);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+#[instrument]
+fn build_personal_chain(user_id: UserId) -> anyhow::Result<MarkovChain> {
+    let path = dm_data_set_path(user_id);
+    if !path.exists() {
+        return Ok(create_default_chain());
+    }
+
+    let messages = fs::read_to_string(path)?;
+    let messages = messages.split("\n\n").collect::<Vec<_>>();
+
+    Ok(MarkovChain::build_parallel(MARKOV_STATE_SIZE, &messages))
+}
+
+/// Generates a reply to a message from the chain the message's author learns with.
+///
+/// Everything except DMs of users who chose a personal chain is replied to from the main chain.
+/// `dm_learning_mode` is the mode of the author if the message is a DM.
+#[instrument(skip(ctx, msg))]
+pub async fn generate_reply(
+    ctx: &Context,
+    msg: &Message,
+    start: Option<&str>,
+    dm_learning_mode: Option<DmLearningMode>,
+) -> anyhow::Result<String> {
+    if msg.guild_id.is_some() || dm_learning_mode != Some(DmLearningMode::Personal) {
+        return Ok(generate_sentence(ctx, start).await);
+    }
+
+    let user_id = msg.author.id;
+
+    // The chain is built under its lock so it's only built once
+    // and no DM is added to the file while it's being read
+    let chain_lock = personal_chain(ctx, user_id).await;
+    let mut chain = chain_lock.lock().await;
+    let chain = match &mut *chain {
+        Some(chain) => chain,
+        unbuilt @ None => {
+            let built =
+                tokio::task::spawn_blocking(move || build_personal_chain(user_id)).await??;
+            unbuilt.insert(built)
+        }
+    };
+
+    Ok(generate_from_chain(chain, start))
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn markov_dms_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let user_id = command.user.id;
+    let mode = DmLearningMode::from_str(&command.data.get_string("learn")).ok();
+
+    let response = match mode {
+        Some(mode) => {
+            upsert_markov_dm_preference(user_id.get() as i64, &mode.to_string(), pool).await?;
+
+            match mode {
+                DmLearningMode::Personal => {
+                    "I'll learn from the messages you send me in DMs and reply to them with what I learned only from you"
+                }
+                DmLearningMode::Global => {
+                    "I'll learn from the messages you send me in DMs together with the messages from servers"
+                }
+            }
+        }
+        None => {
+            delete_markov_dm_preference(user_id.get() as i64, pool).await?;
+
+            ctx.bot_state().write().await.dm_chains.remove(&user_id);
+            let path = dm_data_set_path(user_id);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+
+            "I won't learn from your DMs anymore and I deleted what I learned from them for you personally"
+        }
+    };
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(response),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
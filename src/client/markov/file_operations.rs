@@ -1,11 +1,11 @@
 use super::{
-    create_default_chain,
-    markov_chain::filter_string_for_markov_file,
-    model::{MARKOV_DATA_SET_PATH, MARKOV_EXPORT_PATH},
+    MARKOV_STATE_SIZE,
+    filter::FilterPipeline,
+    markov_chain::{ChainLimits, MarkovChain},
+    model::{LEGACY_MARKOV_EXPORT_PATH, MARKOV_DATA_SET_PATH, MARKOV_EXPORT_PATH},
 };
 use crate::client::file_operations::create_file_if_missing;
 use anyhow::{Context, Result};
-use markov_str::MarkovChain;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::{
     fs::{self, OpenOptions},
@@ -22,24 +22,27 @@ pub fn append_to_markov_file(str: &str) -> Result<(), std::io::Error> {
 
 /// If the way that messages are filtered before being added to the data set is changed then
 /// it's helpful to call this function when the bot starts so the filtering is consistent across the file.
-#[allow(dead_code)]
-pub fn clean_markov_file() {
-    let file = fs::read_to_string(MARKOV_DATA_SET_PATH)
-        .expect("Something went wrong while reading the file.");
+///
+/// Runs every message through the default [`FilterPipeline`] and drops the ones it rejects.
+#[instrument]
+pub fn clean_markov_file() -> Result<()> {
+    let file = fs::read_to_string(MARKOV_DATA_SET_PATH).context("Failed to read file")?;
     let messages = file.split("\n\n").collect::<Vec<&str>>();
 
-    fs::write(MARKOV_DATA_SET_PATH, "").expect("Something went wrong while writing to file.");
+    let pipeline = FilterPipeline::default();
 
     let filtered_messages: Vec<String> = messages
         .into_par_iter()
-        .map(filter_string_for_markov_file)
+        .filter_map(|m| pipeline.filter_str(m))
         .collect();
 
+    fs::write(MARKOV_DATA_SET_PATH, "").context("Failed to write file")?;
+
     for message in filtered_messages {
-        if let Err(why) = append_to_markov_file(&message) {
-            eprintln!("{why}");
-        }
+        append_to_markov_file(&message)?;
     }
+
+    Ok(())
 }
 
 #[instrument]
@@ -49,12 +52,30 @@ pub fn import_chain_from_file() -> Result<MarkovChain> {
         fs::read_to_string(x).context("Failed to read file")
     })?;
 
-    let import_export = info_span!("Parsing file contents")
+    let mut import_export = info_span!("Parsing file contents")
         .in_scope(|| serde_json::from_str::<MarkovChain>(&file_contents))?;
 
+    // The limits might have changed since the chain was exported
+    import_export.apply_limits(ChainLimits::from_env());
+
     Ok(import_export)
 }
 
+/// Whether the export was written by `markov_str`, which stored the chain before it was built in parallel
+#[instrument]
+pub fn is_legacy_export() -> bool {
+    fs::read_to_string(MARKOV_EXPORT_PATH)
+        .is_ok_and(|contents| serde_json::from_str::<markov_str::MarkovChain>(&contents).is_ok())
+}
+
+/// Moves the `markov_str` export to [`LEGACY_MARKOV_EXPORT_PATH`] so it isn't lost when
+/// the chain is rebuilt from the data set in the current format
+#[instrument]
+pub fn backup_legacy_export() -> Result<()> {
+    fs::rename(MARKOV_EXPORT_PATH, LEGACY_MARKOV_EXPORT_PATH)
+        .context("Failed to back up the markov_str export")
+}
+
 #[instrument]
 /// Reads the Markov data set from [`MARKOV_DATA_SET_PATH`]
 pub fn get_messages_from_file() -> Result<Vec<String>> {
@@ -71,14 +92,12 @@ pub fn get_messages_from_file() -> Result<Vec<String>> {
 pub fn generate_new_chain_from_msg_file() -> Result<MarkovChain> {
     let messages = get_messages_from_file()?;
 
-    let mut markov = create_default_chain();
-    for msg in messages {
-        markov.add_text(&msg);
-    }
+    let mut markov = MarkovChain::build_parallel(MARKOV_STATE_SIZE, &messages);
+    markov.apply_limits(ChainLimits::from_env());
 
-    let export = serde_json::to_string(&markov)?;
+    let export = info_span!("Serializing chain").in_scope(|| serde_json::to_string(&markov))?;
 
-    fs::write(MARKOV_EXPORT_PATH, export)?;
+    info_span!("Writing file").in_scope(|| fs::write(MARKOV_EXPORT_PATH, export))?;
 
     Ok(markov)
 }
@@ -0,0 +1,306 @@
+use std::{str::FromStr, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use serenity::model::channel::Message;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+use super::model::MarkovFilterSettings;
+
+pub const DEFAULT_MIN_NUM_OF_WORDS: usize = 5;
+
+static LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:(?:https?|ftp)://|\b(?:[a-z\d]+\.))(?:(?:[^\s()<>]+|\((?:[^\s()<>]+|(?:\([^\s()<>]+\)))?\))+(?:\((?:[^\s()<>]+|(?:\(?:[^\s()<>]+\)))?\)|[^\s`!()\[\]{};:'".,<>?«»“”‘’]))?"#)
+        .expect("Invalid regular expression")
+});
+static USER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<@!?(\d+)>").expect("Invalid regular expression"));
+static ROLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<@&(\d+)>").expect("Invalid regular expression"));
+static EMOTE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<a?:?(\w+:)(\d+)>").expect("Invalid regular expression"));
+static NON_ALPHANUMERIC_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"[,.!"\#$()=?*<>{}\[\]\\\|Łł@*;:+~ˇ^˘°˛`´˝]"#).expect("Invalid regular expression")
+});
+/// Leftover user IDs from mentions that couldn't be resolved
+static USER_ID_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{17,20}\b").expect("Invalid regular expression"));
+static WHITESPACE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s+").expect("Invalid regular expression"));
+static UPPER_CASE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Z][a-z0-9_-]{1,}").expect("Invalid regular expression"));
+
+/// A single step of the [`FilterPipeline`]. Stages run in the order they're declared in.
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FilterStage {
+    /// Removes links
+    RemoveLinks,
+    /// Replaces user mentions with the user's name and removes role mentions.
+    ///
+    /// The message is discarded if a user mention can't be resolved.
+    ResolveMentions,
+    /// Removes emotes and animated emotes
+    RemoveEmotes,
+    /// Removes non alphanumeric characters and leftover user IDs
+    RemoveSymbols,
+    /// Lowercases capitalized words while leaving all caps words alone
+    Lowercase,
+    /// Discards messages with fewer words than the configured minimum
+    MinWordCount,
+}
+
+/// Filters text so it can be inserted into the Markov data set.
+///
+/// Every stage uses precompiled regexes, so a pipeline is cheap to build per message.
+/// Line feeds and extra whitespace are always collapsed regardless of the enabled stages.
+#[derive(Debug, Clone)]
+pub struct FilterPipeline {
+    stages: Vec<FilterStage>,
+    min_words: usize,
+}
+
+impl Default for FilterPipeline {
+    fn default() -> Self {
+        Self {
+            stages: FilterStage::iter().collect(),
+            min_words: DEFAULT_MIN_NUM_OF_WORDS,
+        }
+    }
+}
+
+impl FilterPipeline {
+    /// Builds the pipeline for a guild, skipping the stages the guild disabled
+    pub fn from_settings(settings: &MarkovFilterSettings) -> Self {
+        let disabled_stages = settings
+            .disabled_stages
+            .iter()
+            .filter_map(|s| FilterStage::from_str(s).ok())
+            .collect::<Vec<_>>();
+
+        Self {
+            stages: FilterStage::iter()
+                .filter(|s| !disabled_stages.contains(s))
+                .collect(),
+            min_words: usize::try_from(settings.min_words).unwrap_or(DEFAULT_MIN_NUM_OF_WORDS),
+        }
+    }
+
+    /// Filters a message, resolving user mentions with the users mentioned in it
+    pub fn filter_message(&self, msg: &Message) -> Option<String> {
+        self.run(
+            &msg.content,
+            Some(&|user_id: u64| {
+                msg.mentions
+                    .iter()
+                    .find(|user| user.id.get() == user_id)
+                    .map(|user| user.name.to_string())
+            }),
+        )
+    }
+
+    /// Filters a string that has no mention information attached to it,
+    /// like the lines of the Markov data set or an uploaded text file.
+    ///
+    /// User mentions can't be resolved so they're kept and
+    /// [`FilterStage::RemoveSymbols`] strips them like any other symbols.
+    pub fn filter_str(&self, text: &str) -> Option<String> {
+        self.run(text, None)
+    }
+
+    fn run(
+        &self,
+        text: &str,
+        resolve_user: Option<&dyn Fn(u64) -> Option<String>>,
+    ) -> Option<String> {
+        let mut filtered = text.to_owned();
+
+        for stage in &self.stages {
+            filtered = match stage {
+                FilterStage::RemoveLinks => LINK_REGEX.replace_all(&filtered, "").into_owned(),
+                FilterStage::ResolveMentions => match resolve_user {
+                    Some(resolve_user) => resolve_mentions(&filtered, resolve_user)?,
+                    None => ROLE_REGEX.replace_all(&filtered, " ").into_owned(),
+                },
+                FilterStage::RemoveEmotes => EMOTE_REGEX.replace_all(&filtered, " ").into_owned(),
+                FilterStage::RemoveSymbols => {
+                    let without_symbols = NON_ALPHANUMERIC_REGEX.replace_all(&filtered, " ");
+                    USER_ID_REGEX
+                        .replace_all(&without_symbols, " ")
+                        .into_owned()
+                }
+                FilterStage::Lowercase => lowercase_capitalized_words(filtered),
+                FilterStage::MinWordCount => {
+                    if filtered.split_whitespace().count() < self.min_words {
+                        return None;
+                    }
+                    filtered
+                }
+            };
+        }
+
+        let filtered = WHITESPACE_REGEX
+            .replace_all(&filtered, " ")
+            .trim()
+            .to_owned();
+
+        if filtered.is_empty() {
+            return None;
+        }
+
+        Some(filtered)
+    }
+}
+
+fn resolve_mentions(text: &str, resolve_user: &dyn Fn(u64) -> Option<String>) -> Option<String> {
+    let mut unresolved = false;
+
+    let resolved = USER_REGEX.replace_all(text, |caps: &Captures| {
+        let name = caps[1].parse::<u64>().ok().and_then(resolve_user);
+
+        if name.is_none() {
+            unresolved = true;
+        }
+
+        format!(" {} ", name.unwrap_or_default())
+    });
+
+    // Don't save the message to the chain if it can't replace the user mention with its name
+    if unresolved {
+        return None;
+    }
+
+    Some(ROLE_REGEX.replace_all(&resolved, " ").into_owned())
+}
+
+fn lowercase_capitalized_words(mut text: String) -> String {
+    // Lowercasing a match can expose a new capitalized word, e.g. "ABc" -> "Abc"
+    while UPPER_CASE_REGEX.is_match(&text) {
+        text = UPPER_CASE_REGEX
+            .replace_all(&text, |caps: &Captures| caps[0].to_lowercase())
+            .into_owned();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(disabled_stages: &[&str], min_words: i32) -> MarkovFilterSettings {
+        MarkovFilterSettings {
+            server_id: 0,
+            disabled_stages: disabled_stages.iter().map(|s| (*s).to_owned()).collect(),
+            min_words,
+        }
+    }
+
+    #[test]
+    fn removes_links_and_symbols() {
+        let pipeline = FilterPipeline::default();
+
+        assert_eq!(
+            pipeline.filter_str("check https://example.com/page out, it's great fun"),
+            Some("check out it's great fun".to_owned())
+        );
+    }
+
+    #[test]
+    fn lowercases_capitalized_words_but_not_all_caps() {
+        let pipeline = FilterPipeline::default();
+
+        assert_eq!(
+            pipeline.filter_str("Hello there I am VERY happy today"),
+            Some("hello there I am VERY happy today".to_owned())
+        );
+        assert_eq!(
+            lowercase_capitalized_words("ABc".to_owned()),
+            "abc".to_owned()
+        );
+    }
+
+    #[test]
+    fn keeps_strings_with_user_mentions() {
+        let pipeline = FilterPipeline::default();
+
+        assert_eq!(
+            pipeline.filter_str("hey <@123456789012345678> how are you doing"),
+            Some("hey how are you doing".to_owned())
+        );
+    }
+
+    #[test]
+    fn resolves_user_mentions_or_discards_the_text() {
+        let resolve_user = |user_id: u64| (user_id == 1).then(|| "bob".to_owned());
+
+        assert_eq!(
+            resolve_mentions("hi <@1> and <@!1>", &resolve_user),
+            Some("hi  bob  and  bob ".to_owned())
+        );
+        assert_eq!(resolve_mentions("hi <@2>", &resolve_user), None);
+    }
+
+    #[test]
+    fn removes_role_mentions_and_emotes() {
+        let pipeline = FilterPipeline::default();
+
+        assert_eq!(
+            pipeline.filter_str("hey <@&123456789012345678> how are you doing"),
+            Some("hey how are you doing".to_owned())
+        );
+        assert_eq!(
+            pipeline.filter_str("nice <:pog:123456789012345678> one my friend here"),
+            Some("nice one my friend here".to_owned())
+        );
+    }
+
+    #[test]
+    fn discards_texts_with_too_few_words() {
+        assert_eq!(FilterPipeline::default().filter_str("too short"), None);
+        assert_eq!(
+            FilterPipeline::from_settings(&settings(&[], 2)).filter_str("long enough"),
+            Some("long enough".to_owned())
+        );
+        assert_eq!(
+            FilterPipeline::from_settings(&settings(&["min-word-count"], 5)).filter_str("short"),
+            Some("short".to_owned())
+        );
+    }
+
+    #[test]
+    fn negative_min_words_falls_back_to_the_default() {
+        let pipeline = FilterPipeline::from_settings(&settings(&[], -1));
+
+        assert_eq!(pipeline.min_words, DEFAULT_MIN_NUM_OF_WORDS);
+    }
+
+    #[test]
+    fn skips_disabled_stages_and_ignores_unknown_ones() {
+        let pipeline = FilterPipeline::from_settings(&settings(&["lowercase", "not-a-stage"], 1));
+
+        assert!(!pipeline.stages.contains(&FilterStage::Lowercase));
+        assert_eq!(pipeline.stages.len(), FilterStage::iter().count() - 1);
+        assert_eq!(
+            pipeline.filter_str("Hello World"),
+            Some("Hello World".to_owned())
+        );
+    }
+
+    #[test]
+    fn always_collapses_whitespace() {
+        let disabled_stages = FilterStage::iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let pipeline = FilterPipeline::from_settings(&MarkovFilterSettings {
+            server_id: 0,
+            disabled_stages,
+            min_words: 0,
+        });
+
+        assert_eq!(
+            pipeline.filter_str("  a\n\nb   c "),
+            Some("a b c".to_owned())
+        );
+        assert_eq!(pipeline.filter_str(" \n "), None);
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Write;
+
+use anyhow::Context as _;
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use sqlx::PgPool;
+use tracing::{Instrument, info_span};
+
+use crate::client::global_data::GetBotState;
+
+use super::{
+    data_access::{
+        get_markov_chain_limits, get_markov_personas_by_server_id, upsert_markov_chain_limits,
+    },
+    model::DEFAULT_MAX_PERSONA_STATES,
+};
+
+#[allow(clippy::cast_precision_loss)]
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1}{}", UNITS[unit])
+}
+
+/// Lets admins choose how big the persona chains of the server can grow
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn markov_limits_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let limits = get_markov_chain_limits(guild_id.get() as i64, pool).await;
+    let mut min_transition_count = limits.as_ref().map_or(1, |l| l.min_transition_count);
+    let mut max_states = limits.map_or(DEFAULT_MAX_PERSONA_STATES, |l| l.max_states);
+
+    for option in &command.data.options {
+        match option.name.as_str() {
+            "min-count" => {
+                if let Some(value) = option.value.as_i64() {
+                    min_transition_count = value.try_into().unwrap_or(min_transition_count);
+                }
+            }
+            "max-states" => {
+                if let Some(value) = option.value.as_i64() {
+                    max_states = value.try_into().unwrap_or(max_states);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    upsert_markov_chain_limits(
+        guild_id.get() as i64,
+        min_transition_count,
+        max_states,
+        pool,
+    )
+    .await?;
+
+    let used_states = get_markov_personas_by_server_id(guild_id.get() as i64, pool)
+        .await
+        .iter()
+        .map(|p| i64::from(p.state_count))
+        .sum::<i64>();
+
+    let response = format!(
+        "Transitions seen fewer than {min_transition_count} times are pruned from personas.
+The personas of this server can have {max_states} states together and currently use {used_states}.
+The limits apply to personas uploaded from now on."
+    );
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(response),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+/// Reports how big the main chain and the server's personas are
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn markov_stats_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let mut response = {
+        let state = ctx.bot_state();
+        let chain = &state.read().await.markov_chain;
+
+        format!(
+            "The main chain has {} states and {} distinct words, taking up about {}",
+            chain.state_count(),
+            chain.token_count(),
+            format_bytes(chain.estimated_memory_usage())
+        )
+    };
+
+    if let Some(guild_id) = command.guild_id {
+        let personas = get_markov_personas_by_server_id(guild_id.get() as i64, pool).await;
+        let max_states = get_markov_chain_limits(guild_id.get() as i64, pool)
+            .await
+            .map_or(DEFAULT_MAX_PERSONA_STATES, |l| l.max_states);
+
+        let state_count = personas
+            .iter()
+            .map(|p| i64::from(p.state_count))
+            .sum::<i64>();
+        let estimated_bytes = personas.iter().map(|p| p.estimated_bytes).sum::<i64>();
+
+        write!(
+            response,
+            "\nThis server has {} personas with {state_count} of {max_states} states, taking up about {}",
+            personas.len(),
+            format_bytes(estimated_bytes.try_into().unwrap_or_default())
+        )?;
+    }
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(response),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
@@ -1,165 +1,677 @@
-use regex::{Captures, Regex};
-use serenity::model::channel::Message;
+use std::{cmp::Reverse, collections::HashMap, env, sync::LazyLock, time::Instant};
 
-const MIN_NUM_OF_WORDS: usize = 5;
+use rand::Rng;
+use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::{Span, info_span, instrument};
 
-/// Filters a message so it can be inserted into the Markov data set.
+/// The smallest number of texts a shard of a parallel build learns.
+/// Smaller shards spend more time merging than they save by running in parallel.
+const MIN_SHARD_SIZE: usize = 1000;
+
+/// Texts are split into tokens with the same regex as `markov_str` so the chain learns
+/// and generates the same words it always did
+static WORD_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(markov_str::WORD_REGEX).unwrap());
+
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    WORD_REGEX.find_iter(text).map(|m| m.as_str())
+}
+
+/// An interned token, the index of the token in [`MarkovChain::tokens`]
+type Token = u32;
+/// The last `state_size` tokens of a text
+type State = Box<[Token]>;
+
+/// How big a chain is allowed to grow
+#[derive(Debug, Clone, Copy)]
+pub struct ChainLimits {
+    /// Transitions seen fewer times than this are pruned
+    pub min_transition_count: u32,
+    /// Only the most common states are kept if the chain has more than this many
+    pub max_states: Option<usize>,
+}
+
+impl Default for ChainLimits {
+    fn default() -> Self {
+        Self {
+            min_transition_count: 1,
+            max_states: None,
+        }
+    }
+}
+
+impl ChainLimits {
+    /// Reads the limits of the main chain from the `MARKOV_MIN_TRANSITION_COUNT`
+    /// and `MARKOV_MAX_STATES` environment variables
+    pub fn from_env() -> Self {
+        Self {
+            min_transition_count: env::var("MARKOV_MIN_TRANSITION_COUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
+            max_states: env::var("MARKOV_MAX_STATES")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+/// The tokens that followed a state and how many times they did
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+struct Transitions {
+    /// Most states are followed by only a few different tokens,
+    /// so a vec is both smaller and faster than a map
+    next: Vec<(Token, u32)>,
+    /// How many times a text ended after the state
+    end: u32,
+}
+
+impl Transitions {
+    fn add(&mut self, token: Token, count: u32) {
+        match self.next.iter_mut().find(|(t, _)| *t == token) {
+            Some((_, c)) => *c += count,
+            None => self.next.push((token, count)),
+        }
+    }
+
+    fn total(&self) -> u32 {
+        self.end + self.next.iter().map(|(_, c)| c).sum::<u32>()
+    }
+
+    /// Picks the next token weighted by how many times it was seen.
+    /// Returns [`None`] if the text should end.
+    fn pick(&self, rng: &mut impl Rng) -> Option<Token> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0..total);
+        if roll < self.end {
+            return None;
+        }
+        roll -= self.end;
+
+        for (token, count) in &self.next {
+            if roll < *count {
+                return Some(*token);
+            }
+            roll -= count;
+        }
+
+        None
+    }
+}
+
+/// A Markov chain over the words of [`markov_str::WORD_REGEX`] that counts how often every transition is seen.
 ///
-/// Removes links, User IDs, emotes, animated emotes, non alphanumeric characters, line feeds, extra whitespace, and role IDs.
+/// Counting transitions instead of storing every occurrence means chains that learned different
+/// texts can be merged by adding up their counts, which is what lets [`MarkovChain::build_parallel`]
+/// learn shards of the data set on separate threads. It also lets [`MarkovChain::apply_limits`]
+/// tell rare transitions apart from common ones.
 ///
-/// Replaces uppercase letters with their lowercase variants.
-pub fn filter_message_for_markov_file(msg: &Message) -> Option<String> {
-    let mut filtered_message = remove_links(msg);
+/// Every distinct token is stored once and states refer to tokens by their index.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(from = "SerializedChain")]
+pub struct MarkovChain {
+    state_size: usize,
+    tokens: Vec<String>,
+    #[serde(skip)]
+    token_ids: HashMap<String, Token>,
+    /// The first state of every text and how many texts started with it
+    #[serde(serialize_with = "serialize_map_as_seq")]
+    starts: HashMap<State, u32>,
+    #[serde(serialize_with = "serialize_map_as_seq")]
+    transitions: HashMap<State, Transitions>,
+}
 
-    let user_regex = Regex::new(r"<@!?(\d+)>").expect("Invalid regular expression");
-    let regexes_to_replace_with_whitespace = create_regexes_to_replace_with_whitespace();
-    let upper_case_regex = Regex::new(r"[A-Z][a-z0-9_-]{1,}").expect("Invalid regular expression");
+/// JSON maps can only have string keys, so the maps keyed by states are stored as lists of pairs
+#[derive(Deserialize)]
+struct SerializedChain {
+    state_size: usize,
+    tokens: Vec<String>,
+    #[serde(deserialize_with = "deserialize_map_from_seq")]
+    starts: HashMap<State, u32>,
+    #[serde(deserialize_with = "deserialize_map_from_seq")]
+    transitions: HashMap<State, Transitions>,
+}
 
-    loop {
-        let mut number_of_matches: u16 = 0;
+impl From<SerializedChain> for MarkovChain {
+    fn from(chain: SerializedChain) -> Self {
+        Self {
+            state_size: chain.state_size,
+            token_ids: token_ids(&chain.tokens),
+            tokens: chain.tokens,
+            starts: chain.starts,
+            transitions: chain.transitions,
+        }
+    }
+}
 
-        while user_regex.is_match(&filtered_message) {
-            number_of_matches += 1;
+fn serialize_map_as_seq<S: Serializer, V: Serialize>(
+    map: &HashMap<State, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(map)
+}
 
-            if cant_find_user_in_message(&user_regex, &filtered_message, msg) {
-                // Don't save the message to the chain if it can't replace the user mention with it's name
-                return None;
-            }
+fn deserialize_map_from_seq<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<HashMap<State, V>, D::Error> {
+    Ok(Vec::<(State, V)>::deserialize(deserializer)?
+        .into_iter()
+        .collect())
+}
 
-            filtered_message = user_regex
-                .replace(&filtered_message, |caps: &Captures| {
-                    let mut user_id = String::new();
+fn to_token(id: usize) -> Token {
+    Token::try_from(id).expect("A chain shouldn't have more than u32::MAX distinct tokens")
+}
+
+fn token_ids(tokens: &[String]) -> HashMap<String, Token> {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(id, token)| (token.clone(), to_token(id)))
+        .collect()
+}
+
+impl MarkovChain {
+    pub fn new(state_size: usize) -> Self {
+        Self {
+            state_size,
+            tokens: vec![],
+            token_ids: HashMap::new(),
+            starts: HashMap::new(),
+            transitions: HashMap::new(),
+        }
+    }
 
-                    for char in caps[0].chars() {
-                        if char.is_ascii_digit() {
-                            user_id += &char.to_string();
-                        }
+    /// Builds a chain by learning the texts in parallel shards and merging the shards together
+    #[instrument(skip(texts), fields(texts = texts.len(), shards, states, elapsed_ms))]
+    pub fn build_parallel<S: AsRef<str> + Sync>(state_size: usize, texts: &[S]) -> Self {
+        let started = Instant::now();
+        let span = Span::current();
+
+        let shard_size = texts
+            .len()
+            .div_ceil(rayon::current_num_threads())
+            .max(MIN_SHARD_SIZE);
+        span.record("shards", texts.len().div_ceil(shard_size));
+
+        let chain = texts
+            .par_chunks(shard_size)
+            .map(|shard| {
+                info_span!(parent: &span, "Build shard", texts = shard.len()).in_scope(|| {
+                    let mut chain = Self::new(state_size);
+                    for text in shard {
+                        chain.add_text(text.as_ref());
                     }
-                    let user_id = user_id.parse::<u64>().expect("Couldn't parse user id");
-                    let user = msg
-                        .mentions
-                        .iter()
-                        .find(|&user| user.id.get() == user_id)
-                        .map(|u| u.name.clone())
-                        .unwrap_or_default();
-
-                    format!(" {user} ")
+                    chain
                 })
-                .into_owned();
+            })
+            .reduce(
+                || Self::new(state_size),
+                |mut a, b| {
+                    info_span!(parent: &span, "Merge shards").in_scope(|| a.merge(b));
+                    a
+                },
+            );
+
+        span.record("states", chain.transitions.len());
+        span.record("elapsed_ms", started.elapsed().as_millis());
+
+        chain
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn token_count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    fn intern(&mut self, token: &str) -> Token {
+        if let Some(id) = self.token_ids.get(token) {
+            return *id;
+        }
+
+        let id = to_token(self.tokens.len());
+        self.tokens.push(token.to_owned());
+        self.token_ids.insert(token.to_owned(), id);
+        id
+    }
+
+    /// Learns a text. Texts with fewer tokens than the state size are ignored.
+    pub fn add_text(&mut self, text: &str) {
+        let words = tokenize(text).collect::<Vec<_>>();
+        if words.len() < self.state_size {
+            return;
+        }
+
+        // Only interned once the text is known to be learned, so ignored texts leave no tokens behind
+        let tokens = words
+            .into_iter()
+            .map(|t| self.intern(t))
+            .collect::<Vec<_>>();
+
+        *self
+            .starts
+            .entry(tokens[..self.state_size].into())
+            .or_default() += 1;
+
+        for window in tokens.windows(self.state_size + 1) {
+            let (state, next) = window.split_at(self.state_size);
+            self.transitions
+                .entry(state.into())
+                .or_default()
+                .add(next[0], 1);
+        }
+
+        self.transitions
+            .entry(tokens[tokens.len() - self.state_size..].into())
+            .or_default()
+            .end += 1;
+    }
+
+    /// Adds everything the other chain learned to this one
+    pub fn merge(&mut self, mut other: Self) {
+        // Merging the smaller chain into the bigger one moves less data around
+        if self.transitions.len() < other.transitions.len() {
+            std::mem::swap(self, &mut other);
+        }
+
+        // The other chain interned its tokens in a different order
+        let remap = other
+            .tokens
+            .iter()
+            .map(|t| self.intern(t))
+            .collect::<Vec<_>>();
+        let remap_state =
+            |state: &[Token]| -> State { state.iter().map(|t| remap[*t as usize]).collect() };
+
+        for (state, count) in other.starts {
+            *self.starts.entry(remap_state(&state)).or_default() += count;
+        }
+
+        for (state, transitions) in other.transitions {
+            let entry = self.transitions.entry(remap_state(&state)).or_default();
+            for (token, count) in transitions.next {
+                entry.add(remap[token as usize], count);
+            }
+            entry.end += transitions.end;
+        }
+    }
+
+    /// Prunes rare transitions and keeps only the most common states until the chain fits the limits
+    #[instrument(skip(self), fields(states_before = self.state_count(), states, estimated_bytes))]
+    pub fn apply_limits(&mut self, limits: ChainLimits) {
+        if limits.min_transition_count <= 1
+            && limits
+                .max_states
+                .is_none_or(|max_states| self.transitions.len() <= max_states)
+        {
+            return;
+        }
+
+        if limits.min_transition_count > 1 {
+            for transitions in self.transitions.values_mut() {
+                transitions
+                    .next
+                    .retain(|(_, count)| *count >= limits.min_transition_count);
+                if transitions.end < limits.min_transition_count {
+                    transitions.end = 0;
+                }
+            }
+            self.transitions.retain(|_, t| t.total() > 0);
+            self.starts
+                .retain(|_, count| *count >= limits.min_transition_count);
         }
-        for regex in &regexes_to_replace_with_whitespace {
-            while regex.is_match(&filtered_message) {
-                number_of_matches += 1;
-                filtered_message = regex.replace_all(&filtered_message, " ").into_owned();
+
+        if let Some(max_states) = limits.max_states
+            && self.transitions.len() > max_states
+        {
+            let mut totals = self
+                .transitions
+                .iter()
+                .map(|(state, t)| (state.clone(), t.total()))
+                .collect::<Vec<_>>();
+            totals.sort_unstable_by_key(|(_, total)| Reverse(*total));
+            totals.truncate(max_states);
+
+            let kept = totals
+                .into_iter()
+                .map(|(state, _)| state)
+                .collect::<Vec<_>>();
+            let mut transitions = HashMap::with_capacity(kept.len());
+            for state in kept {
+                let value = self.transitions.remove(&state).unwrap_or_default();
+                transitions.insert(state, value);
             }
+            self.transitions = transitions;
         }
-        while upper_case_regex.is_match(&filtered_message) {
-            number_of_matches += 1;
-            filtered_message = upper_case_regex
-                .replace(&filtered_message, |caps: &Captures| caps[0].to_lowercase())
-                .into_owned();
+
+        // Transitions into pruned states simply end the text, but starts need a state to begin from
+        self.starts
+            .retain(|state, _| self.transitions.contains_key(state));
+
+        self.remove_unused_tokens();
+
+        let span = Span::current();
+        span.record("states", self.state_count());
+        span.record("estimated_bytes", self.estimated_memory_usage());
+    }
+
+    /// Rebuilds the token list without the tokens that were only used by pruned states
+    fn remove_unused_tokens(&mut self) {
+        let mut used = vec![false; self.tokens.len()];
+        for (state, transitions) in &self.transitions {
+            for token in state {
+                used[*token as usize] = true;
+            }
+            for (token, _) in &transitions.next {
+                used[*token as usize] = true;
+            }
         }
-        if number_of_matches == 0 {
-            break;
+
+        let mut remap = vec![0; self.tokens.len()];
+        let mut tokens = Vec::with_capacity(used.iter().filter(|u| **u).count());
+        for (id, token) in std::mem::take(&mut self.tokens).into_iter().enumerate() {
+            if used[id] {
+                remap[id] = to_token(tokens.len());
+                tokens.push(token);
+            }
         }
+
+        let remap_state =
+            |state: State| -> State { state.iter().map(|t| remap[*t as usize]).collect() };
+
+        self.starts = std::mem::take(&mut self.starts)
+            .into_iter()
+            .map(|(state, count)| (remap_state(state), count))
+            .collect();
+        self.transitions = std::mem::take(&mut self.transitions)
+            .into_iter()
+            .map(|(state, mut transitions)| {
+                for (token, _) in &mut transitions.next {
+                    *token = remap[*token as usize];
+                }
+                (remap_state(state), transitions)
+            })
+            .collect();
+
+        self.token_ids = token_ids(&tokens);
+        self.tokens = tokens;
     }
 
-    if filtered_message.trim().split(' ').count() < MIN_NUM_OF_WORDS {
-        return None;
+    /// Roughly how many bytes the chain takes up in memory.
+    ///
+    /// Counts the tokens, states and transitions but not the allocator's own overhead,
+    /// so the real usage is somewhat higher.
+    pub fn estimated_memory_usage(&self) -> usize {
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|t| 2 * (size_of::<String>() + t.capacity()) + size_of::<Token>())
+            .sum::<usize>();
+        let state = size_of::<State>() + self.state_size * size_of::<Token>();
+        // Every hash map entry also has a control byte
+        let starts = self.starts.capacity() * (state + size_of::<u32>() + 1);
+        let transitions = self.transitions.capacity() * (state + size_of::<Transitions>() + 1)
+            + self
+                .transitions
+                .values()
+                .map(|t| t.next.capacity() * size_of::<(Token, u32)>())
+                .sum::<usize>();
+
+        size_of::<Self>() + tokens + starts + transitions
     }
 
-    Some(filtered_message.trim().to_owned())
-}
+    /// Generates a text of at most `max_tokens` tokens from a random start state
+    pub fn generate(&self, max_tokens: usize, rng: &mut impl Rng) -> Option<String> {
+        let start = pick_weighted(&self.starts, rng)?;
+        let mut output = start.to_vec();
+
+        self.walk(start.to_vec(), max_tokens, &mut output, rng);
 
-fn remove_links(msg: &Message) -> String {
-    let re =
-    Regex::new(r#"(?:(?:https?|ftp)://|\b(?:[a-z\d]+\.))(?:(?:[^\s()<>]+|\((?:[^\s()<>]+|(?:\([^\s()<>]+\)))?\))+(?:\((?:[^\s()<>]+|(?:\(?:[^\s()<>]+\)))?\)|[^\s`!()\[\]{};:'".,<>?«»“”‘’]))?"#)
-    .expect("Invalid regular expression");
-    let mut str = re.replace_all(&msg.content, "").into_owned();
-    while str.ends_with(' ') {
-        str.pop();
+        Some(self.join(&output))
     }
-    str
-}
 
-fn create_regexes_to_replace_with_whitespace() -> Vec<Regex> {
-    let regexes_to_replace_with_whitespace: Vec<Regex> = vec![
-        Regex::new(r"<:?(\w+:)(\d+)>").expect("Invalid regular expression"), //emote regex
-        Regex::new(r"<a:?(\w+:)(\d+)>").expect("Invalid regular expression"), //animated emote regex
-        Regex::new(r#"[,.!"\#$()=?*<>{}\[\]\\\|Łł@*;:+~ˇ^˘°˛`´˝]"#)
-            .expect("Invalid regular expression"), //non alphanumeric regex
-        Regex::new(r"^(\d{18})$").expect("Invalid regular expression"), //remaining numbers from users regex
-        Regex::new(r"\n").expect("Invalid regular expression"),         //line feed regex
-        Regex::new(r"[ ]{3}|[ ]{2}").expect("Invalid regular expression"), //double and triple whitespace regex
-        Regex::new(r"<@&(\d+)>").expect("Invalid regular expression"),     // role regex
-    ];
-    regexes_to_replace_with_whitespace
-}
+    /// Continues the given text with at most `max_tokens` tokens.
+    ///
+    /// The returned text doesn't include `start`. If `start` is shorter than the state size
+    /// a state beginning with it is picked at random.
+    pub fn generate_start(
+        &self,
+        start: &str,
+        max_tokens: usize,
+        rng: &mut impl Rng,
+    ) -> Option<String> {
+        let words = tokenize(start).collect::<Vec<_>>();
+        if words.is_empty() {
+            return self.generate(max_tokens, rng);
+        }
 
-fn cant_find_user_in_message(user_regex: &Regex, filtered_message: &str, msg: &Message) -> bool {
-    user_regex
-        .captures_iter(filtered_message)
-        .map(|caps| {
-            let mut user_id = String::new();
+        // A word the chain has never seen can't start anything
+        let tokens = words
+            .iter()
+            .map(|w| self.token_ids.get(*w).copied())
+            .collect::<Option<Vec<_>>>()?;
 
-            for char in caps[0].chars() {
-                if char.is_ascii_digit() {
-                    user_id += &char.to_string();
-                }
-            }
-            user_id.parse::<u64>().expect("Couldn't parse user id")
-        })
-        .any(|user_id| msg.mentions.iter().any(|user| user.id.get() == user_id))
-}
-/// Filters a string so it can be inserted into the Markov data set.
-///
-/// Removes links, User IDs, emotes, animated emotes, non alphanumeric characters, line feeds, extra whitespace, and role IDs.
-///
-/// Replaces uppercase letters with their lowercase variants.
-pub fn filter_string_for_markov_file(msg: &str) -> String {
-    let re =
-    Regex::new(r#"(?:(?:https?|ftp)://|\b(?:[a-z\d]+\.))(?:(?:[^\s()<>]+|\((?:[^\s()<>]+|(?:\([^\s()<>]+\)))?\))+(?:\((?:[^\s()<>]+|(?:\(?:[^\s()<>]+\)))?\)|[^\s`!()\[\]{};:'".,<>?«»“”‘’]))?"#)
-    .expect("Invalid regular expression");
-
-    let mut str = re.replace_all(msg, "").into_owned();
-    while str.ends_with(' ') {
-        str.pop();
-    }
-
-    let mut filtered_message = str;
-
-    let regexes_to_replace_with_whitespace: Vec<Regex> = vec![
-        Regex::new(r"<:?(\w+:)(\d+)>").expect("Invalid regular expression"), //emote regex
-        Regex::new(r"<a:?(\w+:)(\d+)>").expect("Invalid regular expression"), //animated emote regex
-        Regex::new(r#"[,.!"\#$()=?*<>{}\[\]\\\|Łł@*;:+~ˇ^˘°˛`´˝]"#)
-            .expect("Invalid regular expression"), //non alphanumeric regex
-        Regex::new(r"^(\d{18})$").expect("Invalid regular expression"), //remaining numbers from users regex
-        Regex::new(r"\n").expect("Invalid regular expression"),         //line feed regex
-        Regex::new(r"[ ]{3}|[ ]{2}").expect("Invalid regular expression"), //double and triple whitespace regex
-        Regex::new(r"<@&(\d+)>").expect("Invalid regular expression"),     // role regex
-    ];
-
-    let upper_case_regex = Regex::new(r"[A-Z][a-z0-9_-]{1,}").expect("Invalid regular expression");
-
-    loop {
-        let mut number_of_matches: u16 = 0;
-
-        for regex in &regexes_to_replace_with_whitespace {
-            while regex.is_match(&filtered_message) {
-                number_of_matches += 1;
-                filtered_message = regex.replace_all(&filtered_message, " ").into_owned();
+        let mut output = vec![];
+
+        let state = if tokens.len() >= self.state_size {
+            tokens[tokens.len() - self.state_size..].to_vec()
+        } else {
+            let candidates = self
+                .transitions
+                .keys()
+                .filter(|state| state.starts_with(&tokens))
+                .collect::<Vec<_>>();
+
+            if candidates.is_empty() {
+                return None;
             }
+
+            let state = candidates[rng.gen_range(0..candidates.len())];
+            output.extend_from_slice(&state[tokens.len()..]);
+
+            state.to_vec()
+        };
+
+        self.walk(state, max_tokens, &mut output, rng);
+
+        if output.is_empty() {
+            return None;
         }
-        while upper_case_regex.is_match(&filtered_message) {
-            number_of_matches += 1;
-            filtered_message = upper_case_regex
-                .replace(&filtered_message, |caps: &Captures| caps[0].to_lowercase())
-                .into_owned();
+
+        Some(self.join(&output))
+    }
+
+    /// Follows transitions from the state until the text ends or `output` holds `max_tokens` tokens
+    fn walk(
+        &self,
+        mut state: Vec<Token>,
+        max_tokens: usize,
+        output: &mut Vec<Token>,
+        rng: &mut impl Rng,
+    ) {
+        while output.len() < max_tokens {
+            let Some(transitions) = self.transitions.get(state.as_slice()) else {
+                return;
+            };
+            let Some(token) = transitions.pick(rng) else {
+                return;
+            };
+
+            state.remove(0);
+            state.push(token);
+            output.push(token);
         }
-        if number_of_matches == 0 {
-            break;
+    }
+
+    fn join(&self, tokens: &[Token]) -> String {
+        tokens
+            .iter()
+            .map(|t| self.tokens[*t as usize].as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn pick_weighted<'a>(items: &'a HashMap<State, u32>, rng: &mut impl Rng) -> Option<&'a [Token]> {
+    let total = items.values().sum::<u32>();
+    if total == 0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0..total);
+    for (item, count) in items {
+        if roll < *count {
+            return Some(&item[..]);
         }
+        roll -= count;
     }
 
-    filtered_message.trim().to_owned()
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(chain: &MarkovChain, words: &[&str]) -> State {
+        words.iter().map(|w| chain.token_ids[*w]).collect()
+    }
+
+    fn transitions<'a>(chain: &'a MarkovChain, words: &[&str]) -> Option<&'a Transitions> {
+        chain.transitions.get(&state(chain, words))
+    }
+
+    fn next_count(chain: &MarkovChain, words: &[&str], next: &str) -> Option<u32> {
+        let next = chain.token_ids.get(next)?;
+        transitions(chain, words)?
+            .next
+            .iter()
+            .find(|(token, _)| token == next)
+            .map(|(_, count)| *count)
+    }
+
+    #[test]
+    fn add_text_counts_transitions_starts_and_ends() {
+        let mut chain = MarkovChain::new(2);
+        chain.add_text("the cat sat");
+        chain.add_text("the cat sat");
+        chain.add_text("the cat ran");
+
+        assert_eq!(chain.token_count(), 4);
+        assert_eq!(chain.starts[&state(&chain, &["the", "cat"])], 3);
+        assert_eq!(next_count(&chain, &["the", "cat"], "sat"), Some(2));
+        assert_eq!(next_count(&chain, &["the", "cat"], "ran"), Some(1));
+        assert_eq!(transitions(&chain, &["cat", "sat"]).unwrap().end, 2);
+        assert_eq!(transitions(&chain, &["cat", "ran"]).unwrap().end, 1);
+    }
+
+    #[test]
+    fn merge_adds_up_counts_of_chains_with_different_tokens() {
+        let mut a = MarkovChain::new(2);
+        a.add_text("the cat sat");
+        a.add_text("a dog ran");
+
+        let mut b = MarkovChain::new(2);
+        b.add_text("a dog sat");
+        b.add_text("the cat sat");
+
+        a.merge(b);
+
+        assert_eq!(a.token_count(), 6);
+        assert_eq!(a.starts[&state(&a, &["the", "cat"])], 2);
+        assert_eq!(a.starts[&state(&a, &["a", "dog"])], 2);
+        assert_eq!(next_count(&a, &["the", "cat"], "sat"), Some(2));
+        assert_eq!(next_count(&a, &["a", "dog"], "ran"), Some(1));
+        assert_eq!(next_count(&a, &["a", "dog"], "sat"), Some(1));
+        assert_eq!(transitions(&a, &["cat", "sat"]).unwrap().end, 2);
+        assert_eq!(transitions(&a, &["dog", "sat"]).unwrap().end, 1);
+    }
+
+    #[test]
+    fn merge_into_a_smaller_chain_keeps_everything() {
+        let mut small = MarkovChain::new(1);
+        small.add_text("one two");
+
+        let mut big = MarkovChain::new(1);
+        big.add_text("three four five six");
+
+        small.merge(big);
+
+        assert_eq!(small.token_count(), 6);
+        assert_eq!(small.state_count(), 6);
+        assert_eq!(next_count(&small, &["one"], "two"), Some(1));
+        assert_eq!(next_count(&small, &["five"], "six"), Some(1));
+    }
+
+    #[test]
+    fn add_text_ignores_short_texts_without_interning_their_tokens() {
+        let mut chain = MarkovChain::new(2);
+        chain.add_text("hello");
+
+        assert_eq!(chain.token_count(), 0);
+        assert_eq!(chain.state_count(), 0);
+        assert!(chain.starts.is_empty());
+    }
+
+    #[test]
+    fn apply_limits_prunes_rare_transitions_and_end_counts() {
+        let mut chain = MarkovChain::new(2);
+        chain.add_text("the cat sat");
+        chain.add_text("the cat sat");
+        chain.add_text("the cat ran");
+        chain.add_text("a dog ran");
+
+        chain.apply_limits(ChainLimits {
+            min_transition_count: 2,
+            max_states: None,
+        });
+
+        assert_eq!(next_count(&chain, &["the", "cat"], "sat"), Some(2));
+        assert_eq!(next_count(&chain, &["the", "cat"], "ran"), None);
+        assert_eq!(transitions(&chain, &["cat", "sat"]).unwrap().end, 2);
+        // Every transition into or out of these words was seen only once
+        assert!(!chain.token_ids.contains_key("ran"));
+        assert!(!chain.token_ids.contains_key("dog"));
+        assert_eq!(chain.state_count(), 2);
+        assert_eq!(chain.starts.len(), 1);
+        assert_eq!(chain.token_count(), 3);
+    }
+
+    #[test]
+    fn apply_limits_keeps_the_most_common_states() {
+        let mut chain = MarkovChain::new(1);
+        chain.add_text("common word");
+        chain.add_text("common word");
+        chain.add_text("rare");
+
+        chain.apply_limits(ChainLimits {
+            min_transition_count: 1,
+            max_states: Some(2),
+        });
+
+        assert_eq!(chain.state_count(), 2);
+        assert!(transitions(&chain, &["common"]).is_some());
+        assert!(transitions(&chain, &["word"]).is_some());
+        assert!(!chain.token_ids.contains_key("rare"));
+        assert_eq!(chain.starts.len(), 1);
+    }
+
+    #[test]
+    fn apply_limits_leaves_chains_under_the_limits_alone() {
+        let mut chain = MarkovChain::new(1);
+        chain.add_text("one two");
+
+        chain.apply_limits(ChainLimits::default());
+
+        assert_eq!(chain.state_count(), 2);
+        assert_eq!(chain.token_count(), 2);
+    }
 }
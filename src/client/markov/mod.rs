@@ -1,10 +1,14 @@
 pub mod commands;
 mod data_access;
+mod direct_messages;
 mod file_operations;
-mod markov_chain;
+mod filter;
+mod limits;
+pub mod markov_chain;
 pub mod model;
+mod persona;
 
-use crate::client::global_data::GetBotState;
+use crate::client::{get_option_from_command::GetOptionFromCommand, global_data::GetBotState};
 
 use self::{
     data_access::{
@@ -12,32 +16,48 @@ use self::{
         create_markov_blacklisted_user, delete_markov_blacklisted_channel,
         delete_markov_blacklisted_server, delete_markov_blacklisted_user,
         get_markov_blacklisted_channel, get_markov_blacklisted_server, get_markov_blacklisted_user,
+        get_markov_filter_settings, upsert_markov_filter_settings,
     },
-    file_operations::{get_messages_from_file, import_chain_from_file},
-    markov_chain::filter_message_for_markov_file,
-    model::{MARKOV_EXPORT_PATH, replace_markov_chain_lock},
+    direct_messages::add_direct_message_to_chain,
+    file_operations::{
+        backup_legacy_export, clean_markov_file, generate_new_chain_from_msg_file,
+        import_chain_from_file, is_legacy_export,
+    },
+    filter::{DEFAULT_MIN_NUM_OF_WORDS, FilterStage},
+    markov_chain::MarkovChain,
+    model::{DmLearningMode, MARKOV_EXPORT_PATH, MarkovFilterSettings, replace_markov_chain_lock},
+    persona::generate_persona_sentence,
+};
+pub use direct_messages::{
+    PersonalChain, generate_reply, get_dm_learning_mode, markov_dms_command,
 };
-use markov_str::MarkovChain;
+pub use filter::FilterPipeline;
+pub use limits::{markov_limits_command, markov_stats_command};
+pub use persona::{delete_persona_command, list_personas_command, upload_persona_command};
 use rand::Rng;
-use regex::Regex;
 use serenity::{
     all::Context,
-    all::{CommandInteraction, CreateInteractionResponseMessage, User},
+    all::{
+        CommandInteraction, CreateAllowedMentions, CreateInteractionResponseMessage, GuildId, User,
+    },
     builder::CreateInteractionResponse,
     model::channel::Message,
 };
 use sqlx::{PgPool, Pool, Postgres};
-use std::fs;
-use tracing::{Instrument, info_span, instrument};
+use std::{env, fmt::Write, str::FromStr, sync::Arc};
+use strum::IntoEnumIterator;
+use tracing::{Instrument, info, info_span, instrument, warn};
 
+/// `dm_learning_mode` is the mode of the author if the message is a DM
 pub async fn add_message_to_chain(
     msg: &Message,
+    dm_learning_mode: Option<DmLearningMode>,
     ctx: &Context,
     pool: &Pool<Postgres>,
 ) -> Result<bool, std::io::Error> {
     // if the message was not sent in a guild
     let Some(guild_id) = msg.guild_id else {
-        return Ok(false);
+        return add_direct_message_to_chain(msg, dm_learning_mode, ctx, pool).await;
     };
 
     let markov_blacklisted_user =
@@ -55,7 +75,9 @@ pub async fn add_message_to_chain(
         return Ok(false);
     }
 
-    let filtered_message = filter_message_for_markov_file(msg);
+    let filter_pipeline = get_filter_pipeline(ctx, guild_id, pool).await;
+
+    let filtered_message = filter_pipeline.filter_message(msg);
     if let Some(filtered_message) = filtered_message {
         file_operations::append_to_markov_file(&filtered_message)?;
 
@@ -73,11 +95,42 @@ pub async fn add_message_to_chain(
     }
 }
 
+/// The filters of the guild are read from the database the first time they're needed
+/// and kept in [`BotState`][crate::client::global_data::BotState] until they're changed
+pub async fn get_filter_pipeline(
+    ctx: &Context,
+    guild_id: GuildId,
+    pool: &PgPool,
+) -> Arc<FilterPipeline> {
+    if let Some(pipeline) = ctx.bot_state().read().await.markov_filters.get(&guild_id) {
+        return pipeline.clone();
+    }
+
+    let pipeline = Arc::new(
+        get_markov_filter_settings(guild_id.get() as i64, pool)
+            .await
+            .map(|settings| FilterPipeline::from_settings(&settings))
+            .unwrap_or_default(),
+    );
+
+    ctx.bot_state()
+        .write()
+        .await
+        .markov_filters
+        .entry(guild_id)
+        .or_insert(pipeline)
+        .clone()
+}
+
 #[tracing::instrument(skip(ctx))]
 pub async fn generate_sentence(ctx: &Context, start: Option<&str>) -> String {
     let lock = ctx.bot_state();
     let chain = &lock.read().await.markov_chain;
 
+    generate_from_chain(chain, start)
+}
+
+fn generate_from_chain(chain: &MarkovChain, start: Option<&str>) -> String {
     let output = match start {
         Some(start) => chain
             .generate_start(
@@ -85,7 +138,11 @@ pub async fn generate_sentence(ctx: &Context, start: Option<&str>) -> String {
                 rand::thread_rng().gen_range(2..50),
                 &mut rand::thread_rng(),
             )
-            .map(|o| start.to_owned() + " " + &o),
+            .map(|o| start.to_owned() + " " + &o)
+            // Start over if the chain never saw the words the message ends with
+            .or_else(|| {
+                chain.generate(rand::thread_rng().gen_range(2..50), &mut rand::thread_rng())
+            }),
         None => chain.generate(rand::thread_rng().gen_range(2..50), &mut rand::thread_rng()),
     };
 
@@ -101,39 +158,70 @@ pub async fn generate_sentence(ctx: &Context, start: Option<&str>) -> String {
     }
 }
 
+/// Generates a sentence from the main chain or from one of the guild's personas
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn markov_generate_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let persona = command.data.get_optional_string("persona");
+
+    let response = match (persona, command.guild_id) {
+        (None, _) => generate_sentence(ctx, None).await,
+        (Some(_), None) => "Personas only exist in servers".to_owned(),
+        (Some(name), Some(guild_id)) => generate_persona_sentence(ctx, guild_id, &name, pool)
+            .await?
+            .unwrap_or_else(|| format!("There's no persona called ``{name}``")),
+    };
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(response)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
 #[instrument]
 /// Initializes the Markov chain from [`MARKOV_EXPORT_PATH`][model::MARKOV_EXPORT_PATH]
+///
+/// If the `CLEAN_MARKOV_DATA` environment variable is set to `true` the data set is first
+/// re-filtered with the current [`FilterPipeline`] and the chain is rebuilt from it.
 pub fn init() -> anyhow::Result<MarkovChain> {
-    let mut markov_chain = create_default_chain();
+    if env::var("CLEAN_MARKOV_DATA").is_ok_and(|v| v == "true") {
+        clean_markov_file()?;
+        return generate_new_chain_from_msg_file();
+    }
 
     if !std::path::Path::new(MARKOV_EXPORT_PATH).exists() {
-        let messages = get_messages_from_file()?;
-
-        info_span!("Add markov data to corpus").in_scope(|| {
-            for msg in messages {
-                markov_chain.add_text(&msg);
-            }
-        });
-
-        let export = serde_json::to_string(&markov_chain)?;
-        fs::write(MARKOV_EXPORT_PATH, export)?;
-
-        return Ok(markov_chain);
+        return generate_new_chain_from_msg_file();
     }
 
-    markov_chain = import_chain_from_file()?;
+    import_chain_from_file().or_else(|e| {
+        if is_legacy_export() {
+            info!("Converting the markov_str export to the current chain format");
+            backup_legacy_export()?;
+        } else {
+            warn!("Couldn't import the Markov chain, rebuilding it from the data set: {e:?}");
+        }
 
-    Ok(markov_chain)
+        generate_new_chain_from_msg_file()
+    })
 }
 
 pub const MARKOV_STATE_SIZE: usize = 4;
 
 #[instrument]
-fn create_default_chain() -> MarkovChain {
-    MarkovChain::new(
-        MARKOV_STATE_SIZE,
-        Regex::new(markov_str::WORD_REGEX).unwrap(),
-    )
+pub fn create_default_chain() -> MarkovChain {
+    MarkovChain::new(MARKOV_STATE_SIZE)
 }
 
 #[tracing::instrument(skip(ctx))]
@@ -319,6 +407,86 @@ pub async fn stop_saving_messages_server(
     }
 }
 
+#[tracing::instrument(skip(ctx))]
+pub async fn markov_filter_command(ctx: &Context, command: &CommandInteraction, pool: &PgPool) {
+    let Some(guild_id) = command.guild_id else {
+        command
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("This command can only be used in a server"),
+                ),
+            )
+            .instrument(info_span!("Sending message"))
+            .await
+            .unwrap();
+        return;
+    };
+
+    let settings = get_markov_filter_settings(guild_id.get() as i64, pool).await;
+    let mut disabled_stages = settings
+        .as_ref()
+        .map(|s| s.disabled_stages.clone())
+        .unwrap_or_default();
+    let mut min_words = settings.map_or(DEFAULT_MIN_NUM_OF_WORDS as i32, |s| s.min_words);
+
+    let stage = command
+        .data
+        .get_optional_string("stage")
+        .and_then(|s| FilterStage::from_str(&s).ok());
+    let enabled = command.data.get_optional_bool("enabled");
+    let new_min_words = command.data.get_optional_int("min-words");
+
+    if let Some(stage) = stage {
+        let stage = stage.to_string();
+        // Toggle the stage if the user didn't say whether it should be enabled
+        let enabled = enabled.unwrap_or_else(|| disabled_stages.contains(&stage));
+
+        disabled_stages.retain(|s| s != &stage);
+        if !enabled {
+            disabled_stages.push(stage);
+        }
+    }
+
+    if let Some(new_min_words) = new_min_words {
+        min_words = new_min_words.try_into().unwrap_or(min_words);
+    }
+
+    upsert_markov_filter_settings(guild_id.get() as i64, &disabled_stages, min_words, pool)
+        .await
+        .unwrap();
+
+    let pipeline = FilterPipeline::from_settings(&MarkovFilterSettings {
+        server_id: guild_id.get() as i64,
+        disabled_stages: disabled_stages.clone(),
+        min_words,
+    });
+    ctx.bot_state()
+        .write()
+        .await
+        .markov_filters
+        .insert(guild_id, Arc::new(pipeline));
+
+    let mut response = String::from("Messages in this server are filtered with these stages:\n");
+    for stage in FilterStage::iter() {
+        let enabled = !disabled_stages.contains(&stage.to_string());
+        writeln!(response, "{} {stage}", if enabled { "✅" } else { "❌" }).unwrap();
+    }
+    write!(response, "Minimum number of words: {min_words}").unwrap();
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(response),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await
+        .unwrap();
+}
+
 #[instrument()]
 pub fn init_markov_data() -> anyhow::Result<MarkovChain> {
     let markov = init()?;
@@ -1,11 +1,17 @@
 use std::sync::Arc;
 
+use strum_macros::{Display, EnumIter, EnumString};
+
 use crate::client::global_data::BotState;
 
 use super::file_operations::generate_new_chain_from_msg_file;
 
 pub const MARKOV_DATA_SET_PATH: &str = "data/markov data/markov data set.txt";
 pub const MARKOV_EXPORT_PATH: &str = "data/markov data/corpus.json";
+/// Where a corpus exported by `markov_str` is kept after the chain is converted
+pub const LEGACY_MARKOV_EXPORT_PATH: &str = "data/markov data/corpus.markov_str.json";
+pub const MARKOV_PERSONAS_FOLDER: &str = "data/markov data/personas";
+pub const MARKOV_DMS_FOLDER: &str = "data/markov data/dms";
 
 /// User Ids that the bot will not learn from
 pub struct MarkovBlacklistedUser {
@@ -23,6 +29,53 @@ pub struct MarkovBlacklistedServer {
     pub server_id: i64,
 }
 
+/// Which [`FilterStages`][super::filter::FilterStage] a server turned off
+pub struct MarkovFilterSettings {
+    #[allow(dead_code)]
+    pub server_id: i64,
+    pub disabled_stages: Vec<String>,
+    pub min_words: i32,
+}
+
+/// A chain trained on an uploaded text file instead of chat messages
+pub struct MarkovPersona {
+    #[allow(dead_code)]
+    pub id: i32,
+    #[allow(dead_code)]
+    pub server_id: i64,
+    pub name: String,
+    pub creator_id: i64,
+    pub state_count: i32,
+    pub estimated_bytes: i64,
+}
+
+/// Which chain a user's direct messages are learned by
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum DmLearningMode {
+    /// A chain only used when replying to the user in DMs
+    Personal,
+    /// The same chain that learns from servers
+    Global,
+}
+
+pub struct MarkovDmPreference {
+    #[allow(dead_code)]
+    pub user_id: i64,
+    pub learning_mode: String,
+}
+
+pub const DEFAULT_MAX_PERSONA_STATES: i32 = 500_000;
+
+/// How big the persona chains of a server are allowed to grow
+pub struct MarkovChainLimits {
+    #[allow(dead_code)]
+    pub server_id: i64,
+    pub min_transition_count: i32,
+    /// The most states all of the server's personas can have together
+    pub max_states: i32,
+}
+
 #[tracing::instrument(skip(state))]
 pub async fn replace_markov_chain_lock(state: Arc<BotState>) {
     let new_chain = generate_new_chain_from_msg_file().unwrap();
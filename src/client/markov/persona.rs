@@ -0,0 +1,416 @@
+// purpose: to let servers train named chains on a text file, e.g. a book or a chat log,
+// and generate sentences from them instead of the chain learned from chat messages
+//
+// behavior:
+// - an admin runs `/markov-persona upload` with a name and a .txt file
+// - the file is split into sentences which are filtered with the server's filter pipeline
+// - a chain is built the same way as the main chain and saved to
+//   `data/markov data/personas/{server_id}/{name}.json`
+// - `/markov generate persona:<name>` loads the chain the first time it's used and keeps it in memory
+// - uploading a file with the same name replaces the persona
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::Context as _;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use regex::Regex;
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse, GuildId,
+};
+use sqlx::PgPool;
+use tracing::{Instrument, info, info_span, instrument, warn};
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand,
+    global_data::GetBotState,
+    helper_funcs::{command_response, ephemeral_command_response},
+};
+
+use super::{
+    MARKOV_STATE_SIZE,
+    data_access::{
+        delete_markov_persona, get_markov_chain_limits, get_markov_persona,
+        get_markov_persona_state_count, get_markov_personas_by_server_id, upsert_markov_persona,
+    },
+    filter::FilterPipeline,
+    generate_from_chain, get_filter_pipeline,
+    limits::format_bytes,
+    markov_chain::{ChainLimits, MarkovChain},
+    model::{DEFAULT_MAX_PERSONA_STATES, MARKOV_PERSONAS_FOLDER},
+};
+
+pub const MAX_PERSONA_FILE_SIZE_MB: u64 = 20;
+pub const MAX_PERSONA_NAME_LENGTH: usize = 32;
+/// Keeps `/markov-persona list` under Discord's message length limit
+const MAX_SHOWN_PERSONAS: usize = 15;
+
+static PERSONA_NAME_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z0-9_-]+$").expect("Invalid regular expression"));
+/// The end of a sentence, including closing quotes and brackets
+static SENTENCE_END_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"[.!?…]+["'”’)\]]*\s+"#).expect("Invalid regular expression"));
+
+fn persona_path(server_id: GuildId, name: &str) -> PathBuf {
+    Path::new(MARKOV_PERSONAS_FOLDER)
+        .join(server_id.to_string())
+        .join(format!("{name}.json"))
+}
+
+/// Lowercases the name and checks that it's safe to use as a file name
+fn validate_persona_name(name: &str) -> Result<String, String> {
+    let name = name.trim().to_lowercase();
+
+    if name.is_empty() || name.len() > MAX_PERSONA_NAME_LENGTH {
+        return Err(format!(
+            "A persona name has to be between 1 and {MAX_PERSONA_NAME_LENGTH} characters long"
+        ));
+    }
+
+    if !PERSONA_NAME_REGEX.is_match(&name) {
+        return Err(
+            "A persona name can only contain letters, numbers, dashes and underscores".to_owned(),
+        );
+    }
+
+    Ok(name)
+}
+
+/// Splits a text file into sentences.
+///
+/// Lines are joined into paragraphs first so hard wrapped text doesn't get cut mid sentence.
+/// A line that ends a sentence also ends the paragraph, which keeps chat logs one message per line.
+fn split_into_sentences(text: &str) -> Vec<String> {
+    let mut paragraphs = vec![];
+    let mut paragraph = String::new();
+
+    for line in text.lines().map(str::trim) {
+        if !line.is_empty() {
+            paragraph.push_str(line);
+            paragraph.push(' ');
+        }
+
+        let ends_sentence = SENTENCE_END_REGEX.is_match(&(line.to_owned() + " "));
+
+        if (line.is_empty() || ends_sentence) && !paragraph.is_empty() {
+            paragraphs.push(std::mem::take(&mut paragraph));
+        }
+    }
+
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+
+    paragraphs
+        .iter()
+        .flat_map(|p| SENTENCE_END_REGEX.split(p))
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Builds a chain from a text file in the same way as the chain learned from chat messages
+#[instrument(skip(text, pipeline))]
+fn build_persona_chain(
+    text: &str,
+    pipeline: &FilterPipeline,
+    limits: ChainLimits,
+) -> (MarkovChain, usize) {
+    let sentences: Vec<String> = split_into_sentences(text)
+        .into_par_iter()
+        .filter_map(|s| pipeline.filter_str(&s))
+        .collect();
+
+    let mut chain = MarkovChain::build_parallel(MARKOV_STATE_SIZE, &sentences);
+    chain.apply_limits(limits);
+
+    (chain, sentences.len())
+}
+
+fn save_persona_chain(path: &Path, chain: &MarkovChain) -> anyhow::Result<()> {
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+
+    fs::write(path, serde_json::to_string(chain)?)?;
+
+    Ok(())
+}
+
+fn load_persona_chain(path: &Path) -> anyhow::Result<MarkovChain> {
+    let file_contents = fs::read_to_string(path).context("Failed to read persona file")?;
+    Ok(serde_json::from_str(&file_contents)?)
+}
+
+/// Generates a sentence from a persona of the guild.
+///
+/// Returns [`None`] if the guild doesn't have a persona with that name.
+#[instrument(skip(ctx, pool))]
+pub async fn generate_persona_sentence(
+    ctx: &Context,
+    guild_id: GuildId,
+    name: &str,
+    pool: &PgPool,
+) -> anyhow::Result<Option<String>> {
+    let name = name.trim().to_lowercase();
+
+    if get_markov_persona(guild_id.get() as i64, &name, pool)
+        .await
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let state_lock = ctx.bot_state();
+    let key = (guild_id, name.clone());
+
+    if !state_lock.read().await.persona_chains.contains_key(&key) {
+        let path = persona_path(guild_id, &name);
+        let chain = tokio::task::spawn_blocking(move || load_persona_chain(&path)).await??;
+
+        state_lock
+            .write()
+            .await
+            .persona_chains
+            .insert(key.clone(), chain);
+    }
+
+    let state = state_lock.read().await;
+    let chain = state
+        .persona_chains
+        .get(&key)
+        .context("Persona chain should be loaded")?;
+
+    Ok(Some(generate_from_chain(chain, None)))
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn upload_persona_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let name = match validate_persona_name(&command.data.get_string("name")) {
+        Ok(name) => name,
+        Err(response) => {
+            ephemeral_command_response(ctx, command, response).await?;
+            return Ok(());
+        }
+    };
+
+    let attachment = command
+        .data
+        .get_optional_attachment("file")
+        .and_then(|id| command.data.resolved.attachments.get(&id))
+        .context("Attachment should be resolved")?;
+
+    if !Path::new(&attachment.filename)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("txt"))
+    {
+        ephemeral_command_response(ctx, command, "The file has to be a .txt file".to_owned())
+            .await?;
+        return Ok(());
+    }
+
+    if u64::from(attachment.size) > MAX_PERSONA_FILE_SIZE_MB * 1024 * 1024 {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("The file can't be larger than {MAX_PERSONA_FILE_SIZE_MB}MB"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let limits = get_markov_chain_limits(guild_id.get() as i64, pool).await;
+    let max_states = limits
+        .as_ref()
+        .map_or(DEFAULT_MAX_PERSONA_STATES, |l| l.max_states);
+    let used_states = get_markov_persona_state_count(guild_id.get() as i64, &name, pool).await;
+    let remaining_states = i64::from(max_states) - used_states;
+
+    if remaining_states <= 0 {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("The personas of this server already use all {max_states} of their states. Delete a persona or raise the limit with ``/markov-limits``"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let limits = ChainLimits {
+        min_transition_count: limits
+            .map_or(1, |l| l.min_transition_count)
+            .try_into()
+            .unwrap_or(1),
+        max_states: remaining_states.try_into().ok(),
+    };
+
+    command.defer(&ctx.http).await?;
+
+    let bytes = attachment.download().await?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+
+    let pipeline = get_filter_pipeline(ctx, guild_id, pool).await;
+
+    // The chain only replaces the file of the persona once the persona is saved,
+    // otherwise a failed upload would leave a file behind that isn't in the database
+    let path = persona_path(guild_id, &name);
+    let temporary_path = path.with_extension("json.tmp");
+    let (chain, sentence_count) = tokio::task::spawn_blocking({
+        let temporary_path = temporary_path.clone();
+        move || {
+            let (chain, sentence_count) = build_persona_chain(&text, &pipeline, limits);
+            save_persona_chain(&temporary_path, &chain).map(|()| (chain, sentence_count))
+        }
+    })
+    .await??;
+
+    let state_count = chain.state_count();
+    let estimated_bytes = chain.estimated_memory_usage();
+
+    let saved: anyhow::Result<()> = try {
+        upsert_markov_persona(
+            guild_id.get() as i64,
+            &name,
+            command.user.id.get() as i64,
+            state_count.try_into()?,
+            estimated_bytes.try_into()?,
+            pool,
+        )
+        .await?;
+    };
+
+    if let Err(err) = saved {
+        if let Err(remove_err) = fs::remove_file(&temporary_path) {
+            warn!("Couldn't remove the chain of the persona {name}: {remove_err}");
+        }
+        return Err(err);
+    }
+
+    fs::rename(&temporary_path, &path)?;
+
+    ctx.bot_state()
+        .write()
+        .await
+        .persona_chains
+        .insert((guild_id, name.clone()), chain);
+
+    info!(sentence_count, state_count, "saved persona {name}");
+
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().content(format!(
+                "Learned {sentence_count} sentences for the persona ``{name}``, it takes up about {}. Use ``/markov generate persona:{name}`` to hear what it has to say!",
+                format_bytes(estimated_bytes)
+            )),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn delete_persona_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+    let name = command.data.get_string("name").trim().to_lowercase();
+
+    let deleted = delete_markov_persona(guild_id.get() as i64, &name, pool)
+        .await?
+        .rows_affected()
+        > 0;
+
+    if !deleted {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("There's no persona called ``{name}``"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    ctx.bot_state()
+        .write()
+        .await
+        .persona_chains
+        .remove(&(guild_id, name.clone()));
+
+    let path = persona_path(guild_id, &name);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("Deleted the persona ``{name}``")),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn list_personas_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        ephemeral_command_response(ctx, command, "Personas only exist in servers".to_owned())
+            .await?;
+        return Ok(());
+    };
+
+    let personas = get_markov_personas_by_server_id(guild_id.get() as i64, pool).await;
+
+    if personas.is_empty() {
+        return command_response(
+            ctx,
+            command,
+            "This server doesn't have any personas yet. An admin can add one with ``/markov-persona upload``",
+        )
+        .await;
+    }
+
+    let mut response = String::new();
+    for persona in personas.iter().take(MAX_SHOWN_PERSONAS) {
+        writeln!(
+            response,
+            "``{}`` by <@{}>, {} states taking up about {}",
+            persona.name,
+            persona.creator_id,
+            persona.state_count,
+            format_bytes(persona.estimated_bytes.try_into().unwrap_or_default())
+        )?;
+    }
+    if personas.len() > MAX_SHOWN_PERSONAS {
+        writeln!(response, "and {} more", personas.len() - MAX_SHOWN_PERSONAS)?;
+    }
+
+    command_response(ctx, command, response).await
+}
@@ -28,7 +28,8 @@ pub fn create_memes_commands() -> Vec<CreateCommand<'static>> {
             )
             .add_sub_option(
                 CreateCommandOption::new(CommandOptionType::String, "category", "Select a category. Categories can only contain a single word.")
-                    .required(true),
+                    .required(true)
+                    .set_autocomplete(true),
             )
             .add_sub_option(
                 CreateCommandOption::new(
@@ -262,6 +262,26 @@ pub async fn get_categories_by_name(
     .await?)
 }
 
+/// Get the names of the categories that start with the prefix in alphabetical order
+pub async fn get_category_names_starting_with(
+    prefix: &str,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> anyhow::Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        r#"
+            SELECT category FROM meme_categories
+            WHERE starts_with(category, $1)
+            ORDER BY category
+            LIMIT $2
+            "#,
+        prefix,
+        limit,
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
 pub async fn create_new_categories(
     categories: &[String],
     conn: &mut PgConnection,
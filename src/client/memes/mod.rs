@@ -20,20 +20,21 @@ use std::{
 
 use itertools::Itertools;
 use serenity::all::{
-    CommandInteraction, Context, CreateAttachment, CreateEmbed, CreateInteractionResponse,
-    CreateInteractionResponseMessage, CreateQuickModal, EditAttachments, EditInteractionResponse,
-    QuickModal,
+    AutocompleteChoice, CommandInteraction, Context, CreateAttachment, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateQuickModal, EditAttachments,
+    EditInteractionResponse, QuickModal,
 };
 use sqlx::{PgConnection, PgPool};
 use tracing::{Instrument, info, info_span};
 
 use crate::client::{
+    autocomplete::MAX_AUTOCOMPLETE_CHOICES,
     get_option_from_command::GetOptionFromCommand,
     helper_funcs::{download_file_from_link, download_file_from_message, get_seconds_in_month},
     memes::dal::{
         MemeServerCategory, create_meme_file, create_meme_file_categories, create_new_categories,
-        create_new_category_dirs, get_category_file_count, get_file_by_hash,
-        get_meme_file_count_by_folder, save_meme_to_file,
+        create_new_category_dirs, get_category_file_count, get_category_names_starting_with,
+        get_file_by_hash, get_meme_file_count_by_folder, save_meme_to_file,
     },
 };
 
@@ -471,3 +472,23 @@ pub async fn meme_categories_command(
 
     Ok(())
 }
+
+/// Suggests the categories that start with what the user typed so far
+pub async fn meme_category_choices(
+    typed: &str,
+    pool: &PgPool,
+) -> anyhow::Result<Vec<AutocompleteChoice<'static>>> {
+    let mut conn = pool.acquire().await?;
+
+    let categories = get_category_names_starting_with(
+        &typed.trim().to_lowercase(),
+        MAX_AUTOCOMPLETE_CHOICES as i64,
+        &mut conn,
+    )
+    .await?;
+
+    Ok(categories
+        .into_iter()
+        .map(|c| AutocompleteChoice::new(c.clone(), c))
+        .collect())
+}
@@ -1,3 +1,4 @@
+pub mod autocomplete;
 pub mod download;
 pub mod file_operations;
 pub mod get_option_from_command;
@@ -10,6 +11,7 @@ pub mod tags;
 pub mod voice;
 
 use anyhow::Context as _;
+use autocomplete::autocomplete_responses;
 use global_data::{HELP_MESSAGE, init_bot_state};
 use itertools::Itertools;
 use regex::Regex;
@@ -23,13 +25,17 @@ use crate::client::{
 };
 
 use self::{
-    tags::{blacklist_user, respond_to_tag},
+    tags::{
+        TagPreference, change_tag_list_page, forget_deleted_tag_channel, respond_to_tag,
+        toggle_tag_preference,
+    },
     voice::{
         component_interactions::{
             bring_to_front::bring_to_front, change_queue_page::change_queue_page,
             play_now::play_now, skip::skip_button_press,
         },
         helper_funcs::leave_vc_if_alone,
+        persistence::{forget_queue, restore_queue},
         queue::shuffle::shuffle_queue,
     },
 };
@@ -37,8 +43,8 @@ use super::tags::check_for_tag_listeners;
 use serenity::{
     Client,
     all::{
-        Context, CreateInteractionResponseMessage, EventHandler, FullEvent, Guild, Interaction,
-        MessageFlags, Token, VoiceGatewayManager,
+        Context, CreateInteractionResponseMessage, EventHandler, FullEvent, Guild, GuildChannel,
+        Interaction, MessageFlags, Token, VoiceGatewayManager,
     },
     async_trait,
     builder::CreateInteractionResponse,
@@ -62,6 +68,10 @@ pub enum ComponentIds {
     QueueStart,
     QueueEnd,
     Shuffle,
+    TagListStart,
+    TagListPrevious,
+    TagListNext,
+    TagListEnd,
 }
 
 struct Handler {
@@ -91,6 +101,8 @@ impl Handler {
             owner.tag(),
             guild.member_count
         );
+
+        restore_queue(ctx, &guild, &self.pool).await;
     }
 
     /// Is called when a user starts an [`Interaction`]
@@ -100,13 +112,29 @@ impl Handler {
             Interaction::Command(command) => {
                 command_responses(&command, ctx, &self.pool).await;
             }
+            Interaction::Autocomplete(command) => {
+                autocomplete_responses(&command, ctx, &self.pool).await;
+            }
             Interaction::Component(mut component) => {
-                let button_id = ComponentIds::from_str(&component.data.custom_id)
-                    .expect("unexpected button ID");
+                // Buttons can keep their state after the ID, like the page of a tag list
+                let (button_id, button_state) = component
+                    .data
+                    .custom_id
+                    .split_once(':')
+                    .unwrap_or((&component.data.custom_id, ""));
+                let button_id = ComponentIds::from_str(button_id).expect("unexpected button ID");
+                let button_state = button_state.to_owned();
 
                 match button_id {
                     ComponentIds::BlacklistMeFromTags => {
-                        let response = blacklist_user(&component.user, &self.pool).await;
+                        let response = toggle_tag_preference(
+                            ctx,
+                            component.user.id,
+                            component.guild_id,
+                            TagPreference::NoPing,
+                            &self.pool,
+                        )
+                        .await;
                         component
                             .create_response(
                                 &ctx.http,
@@ -126,6 +154,14 @@ impl Handler {
                     | ComponentIds::QueueEnd => {
                         change_queue_page(ctx, &mut component, button_id).await;
                     }
+                    ComponentIds::TagListStart
+                    | ComponentIds::TagListPrevious
+                    | ComponentIds::TagListNext
+                    | ComponentIds::TagListEnd => {
+                        change_tag_list_page(ctx, &component, button_id, &button_state, &self.pool)
+                            .await
+                            .unwrap();
+                    }
                     ComponentIds::Skip => {
                         skip_button_press(ctx, &component).await.unwrap();
                     }
@@ -153,7 +189,13 @@ impl Handler {
             return;
         }
 
-        markov::add_message_to_chain(&msg, ctx, &self.pool)
+        // Learning from a DM and replying to it both depend on the mode, so it's only read once
+        let dm_learning_mode = match msg.guild_id {
+            Some(_) => None,
+            None => markov::get_dm_learning_mode(msg.author.id, &self.pool).await,
+        };
+
+        markov::add_message_to_chain(&msg, dm_learning_mode, ctx, &self.pool)
             .await
             .ok();
 
@@ -165,10 +207,14 @@ impl Handler {
             .map(ToString::to_string)
             .collect::<Vec<String>>();
 
-        if msg
-            .mentions_me(&ctx.http)
-            .await
-            .expect("Couldn't read cache")
+        // Users who let the bot learn from their DMs are replied to without having to mention it
+        let is_direct_message_to_learn_from = dm_learning_mode.is_some();
+
+        if is_direct_message_to_learn_from
+            || msg
+                .mentions_me(&ctx.http)
+                .await
+                .expect("Couldn't read cache")
         {
             async {
                 if words_in_message.contains(&"help".to_owned()) {
@@ -180,66 +226,69 @@ impl Handler {
                     return;
                 }
 
-                if words_in_message.len() > 1 {
+                let sanitized_message = (words_in_message.len() > 1).then(|| {
                     let user_regex = Regex::new(r"<@!?(\d+)>").expect("Invalid regular expression");
 
-                    let sanitized_message = words_in_message
+                    words_in_message
                         .iter()
                         .filter(|w| !user_regex.is_match(w))
-                        .join(" ");
+                        .join(" ")
+                });
+
+                let reply = match markov::generate_reply(
+                    ctx,
+                    &msg,
+                    sanitized_message.as_deref(),
+                    dm_learning_mode,
+                )
+                .await
+                {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        error!("Couldn't generate a reply: {e:?}");
+                        return;
+                    }
+                };
 
-                    msg.channel_id
-                        .say(
-                            &ctx.http,
-                            markov::generate_sentence(ctx, Some(&sanitized_message)).await,
-                        )
-                        .instrument(info_span!("Sending message"))
-                        .await
-                        .expect("Couldn't send message");
-                } else {
-                    msg.channel_id
-                        .say(&ctx.http, markov::generate_sentence(ctx, None).await)
-                        .instrument(info_span!("Sending message"))
-                        .await
-                        .expect("Couldn't send message");
-                }
+                msg.channel_id
+                    .say(&ctx.http, reply)
+                    .instrument(info_span!("Sending message"))
+                    .await
+                    .expect("Couldn't send message");
             }
             .instrument(info_span!("Mentioned"))
             .await;
             return;
         }
 
-        if msg.guild_id.is_some()
-            && let Some(response) = check_for_tag_listeners(
-                &words_in_message,
-                msg.author.id,
-                msg.guild_id.unwrap().get(),
-                &self.pool,
-            )
-            .await
-        {
-            respond_to_tag(ctx, &msg, &response, &self.pool).await;
+        if let Some(tag_match) = check_for_tag_listeners(ctx, &msg, &self.pool).await {
+            respond_to_tag(ctx, &msg, &tag_match, &self.pool).await;
         }
     }
 
+    /// Is called when a channel of a guild is deleted
+    async fn channel_delete(&self, channel: GuildChannel) {
+        forget_deleted_tag_channel(channel.base.guild_id, channel.id, &self.pool).await;
+    }
+
     async fn voice_state_update(&self, ctx: &Context, old: Option<VoiceState>, new: VoiceState) {
         leave_vc_if_alone(&old, ctx).await;
 
         if new.channel_id.is_none() && new.user_id == ctx.http.application_id().unwrap().get() {
-            let state_lock = ctx.bot_state();
-            let state = state_lock.read().await;
-            let call_lock = state.songbird.get(new.guild_id.unwrap());
+            let guild_id = new.guild_id.unwrap();
+            let call_lock = ctx.bot_state().read().await.songbird.get(guild_id);
 
-            let Some(call_lock) = call_lock else {
-                return;
-            };
+            if let Some(call_lock) = call_lock {
+                let mut call = timeout(Duration::from_secs(30), call_lock.lock())
+                    .await
+                    .unwrap();
 
-            let mut call = timeout(Duration::from_secs(30), call_lock.lock())
-                .await
-                .unwrap();
+                call.queue().stop();
+                call.remove_all_global_events();
+            }
 
-            call.queue().stop();
-            call.remove_all_global_events();
+            // The queue is forgotten after the events are removed so it isn't saved again
+            forget_queue(guild_id, &self.pool).await;
         }
     }
 }
@@ -259,6 +308,7 @@ impl EventHandler for Handler {
             FullEvent::VoiceStateUpdate { old, new, .. } => {
                 self.voice_state_update(&ctx, old, new).await;
             }
+            FullEvent::ChannelDelete { channel, .. } => self.channel_delete(channel).await,
             _ => {}
         }
     }
@@ -278,6 +328,9 @@ pub async fn start() {
 
     sqlx::migrate!("./migrations").run(&pool).await.unwrap();
 
+    tokio::spawn(tags::lift_expired_tag_bans_loop(pool.clone()));
+    tokio::spawn(tags::archive_tag_uses_loop(pool.clone()));
+
     let bot_state = init_bot_state()
         .await
         .expect("Couldn't initialize bot state");
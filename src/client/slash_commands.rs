@@ -4,8 +4,14 @@ use super::{
     helper_funcs::{get_full_command_name, ping_command, user_id_command},
     markov::commands::create_markov_commands,
     tags::{
-        blacklist_user_from_tags_command, commands::create_tag_commands, create_tag, list_tags,
-        remove_tag, set_tag_response_channel,
+        TagPreference, add_tag_response_command, commands::create_tag_commands, create_tag,
+        edit_tag_command, export_tags_command, import_tags_command, list_tag_bans_command,
+        list_tag_responses_command, list_tags, remove_tag, remove_tag_response_command,
+        set_tag_response_channel, tag_audit_log_command, tag_cooldown_command,
+        tag_default_cooldown_command, tag_default_delivery_command, tag_delivery_command,
+        tag_info_command, tag_permissions_command, tag_preference_command,
+        tag_response_type_command, top_tags_command, transfer_tag_command,
+        unban_user_from_editing_tags,
     },
     voice::commands::create_voice_commands,
 };
@@ -19,6 +25,10 @@ use crate::{
             play::play,
             play_from_attachment::play_from_attachment,
             playing::playing,
+            playlists::{
+                delete_playlist_command, list_playlists_command, load_playlist_command,
+                rename_playlist_command, save_playlist_command, share_playlist_command,
+            },
             queue::{command_response::queue, shuffle::shuffle_queue},
             skip::skip,
             stop::stop,
@@ -53,6 +63,22 @@ pub enum UserCommand {
     stop_saving_messages_channel,
     #[strum(serialize = "stop-saving-messages-server")]
     stop_saving_messages_server,
+    #[strum(serialize = "markov-filter")]
+    markov_filter,
+    #[strum(props(SubCommand = "generate"), serialize = "markov generate")]
+    markov_generate,
+    #[strum(props(SubCommand = "personas"), serialize = "markov personas")]
+    markov_personas,
+    #[strum(props(SubCommand = "stats"), serialize = "markov stats")]
+    markov_stats,
+    #[strum(props(SubCommand = "dms"), serialize = "markov dms")]
+    markov_dms,
+    #[strum(props(SubCommand = "upload"), serialize = "markov-persona upload")]
+    markov_persona_upload,
+    #[strum(props(SubCommand = "delete"), serialize = "markov-persona delete")]
+    markov_persona_delete,
+    #[strum(serialize = "markov-limits")]
+    markov_limits,
     help,
     version,
     download,
@@ -71,6 +97,8 @@ pub enum UserCommand {
         serialize = "tag stop-pinging-me"
     )]
     blacklist_me_from_tags,
+    #[strum(props(SubCommand = "ignore-me"), serialize = "tag ignore-me")]
+    tag_ignore_me,
     #[strum(
         props(SubCommand = "response-channel"),
         serialize = "tag response-channel"
@@ -78,6 +106,48 @@ pub enum UserCommand {
     tag_response_channel,
     #[strum(props(SubCommand = "ban"), serialize = "tag ban")]
     tag_ban,
+    #[strum(props(SubCommand = "unban"), serialize = "tag unban")]
+    tag_unban,
+    #[strum(props(SubCommand = "bans"), serialize = "tag bans")]
+    tag_bans,
+    #[strum(props(SubCommand = "audit-log"), serialize = "tag audit-log")]
+    tag_audit_log,
+    #[strum(props(SubCommand = "permissions"), serialize = "tag permissions")]
+    tag_permissions,
+    #[strum(props(SubCommand = "edit"), serialize = "tag edit")]
+    edit_tag,
+    #[strum(props(SubCommand = "transfer"), serialize = "tag transfer")]
+    transfer_tag,
+    #[strum(props(SubCommand = "add"), serialize = "tag response add")]
+    tag_response_add,
+    #[strum(props(SubCommand = "remove"), serialize = "tag response remove")]
+    tag_response_remove,
+    #[strum(props(SubCommand = "list"), serialize = "tag response list")]
+    tag_response_list,
+    #[strum(props(SubCommand = "type"), serialize = "tag response type")]
+    tag_response_type,
+    #[strum(props(SubCommand = "export"), serialize = "tag export")]
+    tag_export,
+    #[strum(props(SubCommand = "import"), serialize = "tag import")]
+    tag_import,
+    #[strum(props(SubCommand = "info"), serialize = "tag info")]
+    tag_info,
+    #[strum(props(SubCommand = "top"), serialize = "tag top")]
+    tag_top,
+    #[strum(props(SubCommand = "cooldown"), serialize = "tag cooldown")]
+    tag_cooldown,
+    #[strum(
+        props(SubCommand = "default-cooldown"),
+        serialize = "tag default-cooldown"
+    )]
+    tag_default_cooldown,
+    #[strum(props(SubCommand = "delivery"), serialize = "tag delivery")]
+    tag_delivery,
+    #[strum(
+        props(SubCommand = "default-delivery"),
+        serialize = "tag default-delivery"
+    )]
+    tag_default_delivery,
 
     // =====VOICE=====
     play,
@@ -93,6 +163,18 @@ pub enum UserCommand {
     loop_song,
     #[strum(serialize = "swap-songs")]
     swap_songs,
+    #[strum(props(SubCommand = "save"), serialize = "playlist save")]
+    playlist_save,
+    #[strum(props(SubCommand = "load"), serialize = "playlist load")]
+    playlist_load,
+    #[strum(props(SubCommand = "list"), serialize = "playlist list")]
+    playlist_list,
+    #[strum(props(SubCommand = "delete"), serialize = "playlist delete")]
+    playlist_delete,
+    #[strum(props(SubCommand = "rename"), serialize = "playlist rename")]
+    playlist_rename,
+    #[strum(props(SubCommand = "share"), serialize = "playlist share")]
+    playlist_share,
 
     // =====MEME=====
     #[strum(serialize = "Upload meme")]
@@ -132,9 +214,12 @@ pub async fn command_responses(command: &CommandInteraction, ctx: &Context, pool
             }
             UserCommand::create_tag => create_tag(ctx, command, pool).await,
             UserCommand::remove_tag => remove_tag(ctx, command, pool).await,
-            UserCommand::tag_list => list_tags(ctx, command, pool).await,
+            UserCommand::tag_list => list_tags(ctx, command, pool).await.unwrap(),
             UserCommand::blacklist_me_from_tags => {
-                blacklist_user_from_tags_command(ctx, user, command, pool).await;
+                tag_preference_command(ctx, command, TagPreference::NoPing, pool).await;
+            }
+            UserCommand::tag_ignore_me => {
+                tag_preference_command(ctx, command, TagPreference::Ignore, pool).await;
             }
             UserCommand::tag_response_channel => {
                 set_tag_response_channel(ctx, command, pool).await;
@@ -142,6 +227,70 @@ pub async fn command_responses(command: &CommandInteraction, ctx: &Context, pool
             UserCommand::tag_ban => {
                 ban_user_from_editing_tags(ctx, command, pool).await;
             }
+            UserCommand::edit_tag => {
+                edit_tag_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::transfer_tag => {
+                transfer_tag_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_response_add => {
+                add_tag_response_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_response_remove => {
+                remove_tag_response_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::tag_response_list => {
+                list_tag_responses_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::tag_response_type => {
+                tag_response_type_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_export => {
+                export_tags_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_import => {
+                import_tags_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_info => {
+                tag_info_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_top => {
+                top_tags_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_cooldown => {
+                tag_cooldown_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_unban => {
+                unban_user_from_editing_tags(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::tag_bans => {
+                list_tag_bans_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_audit_log => {
+                tag_audit_log_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_permissions => {
+                tag_permissions_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_default_cooldown => {
+                tag_default_cooldown_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::tag_delivery => {
+                tag_delivery_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::tag_default_delivery => {
+                tag_default_delivery_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
             UserCommand::help => command
                 .create_response(
                     &ctx.http,
@@ -173,14 +322,66 @@ pub async fn command_responses(command: &CommandInteraction, ctx: &Context, pool
             UserCommand::stop_saving_messages_server => {
                 markov::stop_saving_messages_server(ctx, command, pool).await;
             }
-            UserCommand::play => play(ctx, command).await,
-            UserCommand::play_from_attachment => play_from_attachment(ctx, command).await,
+            UserCommand::markov_filter => {
+                markov::markov_filter_command(ctx, command, pool).await;
+            }
+            UserCommand::markov_generate => {
+                markov::markov_generate_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_personas => {
+                markov::list_personas_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_stats => {
+                markov::markov_stats_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_dms => {
+                markov::markov_dms_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_limits => {
+                markov::markov_limits_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_persona_upload => {
+                markov::upload_persona_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::markov_persona_delete => {
+                markov::delete_persona_command(ctx, command, pool)
+                    .await
+                    .unwrap();
+            }
+            UserCommand::play => play(ctx, command, pool).await,
+            UserCommand::play_from_attachment => {
+                play_from_attachment(ctx, command, pool).await;
+            }
             UserCommand::skip => skip(ctx, command).await.unwrap(),
             UserCommand::stop => stop(ctx, command).await,
             UserCommand::playing => playing(ctx, command).await,
             UserCommand::queue => queue(ctx, command).await,
             UserCommand::loop_song => loop_song(ctx, command).await,
             UserCommand::swap_songs => swap(ctx, command).await,
+            UserCommand::playlist_save => save_playlist_command(ctx, command, pool).await.unwrap(),
+            UserCommand::playlist_load => load_playlist_command(ctx, command, pool).await.unwrap(),
+            UserCommand::playlist_list => list_playlists_command(ctx, command, pool).await.unwrap(),
+            UserCommand::playlist_delete => {
+                delete_playlist_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::playlist_rename => {
+                rename_playlist_command(ctx, command, pool).await.unwrap();
+            }
+            UserCommand::playlist_share => {
+                share_playlist_command(ctx, command, pool).await.unwrap();
+            }
             UserCommand::queue_shuffle => {
                 command.defer(&ctx.http).await.unwrap();
 
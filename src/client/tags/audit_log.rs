@@ -0,0 +1,184 @@
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::Context as _;
+use serenity::all::{
+    CommandInteraction, Context, CreateEmbed, CreateInteractionResponseMessage, GuildId, UserId,
+};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response_message,
+};
+
+use super::{
+    data_access::{create_tag_audit_entry, get_tag_audit_entries},
+    model::{TAG_AUDIT_BOT_USER_ID, TagAction, TagAuditAction, TagAuditEntry},
+    permissions::check_tag_permission,
+    stats::discord_timestamp,
+};
+
+const ENTRIES_PER_PAGE: i64 = 15;
+
+/// An entry of the audit log of a server, built up and then saved
+pub struct TagAuditLog {
+    server_id: i64,
+    user_id: i64,
+    action: TagAuditAction,
+    tag_listener: Option<String>,
+    target_user_id: Option<i64>,
+    details: Option<String>,
+}
+
+impl TagAuditLog {
+    pub fn new(guild_id: GuildId, user_id: UserId, action: TagAuditAction) -> Self {
+        Self {
+            server_id: guild_id.get() as i64,
+            user_id: user_id.get() as i64,
+            action,
+            tag_listener: None,
+            target_user_id: None,
+            details: None,
+        }
+    }
+
+    /// Something the bot did on its own
+    pub fn by_bot(server_id: i64, action: TagAuditAction) -> Self {
+        Self {
+            server_id,
+            user_id: TAG_AUDIT_BOT_USER_ID,
+            action,
+            tag_listener: None,
+            target_user_id: None,
+            details: None,
+        }
+    }
+
+    pub fn tag(mut self, listener: &str) -> Self {
+        self.tag_listener = Some(listener.to_owned());
+        self
+    }
+
+    pub fn target(mut self, user_id: i64) -> Self {
+        self.target_user_id = Some(user_id);
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub async fn save(self, executor: impl PgExecutor<'_>) {
+        create_tag_audit_entry(
+            self.server_id,
+            self.user_id,
+            self.action.to_string(),
+            self.tag_listener,
+            self.target_user_id,
+            self.details,
+            executor,
+        )
+        .await;
+    }
+}
+
+/// Shows what was done to the tags of the server, to the people who can ban from editing tags
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_audit_log_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Ban, pool).await {
+        return command_response_message(
+            ctx,
+            command,
+            CreateInteractionResponseMessage::new().content(reason),
+        )
+        .await;
+    }
+
+    let user = command.data.get_optional_user("user");
+    let page = command.data.get_optional_int("page").unwrap_or(1).max(1);
+
+    let entries = get_tag_audit_entries(
+        guild_id.get() as i64,
+        user.map(|u| u.get() as i64),
+        ENTRIES_PER_PAGE,
+        (page - 1) * ENTRIES_PER_PAGE,
+        pool,
+    )
+    .await;
+
+    if entries.is_empty() {
+        return command_response_message(
+            ctx,
+            command,
+            CreateInteractionResponseMessage::new().content("There's nothing on this page"),
+        )
+        .await;
+    }
+
+    let mut description = String::new();
+    for entry in &entries {
+        writeln!(description, "{}", describe_entry(entry))?;
+    }
+
+    let embed = CreateEmbed::new()
+        .title(format!("Tag audit log, page {page}"))
+        .description(description);
+
+    command_response_message(
+        ctx,
+        command,
+        CreateInteractionResponseMessage::new().embed(embed),
+    )
+    .await
+}
+
+fn describe_entry(entry: &TagAuditEntry) -> String {
+    let who = if entry.user_id == TAG_AUDIT_BOT_USER_ID {
+        "I".to_owned()
+    } else {
+        format!("<@{}>", entry.user_id)
+    };
+    let tag = entry
+        .tag_listener
+        .as_ref()
+        .map_or_else(|| "a tag".to_owned(), |l| format!("\"{}\"", shorten(l)));
+    let target = entry
+        .target_user_id
+        .map_or_else(|| "someone".to_owned(), |t| format!("<@{t}>"));
+
+    let what = match TagAuditAction::from_str(&entry.action) {
+        Ok(TagAuditAction::Create) => format!("{who} created {tag}"),
+        Ok(TagAuditAction::Edit) => format!("{who} edited {tag}"),
+        Ok(TagAuditAction::Transfer) => format!("{who} gave {tag} to {target}"),
+        Ok(TagAuditAction::Remove) => format!("{who} removed {tag}"),
+        Ok(TagAuditAction::Ban) => format!("{who} banned {target} from editing tags"),
+        Ok(TagAuditAction::Unban) => format!("{who} unbanned {target} from editing tags"),
+        Ok(TagAuditAction::BanExpired) => format!("The tag ban of {target} ran out"),
+        Err(_) => format!("{who} did {}", entry.action),
+    };
+
+    let mut line = format!("{} {what}", discord_timestamp(entry.created_at, 'f'));
+    if let Some(details) = &entry.details {
+        write!(line, " ({})", shorten(details)).unwrap();
+    }
+
+    line
+}
+
+fn shorten(text: &str) -> String {
+    const MAX_SHOWN_CHARACTERS: usize = 60;
+
+    if text.chars().count() > MAX_SHOWN_CHARACTERS {
+        text.chars().take(MAX_SHOWN_CHARACTERS).collect::<String>() + "..."
+    } else {
+        text.to_owned()
+    }
+}
@@ -0,0 +1,39 @@
+use serenity::all::{AutocompleteChoice, Context, GuildId};
+use sqlx::PgPool;
+
+use super::get_tag_matcher;
+
+/// Discord doesn't accept longer names or values for a choice
+const MAX_CHOICE_LENGTH: usize = 100;
+
+/// Suggests the tags of the server whose listener contains what the user typed so far
+///
+/// The listeners come from the [`TagMatcher`][super::TagMatcher] of the server
+/// so typing doesn't query the database on every key press
+pub async fn tag_listener_choices(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    typed: &str,
+    pool: &PgPool,
+) -> Vec<AutocompleteChoice<'static>> {
+    let Some(guild_id) = guild_id else {
+        return Vec::new();
+    };
+
+    let typed = typed.to_lowercase();
+
+    let matcher = get_tag_matcher(ctx, guild_id, pool).await;
+    let mut listeners = matcher
+        .listeners()
+        .filter(|l| l.chars().count() <= MAX_CHOICE_LENGTH && l.to_lowercase().contains(&typed))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    // Tags that start with what was typed come first
+    listeners.sort_by_key(|l| (!l.to_lowercase().starts_with(&typed), l.to_lowercase()));
+
+    listeners
+        .into_iter()
+        .map(|l| AutocompleteChoice::new(l.clone(), l))
+        .collect()
+}
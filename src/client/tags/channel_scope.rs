@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::LazyLock,
+};
+
+use regex::Regex;
+use serenity::all::{ChannelType, CommandInteraction, Context, GuildId, Message};
+use sqlx::PgPool;
+
+use crate::client::get_option_from_command::GetOptionFromCommand;
+
+use super::{
+    Tag,
+    data_access::{
+        get_tag_scope_channels_by_tag_id, set_tag_scope_channels, update_tag_channel_scope,
+    },
+    model::{TagChannelScope, TagScopeChannel},
+};
+
+static CHANNEL_MENTION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<#(\d+)>").unwrap());
+
+/// How many channels can be in the allow-list or deny-list of a tag
+const MAX_SCOPE_CHANNELS: usize = 25;
+
+/// The channel a message was sent in, with what the channel restrictions of tags check
+pub struct MessageChannel {
+    /// The channel and the channel it's a thread of
+    ids: Vec<i64>,
+    nsfw: bool,
+    announcement: bool,
+}
+
+impl MessageChannel {
+    pub fn new(ctx: &Context, msg: &Message) -> Self {
+        let mut channel = Self {
+            ids: vec![msg.channel_id.get() as i64],
+            nsfw: false,
+            announcement: false,
+        };
+
+        let Some(guild) = msg.guild(&ctx.cache) else {
+            return channel;
+        };
+
+        // Threads take the restrictions of the channel they're in
+        let guild_channel = match guild
+            .channels
+            .iter()
+            .find(|c| c.id.widen() == msg.channel_id)
+        {
+            Some(guild_channel) => Some(guild_channel),
+            None => guild
+                .threads
+                .iter()
+                .find(|t| t.id.widen() == msg.channel_id)
+                .and_then(|t| guild.channels.get(&t.parent_id)),
+        };
+
+        if let Some(guild_channel) = guild_channel {
+            channel.ids.push(guild_channel.id.get() as i64);
+            channel.nsfw = guild_channel.base.nsfw;
+            channel.announcement = guild_channel.base.kind == ChannelType::News;
+        }
+
+        channel
+    }
+}
+
+#[cfg(test)]
+impl MessageChannel {
+    /// A channel that isn't in the cache, for the tests of the tag modules
+    pub fn for_tests(ids: Vec<i64>, nsfw: bool, announcement: bool) -> Self {
+        Self {
+            ids,
+            nsfw,
+            announcement,
+        }
+    }
+}
+
+/// The channels each tag of a guild is limited to or kept out of
+pub struct ScopeChannels(HashMap<i32, HashSet<i64>>);
+
+impl ScopeChannels {
+    pub fn new(channels: Vec<TagScopeChannel>) -> Self {
+        let mut scope_channels: HashMap<i32, HashSet<i64>> = HashMap::new();
+        for channel in channels {
+            scope_channels
+                .entry(channel.tag_id)
+                .or_default()
+                .insert(channel.channel_id);
+        }
+
+        Self(scope_channels)
+    }
+
+    /// Checks if the tag can respond in the channel
+    pub fn allows(&self, tag: &Tag, channel: &MessageChannel) -> bool {
+        if (tag.disabled_in_nsfw && channel.nsfw)
+            || (tag.disabled_in_announcements && channel.announcement)
+        {
+            return false;
+        }
+
+        let is_listed = self
+            .0
+            .get(&tag.id)
+            .is_some_and(|c| channel.ids.iter().any(|id| c.contains(id)));
+
+        match tag.channel_scope() {
+            TagChannelScope::Everywhere => true,
+            TagChannelScope::AllowList => is_listed,
+            TagChannelScope::DenyList => !is_listed,
+        }
+    }
+}
+
+/// The channel options of `/tag create` and `/tag edit`, anything not given stays as it is
+struct ChannelScopeOptions {
+    scope: Option<TagChannelScope>,
+    channels: Option<Vec<i64>>,
+    disabled_in_nsfw: Option<bool>,
+    disabled_in_announcements: Option<bool>,
+}
+
+/// Where a tag responds after the options are applied
+pub struct ChannelScope {
+    scope: TagChannelScope,
+    channels: Vec<i64>,
+    disabled_in_nsfw: bool,
+    disabled_in_announcements: bool,
+}
+
+impl ChannelScopeOptions {
+    /// Reads the options and checks that the listed channels are in the server
+    fn from_command(ctx: &Context, command: &CommandInteraction) -> Result<Self, String> {
+        let channels = match command.data.get_optional_string("channels") {
+            Some(channels) => Some(parse_channels(ctx, command.guild_id, &channels)?),
+            None => None,
+        };
+
+        Ok(Self {
+            scope: command
+                .data
+                .get_optional_string("channel-scope")
+                .and_then(|s| TagChannelScope::from_str(&s).ok()),
+            channels,
+            disabled_in_nsfw: command.data.get_optional_bool("disable-in-nsfw"),
+            disabled_in_announcements: command.data.get_optional_bool("disable-in-announcements"),
+        })
+    }
+
+    /// Combines the options with the current settings of the tag, if it already exists
+    async fn resolve(self, tag: Option<&Tag>, pool: &PgPool) -> Result<ChannelScope, String> {
+        let channels = match (self.channels, tag) {
+            (Some(channels), _) => channels,
+            (None, Some(tag)) => get_tag_scope_channels_by_tag_id(tag.id, pool)
+                .await
+                .into_iter()
+                .map(|c| c.channel_id)
+                .collect(),
+            (None, None) => vec![],
+        };
+
+        let scope = ChannelScope {
+            scope: self
+                .scope
+                .or(tag.map(Tag::channel_scope))
+                .unwrap_or_default(),
+            channels,
+            disabled_in_nsfw: self
+                .disabled_in_nsfw
+                .or(tag.map(|t| t.disabled_in_nsfw))
+                .unwrap_or_default(),
+            disabled_in_announcements: self
+                .disabled_in_announcements
+                .or(tag.map(|t| t.disabled_in_announcements))
+                .unwrap_or_default(),
+        };
+
+        if scope.scope != TagChannelScope::Everywhere && scope.channels.is_empty() {
+            return Err(
+                "Allow-lists and deny-lists need channels, add them with the channels option"
+                    .to_owned(),
+            );
+        }
+
+        Ok(scope)
+    }
+}
+
+impl ChannelScope {
+    /// Reads the channel options of the command on top of the settings of the tag
+    pub async fn from_command(
+        ctx: &Context,
+        command: &CommandInteraction,
+        tag: Option<&Tag>,
+        pool: &PgPool,
+    ) -> Result<Self, String> {
+        ChannelScopeOptions::from_command(ctx, command)?
+            .resolve(tag, pool)
+            .await
+    }
+
+    pub async fn save(&self, tag_id: i32, pool: &PgPool) {
+        update_tag_channel_scope(
+            tag_id,
+            self.scope.to_string(),
+            self.disabled_in_nsfw,
+            self.disabled_in_announcements,
+            pool,
+        )
+        .await;
+        set_tag_scope_channels(tag_id, &self.channels, pool).await;
+    }
+}
+
+/// Reads the channel mentions out of the option
+fn parse_channels(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    channels: &str,
+) -> Result<Vec<i64>, String> {
+    let mut channel_ids = CHANNEL_MENTION_REGEX
+        .captures_iter(channels)
+        .filter_map(|c| c[1].parse::<u64>().ok())
+        .collect::<Vec<_>>();
+    channel_ids.sort_unstable();
+    channel_ids.dedup();
+
+    if channel_ids.len() > MAX_SCOPE_CHANNELS {
+        return Err(format!(
+            "A tag can't be limited to more than {MAX_SCOPE_CHANNELS} channels"
+        ));
+    }
+
+    let guild = guild_id
+        .and_then(|g| ctx.cache.guild(g))
+        .ok_or_else(|| "Couldn't find the server".to_owned())?;
+
+    if let Some(unknown) = channel_ids
+        .iter()
+        .find(|id| !guild.channels.iter().any(|c| c.id.get() == **id))
+    {
+        return Err(format!("<#{unknown}> isn't a channel of this server"));
+    }
+
+    Ok(channel_ids.into_iter().map(u64::cast_signed).collect())
+}
+
+/// Describes where the tag responds, for `/tag info`
+pub async fn describe_channel_scope(tag: &Tag, pool: &PgPool) -> String {
+    let channels = get_tag_scope_channels_by_tag_id(tag.id, pool)
+        .await
+        .iter()
+        .map(|c| format!("<#{}>", c.channel_id))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut description = match tag.channel_scope() {
+        TagChannelScope::Everywhere => "everywhere".to_owned(),
+        TagChannelScope::AllowList => format!("only in {channels}"),
+        TagChannelScope::DenyList => format!("everywhere except {channels}"),
+    };
+    if tag.disabled_in_nsfw {
+        description += ", not in nsfw channels";
+    }
+    if tag.disabled_in_announcements {
+        description += ", not in announcement channels";
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::tags::matching::TagMatchMode;
+
+    use super::*;
+
+    fn scoped_tag(id: i32, scope: TagChannelScope) -> Tag {
+        Tag {
+            channel_scope: scope.to_string(),
+            ..Tag::for_tests(id, "hello", TagMatchMode::Word)
+        }
+    }
+
+    fn scope_channels(channels: &[(i32, i64)]) -> ScopeChannels {
+        ScopeChannels::new(
+            channels
+                .iter()
+                .map(|(tag_id, channel_id)| TagScopeChannel {
+                    tag_id: *tag_id,
+                    channel_id: *channel_id,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn everywhere_allows_every_channel() {
+        let channels = scope_channels(&[(1, 10)]);
+        let tag = scoped_tag(1, TagChannelScope::Everywhere);
+
+        assert!(channels.allows(&tag, &MessageChannel::for_tests(vec![10], false, false)));
+        assert!(channels.allows(&tag, &MessageChannel::for_tests(vec![20], false, false)));
+    }
+
+    #[test]
+    fn allow_list_only_allows_listed_channels() {
+        let channels = scope_channels(&[(1, 10), (2, 20)]);
+        let tag = scoped_tag(1, TagChannelScope::AllowList);
+
+        assert!(channels.allows(&tag, &MessageChannel::for_tests(vec![10], false, false)));
+        assert!(!channels.allows(&tag, &MessageChannel::for_tests(vec![20], false, false)));
+        // Tags without channels of their own aren't allowed anywhere
+        assert!(!channels.allows(
+            &scoped_tag(3, TagChannelScope::AllowList),
+            &MessageChannel::for_tests(vec![10], false, false)
+        ));
+    }
+
+    #[test]
+    fn deny_list_allows_everything_but_listed_channels() {
+        let channels = scope_channels(&[(1, 10)]);
+        let tag = scoped_tag(1, TagChannelScope::DenyList);
+
+        assert!(!channels.allows(&tag, &MessageChannel::for_tests(vec![10], false, false)));
+        assert!(channels.allows(&tag, &MessageChannel::for_tests(vec![20], false, false)));
+    }
+
+    #[test]
+    fn threads_follow_their_parent_channel() {
+        let channels = scope_channels(&[(1, 10), (2, 10)]);
+        let thread = MessageChannel::for_tests(vec![30, 10], false, false);
+
+        assert!(channels.allows(&scoped_tag(1, TagChannelScope::AllowList), &thread));
+        assert!(!channels.allows(&scoped_tag(2, TagChannelScope::DenyList), &thread));
+    }
+
+    #[test]
+    fn nsfw_and_announcement_channels_can_be_excluded() {
+        let channels = scope_channels(&[]);
+        let tag = Tag {
+            disabled_in_nsfw: true,
+            disabled_in_announcements: true,
+            ..scoped_tag(1, TagChannelScope::Everywhere)
+        };
+
+        assert!(!channels.allows(&tag, &MessageChannel::for_tests(vec![10], true, false)));
+        assert!(!channels.allows(&tag, &MessageChannel::for_tests(vec![10], false, true)));
+        assert!(channels.allows(&tag, &MessageChannel::for_tests(vec![10], false, false)));
+        assert!(channels.allows(
+            &scoped_tag(2, TagChannelScope::Everywhere),
+            &MessageChannel::for_tests(vec![10], true, true)
+        ));
+    }
+}
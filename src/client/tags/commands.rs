@@ -2,9 +2,22 @@ use serenity::{
     all::{CommandOptionType, InteractionContext},
     builder::{CreateCommand, CreateCommandOption},
 };
-use strum::EnumProperty;
+use strum::{EnumProperty, IntoEnumIterator};
 
-use crate::client::slash_commands::UserCommand;
+use crate::client::{
+    slash_commands::UserCommand,
+    tags::{
+        import_export::ImportConflictPolicy,
+        matching::TagMatchMode,
+        model::{TagAction, TagChannelScope, TagDeliveryMode, TagResponseType},
+        tag_list::{MAX_SEARCH_LENGTH, TagListSort},
+    },
+};
+
+/// Tags can wait at most a day between responses
+pub const MAX_COOLDOWN_SECONDS: u64 = 24 * 60 * 60;
+/// Responses can be deleted at most an hour after they're posted
+pub const MAX_DELETE_AFTER_SECONDS: u64 = 60 * 60;
 
 pub fn create_tag_commands() -> CreateCommand<'static> {
     let command = CreateCommand::new("tag").add_context(InteractionContext::Guild);
@@ -14,17 +27,39 @@ pub fn create_tag_commands() -> CreateCommand<'static> {
         .description("tagdesc")
         .add_option(create_tag_creation_option())
         .add_option(create_tag_removal_option())
+        .add_option(create_tag_edit_option())
+        .add_option(create_tag_transfer_option())
+        .add_option(create_tag_response_group())
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::tag_info.get_str("SubCommand").unwrap(),
+                "Show who made a tag and how often it's used",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to show")
+                    .required(true),
+            ),
+        )
         .add_option(CreateCommandOption::new(
             CommandOptionType::SubCommand,
-            UserCommand::tag_list.get_str("SubCommand").unwrap(),
-            "List all of the tags",
+            UserCommand::tag_top.get_str("SubCommand").unwrap(),
+            "List the most used tags in this server",
         ))
+        .add_option(create_tag_list_option())
         .add_option(CreateCommandOption::new(
             CommandOptionType::SubCommand,
-            UserCommand::blacklist_me_from_tags
-                .get_str("SubCommand")
-                .unwrap(),
-            "The bot won't respond to your messages if you trip off a tag",
+            UserCommand::tag_export.get_str("SubCommand").unwrap(),
+            "Get a file of every tag in this server",
+        ))
+        .add_option(create_tag_import_option())
+        .add_option(create_tag_preference_option(
+            &UserCommand::blacklist_me_from_tags,
+            "The bot won't ping you if you trip off a tag, use it again to be pinged again",
+        ))
+        .add_option(create_tag_preference_option(
+            &UserCommand::tag_ignore_me,
+            "The bot won't respond to your messages at all if they trip off a tag, use it again to undo",
         ))
         .add_option(CreateCommandOption::new(
             CommandOptionType::SubCommand,
@@ -34,13 +69,51 @@ pub fn create_tag_commands() -> CreateCommand<'static> {
             "Set this channel as the channel where i will reply to tags",
         ))
         .add_option(create_tag_ban_option())
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::tag_unban.get_str("SubCommand").unwrap(),
+                "Lift the ban of a user from editing tags",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::User, "user", "The user to unban")
+                    .required(true),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::tag_bans.get_str("SubCommand").unwrap(),
+            "List the users banned from editing tags",
+        ))
+        .add_option(create_tag_audit_log_option())
+        .add_option(create_tag_permissions_option())
+        .add_option(create_tag_cooldown_option())
+        .add_option(create_tag_default_cooldown_option())
+        .add_option(create_tag_delivery_option())
+        .add_option(create_tag_default_delivery_option())
 }
 
-fn create_tag_creation_option() -> CreateCommandOption<'static> {
+fn create_tag_preference_option(
+    command: &UserCommand,
+    description: &'static str,
+) -> CreateCommandOption<'static> {
     CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        command.get_str("SubCommand").unwrap(),
+        description,
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Boolean,
+        "everywhere",
+        "Apply it in every server instead of only this one",
+    ))
+}
+
+fn create_tag_creation_option() -> CreateCommandOption<'static> {
+    let option = CreateCommandOption::new(
         CommandOptionType::SubCommand,
         UserCommand::create_tag.get_str("SubCommand").unwrap(),
-        "Create a tag for a word or list of words and a response whenever someone says that word",
+        "Create a tag for a word or list of words, leave the response out to write it over multiple lines",
     )
     .add_sub_option(
         CreateCommandOption::new(CommandOptionType::String, "tag", "What word to listen for")
@@ -50,10 +123,266 @@ fn create_tag_creation_option() -> CreateCommandOption<'static> {
         CreateCommandOption::new(
             CommandOptionType::String,
             "response",
-            "What the response should be when the tag is said",
+            "The response, can use {user}, {user.mention}, {channel}, {server}, {args}, {random:a|b}, {markov}",
+        ),
+    )
+    .add_sub_option(match_mode_option());
+
+    add_channel_scope_sub_options(option)
+}
+
+fn match_mode_option() -> CreateCommandOption<'static> {
+    let mut option = CreateCommandOption::new(
+        CommandOptionType::String,
+        "match-mode",
+        "How the tag is found in messages, single words and phrases by default",
+    );
+
+    for mode in TagMatchMode::iter() {
+        option = option.add_string_choice(mode.to_string(), mode.to_string());
+    }
+
+    option
+}
+
+fn create_tag_edit_option() -> CreateCommandOption<'static> {
+    let option = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::edit_tag.get_str("SubCommand").unwrap(),
+        "Change a tag you own",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to change")
+            .required(true),
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "new-tag",
+        "What to listen for instead",
+    ))
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "response",
+        "The new response",
+    ))
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Boolean,
+        "multi-line",
+        "Edit the response in a text box that can hold line breaks, starting from the response option",
+    ))
+    .add_sub_option(match_mode_option());
+
+    add_channel_scope_sub_options(option)
+}
+
+fn add_channel_scope_sub_options(
+    option: CreateCommandOption<'static>,
+) -> CreateCommandOption<'static> {
+    let mut channel_scope = CreateCommandOption::new(
+        CommandOptionType::String,
+        "channel-scope",
+        "Limit the tag to the channels option or keep it out of them, everywhere by default",
+    );
+
+    for scope in TagChannelScope::iter() {
+        channel_scope = channel_scope.add_string_choice(scope.to_string(), scope.to_string());
+    }
+
+    option
+        .add_sub_option(channel_scope)
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "channels",
+            "The channels of the allow-list or deny-list, like #general #memes",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "disable-in-nsfw",
+            "Don't respond in nsfw channels",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "disable-in-announcements",
+            "Don't respond in announcement channels",
+        ))
+}
+
+fn create_tag_transfer_option() -> CreateCommandOption<'static> {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::transfer_tag.get_str("SubCommand").unwrap(),
+        "Give a tag you own to someone else",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to give away")
+            .required(true),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::User, "user", "Who gets the tag")
+            .required(true),
+    )
+}
+
+fn create_tag_response_group() -> CreateCommandOption<'static> {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "response",
+        "Give a tag more responses to pick from at random",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::tag_response_add.get_str("SubCommand").unwrap(),
+            "Add a response to a tag you own",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to add to")
+                .required(true),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "response", "The new response")
+                .required(true),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "weight",
+                "How likely the response is compared to the others, the main response has 1",
+            )
+            .min_int_value(1)
+            .max_int_value(100),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::tag_response_remove
+                .get_str("SubCommand")
+                .unwrap(),
+            "Remove a response from a tag you own",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to remove from")
+                .required(true),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "id",
+                "The number of the response in /tag response list",
+            )
+            .required(true),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::tag_response_list
+                .get_str("SubCommand")
+                .unwrap(),
+            "List the responses of a tag",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to list")
+                .required(true),
+        ),
+    )
+    .add_sub_option(create_tag_response_type_option())
+}
+
+fn create_tag_response_type_option() -> CreateCommandOption<'static> {
+    let mut response_type = CreateCommandOption::new(
+        CommandOptionType::String,
+        "type",
+        "What the tag responds with, reaction tags need an emoji as their response",
+    )
+    .required(true);
+
+    for kind in TagResponseType::iter() {
+        response_type = response_type.add_string_choice(kind.to_string(), kind.to_string());
+    }
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_response_type
+            .get_str("SubCommand")
+            .unwrap(),
+        "Change what kind of message a tag you own responds with",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to change")
+            .required(true),
+    )
+    .add_sub_option(response_type)
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "title",
+        "The title of the embed",
+    ))
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "colour",
+        "The colour of the embed as a hex code like #FF0000",
+    ))
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Attachment,
+        "attachment",
+        "The file to send, up to 8MB",
+    ))
+}
+
+fn create_tag_list_option() -> CreateCommandOption<'static> {
+    let mut sort = CreateCommandOption::new(
+        CommandOptionType::String,
+        "sort",
+        "How the tags are ordered, by name by default",
+    );
+
+    for kind in TagListSort::iter() {
+        sort = sort.add_string_choice(kind.to_string(), kind.to_string());
+    }
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_list.get_str("SubCommand").unwrap(),
+        "List all of the tags",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "search",
+            "Only list tags with this in their listener or response",
+        )
+        .max_length(MAX_SEARCH_LENGTH),
+    )
+    .add_sub_option(sort)
+}
+
+fn create_tag_import_option() -> CreateCommandOption<'static> {
+    let mut conflict = CreateCommandOption::new(
+        CommandOptionType::String,
+        "conflict",
+        "What to do with tags that already exist, they're skipped by default",
+    );
+
+    for policy in ImportConflictPolicy::iter() {
+        conflict = conflict.add_string_choice(policy.to_string(), policy.to_string());
+    }
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_import.get_str("SubCommand").unwrap(),
+        "For moderators only, create the tags of a file from /tag export",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::Attachment,
+            "file",
+            "The file from /tag export",
         )
         .required(true),
     )
+    .add_sub_option(conflict)
 }
 
 fn create_tag_removal_option() -> CreateCommandOption<'static> {
@@ -64,7 +393,8 @@ fn create_tag_removal_option() -> CreateCommandOption<'static> {
     )
     .add_sub_option(
         CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to remove")
-            .required(true),
+            .required(true)
+            .set_autocomplete(true),
     )
 }
 
@@ -82,4 +412,180 @@ fn create_tag_ban_option() -> CreateCommandOption<'static> {
         )
         .required(true),
     )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "days",
+            "How many days the ban lasts",
+        )
+        .min_int_value(0)
+        .max_int_value(365),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "hours",
+            "How many hours the ban lasts on top of the days, leave both out to ban for good",
+        )
+        .min_int_value(0)
+        .max_int_value(23),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "reason",
+            "Why the user is banned",
+        )
+        .max_length(200),
+    )
+}
+
+fn create_tag_audit_log_option() -> CreateCommandOption<'static> {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_audit_log.get_str("SubCommand").unwrap(),
+        "See who created, edited and removed tags and who was banned",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::User,
+        "user",
+        "Only show what was done by or to this user",
+    ))
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::Integer, "page", "Which page to show")
+            .min_int_value(1),
+    )
+}
+
+fn create_tag_permissions_option() -> CreateCommandOption<'static> {
+    let mut action = CreateCommandOption::new(
+        CommandOptionType::String,
+        "action",
+        "What to choose the roles of, leave it out to see who can do what",
+    );
+
+    for kind in TagAction::iter() {
+        action = action.add_string_choice(kind.to_string(), kind.to_string());
+    }
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_permissions.get_str("SubCommand").unwrap(),
+        "Choose which roles can create, edit, remove and ban, admins only",
+    )
+    .add_sub_option(action)
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Role,
+        "role",
+        "The role to allow or disallow, leave it out to let anyone do it again",
+    ))
+}
+
+fn create_tag_cooldown_option() -> CreateCommandOption<'static> {
+    let option = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_cooldown.get_str("SubCommand").unwrap(),
+        "Choose how long a tag waits before responding again",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to change")
+            .required(true),
+    );
+
+    add_cooldown_sub_options(option).add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Boolean,
+        "use-default",
+        "Use the cooldowns of the server instead",
+    ))
+}
+
+fn create_tag_default_cooldown_option() -> CreateCommandOption<'static> {
+    add_cooldown_sub_options(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_default_cooldown
+            .get_str("SubCommand")
+            .unwrap(),
+        "Choose the cooldowns of tags that don't have their own",
+    ))
+}
+
+fn add_cooldown_sub_options(option: CreateCommandOption<'static>) -> CreateCommandOption<'static> {
+    option
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "seconds",
+                "Seconds between any two responses",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_COOLDOWN_SECONDS),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "channel-seconds",
+                "Seconds between responses in the same channel",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_COOLDOWN_SECONDS),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "user-seconds",
+                "Seconds between responses to the same user",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_COOLDOWN_SECONDS),
+        )
+}
+
+fn create_tag_delivery_option() -> CreateCommandOption<'static> {
+    let option = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_delivery.get_str("SubCommand").unwrap(),
+        "Choose how a tag posts its responses and when they're deleted",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "tag", "The tag to change")
+            .required(true),
+    );
+
+    add_delivery_sub_options(option).add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Boolean,
+        "use-default",
+        "Post the tag the way the server chooses instead",
+    ))
+}
+
+fn create_tag_default_delivery_option() -> CreateCommandOption<'static> {
+    add_delivery_sub_options(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        UserCommand::tag_default_delivery
+            .get_str("SubCommand")
+            .unwrap(),
+        "Choose how tags that don't choose themselves post their responses",
+    ))
+}
+
+fn add_delivery_sub_options(option: CreateCommandOption<'static>) -> CreateCommandOption<'static> {
+    let mut mode = CreateCommandOption::new(
+        CommandOptionType::String,
+        "mode",
+        "Post responses as a message, as a reply or in a thread on the message",
+    );
+
+    for kind in TagDeliveryMode::iter() {
+        mode = mode.add_string_choice(kind.to_string(), kind.to_string());
+    }
+
+    option.add_sub_option(mode).add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "delete-after",
+            "Seconds until responses are deleted, 0 keeps them",
+        )
+        .min_int_value(0)
+        .max_int_value(MAX_DELETE_AFTER_SECONDS),
+    )
 }
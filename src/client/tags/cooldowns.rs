@@ -0,0 +1,311 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use serenity::all::{ChannelId, CommandInteraction, Context, UserId};
+use sqlx::PgPool;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    audit_log::TagAuditLog,
+    data_access::{get_tag_settings, update_tag_cooldowns, upsert_tag_default_cooldowns},
+    find_tag_by_listener, is_moderator,
+    model::{Tag, TagAction, TagAuditAction, TagSettings},
+    permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+};
+
+/// How many cooldowns can be stored before the ones that ran out are cleaned up
+const MAX_COOLDOWNS_BEFORE_CLEANUP: usize = 10_000;
+/// How long a user waits for another tag response in their DMs when it can't be posted in the server
+const DIRECT_MESSAGE_COOLDOWN: Duration = Duration::from_mins(10);
+
+/// How long a tag waits before responding again, a zero duration doesn't wait
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Cooldowns {
+    /// Between any two responses of the tag
+    pub tag: Duration,
+    /// Between responses of the tag in the same channel
+    pub channel: Duration,
+    /// Between responses of the tag to the same user
+    pub user: Duration,
+}
+
+impl Cooldowns {
+    pub fn from_settings(settings: Option<&TagSettings>) -> Self {
+        settings.map_or_else(Self::default, |s| Self {
+            tag: seconds(s.cooldown_seconds),
+            channel: seconds(s.channel_cooldown_seconds),
+            user: seconds(s.user_cooldown_seconds),
+        })
+    }
+
+    /// The cooldowns the tag sets itself, with these as the defaults for the ones it doesn't
+    pub fn for_tag(self, tag: &Tag) -> Self {
+        Self {
+            tag: tag.cooldown_seconds.map_or(self.tag, seconds),
+            channel: tag.channel_cooldown_seconds.map_or(self.channel, seconds),
+            user: tag.user_cooldown_seconds.map_or(self.user, seconds),
+        }
+    }
+}
+
+pub fn seconds(seconds: i32) -> Duration {
+    Duration::from_secs(seconds.try_into().unwrap_or_default())
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum CooldownKey {
+    Tag(i32),
+    Channel(i32, ChannelId),
+    User(i32, UserId),
+    /// Tag responses sent to the DMs of the user
+    DirectMessage(UserId),
+}
+
+/// When the running cooldowns of tags run out
+#[derive(Default)]
+pub struct TagCooldowns {
+    expiries: HashMap<CooldownKey, Instant>,
+}
+
+impl TagCooldowns {
+    /// Whether one of the cooldowns of the tag is still running and the tag shouldn't respond
+    pub fn is_running(&self, tag_id: i32, channel_id: ChannelId, user_id: UserId) -> bool {
+        let now = Instant::now();
+
+        [
+            CooldownKey::Tag(tag_id),
+            CooldownKey::Channel(tag_id, channel_id),
+            CooldownKey::User(tag_id, user_id),
+        ]
+        .iter()
+        .any(|key| self.expiries.get(key).is_some_and(|expiry| *expiry > now))
+    }
+
+    /// Starts the cooldowns of the tag, has to be called once its response was sent
+    pub fn start(
+        &mut self,
+        tag_id: i32,
+        cooldowns: Cooldowns,
+        channel_id: ChannelId,
+        user_id: UserId,
+    ) {
+        let now = Instant::now();
+
+        if self.expiries.len() > MAX_COOLDOWNS_BEFORE_CLEANUP {
+            self.expiries.retain(|_, expiry| *expiry > now);
+        }
+
+        let keys = [
+            (CooldownKey::Tag(tag_id), cooldowns.tag),
+            (CooldownKey::Channel(tag_id, channel_id), cooldowns.channel),
+            (CooldownKey::User(tag_id, user_id), cooldowns.user),
+        ];
+
+        for (key, cooldown) in keys {
+            if !cooldown.is_zero() {
+                self.expiries.insert(key, now + cooldown);
+            }
+        }
+    }
+
+    /// Starts the cooldown of tag responses sent to the DMs of the user,
+    /// returns false without starting it if it's still running
+    pub fn start_direct_message(&mut self, user_id: UserId) -> bool {
+        let now = Instant::now();
+        let expiry = self
+            .expiries
+            .entry(CooldownKey::DirectMessage(user_id))
+            .or_insert(now);
+
+        if *expiry > now {
+            return false;
+        }
+
+        *expiry = now + DIRECT_MESSAGE_COOLDOWN;
+        true
+    }
+}
+
+/// Lets moderators choose the cooldowns of a single tag
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_cooldown_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    if !is_moderator(command) {
+        return command_response(ctx, command, "Only moderators can change tag cooldowns").await;
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    let (cooldown_seconds, channel_cooldown_seconds, user_cooldown_seconds) =
+        if command.data.get_optional_bool("use-default") == Some(true) {
+            (None, None, None)
+        } else {
+            (
+                get_seconds(command, "seconds").or(tag.cooldown_seconds),
+                get_seconds(command, "channel-seconds").or(tag.channel_cooldown_seconds),
+                get_seconds(command, "user-seconds").or(tag.user_cooldown_seconds),
+            )
+        };
+
+    update_tag_cooldowns(
+        tag.id,
+        cooldown_seconds,
+        channel_cooldown_seconds,
+        user_cooldown_seconds,
+        pool,
+    )
+    .await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+    TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Edit)
+        .tag(&tag.listener)
+        .details("changed the cooldowns")
+        .save(pool)
+        .await;
+
+    let show =
+        |seconds: Option<i32>| seconds.map_or("the server default".to_owned(), |s| format!("{s}s"));
+    let response = format!(
+        "The tag \"{}\" now waits {} between responses, {} in the same channel and {} for the same user",
+        tag.listener,
+        show(cooldown_seconds),
+        show(channel_cooldown_seconds),
+        show(user_cooldown_seconds)
+    );
+
+    command_response(ctx, command, &response).await
+}
+
+/// Lets moderators choose the cooldowns of the tags that don't set their own
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_default_cooldown_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if !is_moderator(command) {
+        return command_response(ctx, command, "Only moderators can change tag cooldowns").await;
+    }
+
+    let settings = get_tag_settings(guild_id.get() as i64, pool).await;
+    let settings = upsert_tag_default_cooldowns(
+        guild_id.get() as i64,
+        get_seconds(command, "seconds")
+            .unwrap_or(settings.as_ref().map_or(0, |s| s.cooldown_seconds)),
+        get_seconds(command, "channel-seconds")
+            .unwrap_or(settings.as_ref().map_or(0, |s| s.channel_cooldown_seconds)),
+        get_seconds(command, "user-seconds")
+            .unwrap_or(settings.as_ref().map_or(0, |s| s.user_cooldown_seconds)),
+        pool,
+    )
+    .await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+
+    let response = format!(
+        "Tags without their own cooldowns now wait {}s between responses, {}s in the same channel and {}s for the same user",
+        settings.cooldown_seconds,
+        settings.channel_cooldown_seconds,
+        settings.user_cooldown_seconds
+    );
+
+    command_response(ctx, command, &response).await
+}
+
+fn get_seconds(command: &CommandInteraction, name: &str) -> Option<i32> {
+    command
+        .data
+        .get_optional_int(name)
+        .and_then(|s| s.try_into().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::tags::matching::TagMatchMode;
+
+    use super::*;
+
+    #[test]
+    fn tags_override_the_default_cooldowns() {
+        let defaults = Cooldowns {
+            tag: seconds(10),
+            channel: seconds(20),
+            user: seconds(30),
+        };
+        let tag = Tag {
+            cooldown_seconds: Some(5),
+            user_cooldown_seconds: Some(0),
+            ..Tag::for_tests(1, "hello", TagMatchMode::Word)
+        };
+
+        let cooldowns = defaults.for_tag(&tag);
+
+        assert_eq!(cooldowns.tag, seconds(5));
+        assert_eq!(cooldowns.channel, seconds(20));
+        assert!(cooldowns.user.is_zero());
+        assert!(seconds(-5).is_zero());
+    }
+
+    #[test]
+    fn started_cooldowns_keep_the_tag_from_responding() {
+        let mut cooldowns = TagCooldowns::default();
+        let (channel, other_channel) = (ChannelId::new(1), ChannelId::new(2));
+        let (user, other_user) = (UserId::new(1), UserId::new(2));
+
+        assert!(!cooldowns.is_running(1, channel, user));
+
+        cooldowns.start(
+            1,
+            Cooldowns {
+                channel: seconds(60),
+                ..Cooldowns::default()
+            },
+            channel,
+            user,
+        );
+
+        assert!(cooldowns.is_running(1, channel, other_user));
+        assert!(!cooldowns.is_running(1, other_channel, user));
+        assert!(!cooldowns.is_running(2, channel, user));
+    }
+
+    #[test]
+    fn zero_cooldowns_are_not_started() {
+        let mut cooldowns = TagCooldowns::default();
+
+        cooldowns.start(1, Cooldowns::default(), ChannelId::new(1), UserId::new(1));
+
+        assert!(!cooldowns.is_running(1, ChannelId::new(1), UserId::new(1)));
+        assert!(cooldowns.expiries.is_empty());
+    }
+}
@@ -1,13 +1,26 @@
-use regex::Regex;
+use std::str::FromStr;
+
 use serenity::{
-    all::{CommandDataOptionValue, CommandInteraction, CreateInteractionResponseMessage},
+    all::{CommandInteraction, CreateInteractionResponseMessage},
     builder::CreateInteractionResponse,
     prelude::Context,
 };
 use sqlx::{Pool, Postgres};
 use tracing::{Instrument, info_span};
 
-use crate::client::tags::{data_access::get_tag_banned_user, user_banned_response};
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand,
+    tags::{
+        audit_log::TagAuditLog,
+        channel_scope::ChannelScope,
+        matching::{InvalidTagError, TagMatchMode, is_response_valid},
+        model::{TagAction, TagAuditAction},
+        permission_denied_response,
+        permissions::check_tag_permission,
+        refresh_tag_matcher,
+        response_modal::{TagCommandReply, ask_for_response},
+    },
+};
 
 #[tracing::instrument(skip(ctx))]
 pub async fn create_tag(ctx: &Context, command: &CommandInteraction, pool: &Pool<Postgres>) {
@@ -16,76 +29,99 @@ pub async fn create_tag(ctx: &Context, command: &CommandInteraction, pool: &Pool
         return;
     };
 
-    let (listener, response) = get_listener_and_response(command);
+    let (listener, response, match_mode) = get_listener_and_response(command);
 
-    if !is_tag_valid(&response, &listener) {
-        invalid_tag_response(command, ctx).await;
+    if let Err(e) = match_mode.validate(&listener) {
+        invalid_tag_response(&TagCommandReply::Command(command), &e, ctx).await;
         return;
     }
 
-    if get_tag_banned_user(command.user.id.get() as i64, guild_id.get() as i64, pool)
-        .await
-        .is_some()
-    {
-        user_banned_response(command, ctx).await;
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Create, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return;
+    }
+
+    let channel_scope = match ChannelScope::from_command(ctx, command, None, pool).await {
+        Ok(channel_scope) => channel_scope,
+        Err(e) => {
+            invalid_channels_response(command, &e, ctx).await;
+            return;
+        }
+    };
+
+    // Without a response option the response is written in a modal so it can span multiple lines
+    let (response, reply) = match response {
+        Some(response) => (response, TagCommandReply::Command(command)),
+        None => match ask_for_response(ctx, command, "Create tag", None).await {
+            Ok(Some((response, reply))) => (response, reply),
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("Couldn't ask for the response of the tag: {e:?}");
+                return;
+            }
+        },
+    };
+
+    if let Err(e) = is_tag_valid(&response, &listener, match_mode) {
+        invalid_tag_response(&reply, &e, ctx).await;
         return;
     }
 
     match super::data_access::create_tag(
-        listener.to_lowercase().trim().to_owned(),
+        match_mode.normalize_listener(&listener),
         response.trim().to_owned(),
         command.user.name.to_string(),
         command.user.id.get() as i64,
         guild_id.get() as i64,
+        match_mode.to_string(),
         pool,
     )
     .await
     {
         Ok(tag) => {
-            tag_created_response(command, &tag.listener, ctx).await;
+            channel_scope.save(tag.id, pool).await;
+            refresh_tag_matcher(ctx, guild_id, pool).await;
+            TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Create)
+                .tag(&tag.listener)
+                .save(pool)
+                .await;
+            tag_created_response(&reply, &tag.listener, ctx).await;
         }
         Err(e) => match e {
             super::data_access::CreateTagError::TagWithSameListenerExists => {
-                tag_exists_response(command, &listener, ctx).await;
+                tag_exists_response(&reply, &listener, ctx).await;
             }
         },
     }
 }
 
-async fn tag_exists_response(command: &CommandInteraction, listener: &str, ctx: &Context) {
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content(format!("The tag \"{listener}\" already exists")),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
+async fn tag_exists_response(reply: &TagCommandReply<'_>, listener: &str, ctx: &Context) {
+    reply
+        .send(ctx, &format!("The tag \"{listener}\" already exists"))
         .await
         .expect("Error creating interaction response");
 }
 
-async fn tag_created_response(command: &CommandInteraction, listener: &str, ctx: &Context) {
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new().content(format!("Created tag {listener}")),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
+async fn tag_created_response(reply: &TagCommandReply<'_>, listener: &str, ctx: &Context) {
+    reply
+        .send(ctx, &format!("Created tag {listener}"))
+        .await
+        .expect("Error creating interaction response");
+}
+
+async fn invalid_tag_response(reply: &TagCommandReply<'_>, error: &InvalidTagError, ctx: &Context) {
+    reply
+        .send(ctx, &error.to_string())
         .await
         .expect("Error creating interaction response");
 }
 
-async fn invalid_tag_response(command: &CommandInteraction, ctx: &Context) {
+async fn invalid_channels_response(command: &CommandInteraction, error: &str, ctx: &Context) {
     command
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content("Tags can't contain mentions or non alphanumeric characters"),
+                CreateInteractionResponseMessage::new().content(error),
             ),
         )
         .instrument(info_span!("Sending message"))
@@ -107,39 +143,28 @@ async fn tag_outside_server_response(command: &CommandInteraction, ctx: &Context
         .expect("Error creating interaction response");
 }
 
-fn get_listener_and_response(command: &CommandInteraction) -> (String, String) {
-    let (listener, response) = if let CommandDataOptionValue::SubCommand(sub_command) =
-        command.data.options.first().unwrap().value.clone()
-    {
-        (
-            sub_command.first().unwrap().value.clone(),
-            sub_command.get(1).unwrap().value.clone(),
-        )
-    } else {
-        panic!("The first option should be a SubCommand");
-    };
-
-    let CommandDataOptionValue::String(listener) = listener else {
-        panic!("Expected listener to be a string")
-    };
-
-    let CommandDataOptionValue::String(response) = response else {
-        panic!("Expected listener to be a string")
-    };
-
-    (listener.to_string(), response.to_string())
+fn get_listener_and_response(
+    command: &CommandInteraction,
+) -> (String, Option<String>, TagMatchMode) {
+    let listener = command.data.get_string("tag");
+    let response = command.data.get_optional_string("response");
+    let match_mode = command
+        .data
+        .get_optional_string("match-mode")
+        .and_then(|m| TagMatchMode::from_str(&m).ok())
+        .unwrap_or_else(|| TagMatchMode::from_listener(&listener));
+
+    (listener, response, match_mode)
 }
 
-fn is_tag_valid(response: &str, listener: &str) -> bool {
-    let user_regex = Regex::new(r"<@!?(\d+)>").expect("Invalid regular expression");
-    let role_regex = Regex::new(r"<@&(\d+)>").expect("Invalid regular expression");
-    let alphanumeric_regex = Regex::new(r"[^A-Za-z0-9 ]").expect("Invalid regular expression");
-
-    !(user_regex.is_match(response)
-        || user_regex.is_match(listener)
-        || role_regex.is_match(response)
-        || role_regex.is_match(listener)
-        || response.contains("@everyone")
-        || response.contains("@here")
-        || alphanumeric_regex.is_match(listener))
+pub fn is_tag_valid(
+    response: &str,
+    listener: &str,
+    match_mode: TagMatchMode,
+) -> Result<(), InvalidTagError> {
+    if !is_response_valid(response) {
+        return Err(InvalidTagError::ContainsMention);
+    }
+
+    match_mode.validate(listener)
 }
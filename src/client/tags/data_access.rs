@@ -1,11 +1,15 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
 use thiserror::Error;
 
 use crate::client::tags::model::TagBannedUser;
 
 use super::{
     Tag,
-    model::{TagBlacklistedUser, TagChannel},
+    model::{
+        TagAuditEntry, TagChannel, TagListEntry, TagPermissionRole, TagResponse, TagScopeChannel,
+        TagSettings, TagUseStats, TagUserPreference, TopTag,
+    },
 };
 
 #[derive(Debug, Error)]
@@ -20,28 +24,73 @@ pub async fn create_tag(
     creator_name: String,
     creator_id: i64,
     server_id: i64,
-    pool: &PgPool,
+    match_mode: String,
+    executor: impl PgExecutor<'_>,
 ) -> Result<Tag, CreateTagError> {
-    let created_tag_id = sqlx::query!(
+    sqlx::query_as!(
+        Tag,
         r#"
-		INSERT INTO tags ( listener, response, creator_name, creator_id, server_id )
-		VALUES ( $1, $2, $3, $4, $5)
-        RETURNING id
+		INSERT INTO tags ( listener, response, creator_name, creator_id, server_id, match_mode )
+		VALUES ( $1, $2, $3, $4, $5, $6 )
+        RETURNING *
 		"#,
         listener,
         response,
         creator_name,
         creator_id,
-        server_id
+        server_id,
+        match_mode
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
-    .or(Err(CreateTagError::TagWithSameListenerExists))?
-    .id;
+    .or(Err(CreateTagError::TagWithSameListenerExists))
+}
 
-    Ok(get_tag_by_id(created_tag_id, server_id, pool)
-        .await
-        .unwrap())
+pub async fn update_tag(
+    id: i32,
+    listener: String,
+    response: String,
+    match_mode: String,
+    executor: impl PgExecutor<'_>,
+) -> Result<Tag, CreateTagError> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        UPDATE tags
+        SET listener = $2, response = $3, match_mode = $4
+        WHERE id = $1
+        RETURNING *
+        "#,
+        id,
+        listener,
+        response,
+        match_mode
+    )
+    .fetch_one(executor)
+    .await
+    .or(Err(CreateTagError::TagWithSameListenerExists))
+}
+
+pub async fn update_tag_creator(
+    id: i32,
+    creator_name: String,
+    creator_id: i64,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE tags
+        SET creator_name = $2, creator_id = $3
+        WHERE id = $1
+        "#,
+        id,
+        creator_name,
+        creator_id
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
 }
 
 pub async fn delete_tag(id: i32, pool: &PgPool) -> u64 {
@@ -58,7 +107,11 @@ pub async fn delete_tag(id: i32, pool: &PgPool) -> u64 {
     .rows_affected()
 }
 
-pub async fn get_tag_by_listener(listener: &str, server_id: i64, pool: &PgPool) -> Option<Tag> {
+pub async fn get_tag_by_listener(
+    listener: &str,
+    server_id: i64,
+    executor: impl PgExecutor<'_>,
+) -> Option<Tag> {
     sqlx::query_as!(
         Tag,
         r#"
@@ -68,32 +121,40 @@ pub async fn get_tag_by_listener(listener: &str, server_id: i64, pool: &PgPool)
         listener,
         server_id
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await
     .unwrap()
 }
 
-pub async fn get_tag_by_id(id: i32, server_id: i64, pool: &PgPool) -> Option<Tag> {
+pub async fn get_tags_by_server_id(server_id: i64, pool: &PgPool) -> Vec<Tag> {
     sqlx::query_as!(
         Tag,
         r#"
         SELECT * FROM tags
-        WHERE id = $1 AND server_id = $2
+        WHERE server_id = $1
         "#,
-        id,
         server_id
     )
-    .fetch_optional(pool)
+    .fetch_all(pool)
     .await
     .unwrap()
 }
 
-pub async fn get_tags_by_server_id(server_id: i64, pool: &PgPool) -> Vec<Tag> {
+pub async fn get_tag_list_entries(server_id: i64, pool: &PgPool) -> Vec<TagListEntry> {
     sqlx::query_as!(
-        Tag,
+        TagListEntry,
         r#"
-        SELECT * FROM tags
-        WHERE server_id = $1
+        SELECT
+            tags.listener,
+            tags.response,
+            tags.creator_name,
+            tags.match_mode,
+            COALESCE(MAX(tag_use_totals.uses), 0) + COUNT(tag_uses.id) AS "uses!"
+        FROM tags
+        LEFT JOIN tag_uses ON tag_uses.tag_id = tags.id
+        LEFT JOIN tag_use_totals ON tag_use_totals.tag_id = tags.id
+        WHERE tags.server_id = $1
+        GROUP BY tags.id, tags.listener, tags.response, tags.creator_name, tags.match_mode
         "#,
         server_id
     )
@@ -102,27 +163,108 @@ pub async fn get_tags_by_server_id(server_id: i64, pool: &PgPool) -> Vec<Tag> {
     .unwrap()
 }
 
-pub async fn get_tag_blacklisted_user(user_id: i64, pool: &PgPool) -> Option<TagBlacklistedUser> {
+pub async fn update_tag_cooldowns(
+    id: i32,
+    cooldown_seconds: Option<i32>,
+    channel_cooldown_seconds: Option<i32>,
+    user_cooldown_seconds: Option<i32>,
+    executor: impl PgExecutor<'_>,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE tags
+        SET cooldown_seconds = $2, channel_cooldown_seconds = $3, user_cooldown_seconds = $4
+        WHERE id = $1
+        "#,
+        id,
+        cooldown_seconds,
+        channel_cooldown_seconds,
+        user_cooldown_seconds
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn update_tag_delivery(
+    id: i32,
+    delivery_mode: Option<String>,
+    delete_after_seconds: Option<i32>,
+    executor: impl PgExecutor<'_>,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE tags
+        SET delivery_mode = $2, delete_after_seconds = $3
+        WHERE id = $1
+        "#,
+        id,
+        delivery_mode,
+        delete_after_seconds
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn update_tag_response_type(
+    id: i32,
+    response_type: String,
+    embed_title: Option<String>,
+    embed_colour: Option<i32>,
+    attachment_file: Option<String>,
+    executor: impl PgExecutor<'_>,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE tags
+        SET response_type = $2, embed_title = $3, embed_colour = $4, attachment_file = $5
+        WHERE id = $1
+        "#,
+        id,
+        response_type,
+        embed_title,
+        embed_colour,
+        attachment_file
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn create_tag_response(
+    tag_id: i32,
+    response: String,
+    weight: i32,
+    executor: impl PgExecutor<'_>,
+) -> TagResponse {
     sqlx::query_as!(
-        TagBlacklistedUser,
+        TagResponse,
         r#"
-        SELECT * FROM tag_blacklisted_users
-        WHERE user_id = $1
+        INSERT INTO tag_responses ( tag_id, response, weight )
+        VALUES ( $1, $2, $3 )
+        RETURNING *
         "#,
-        user_id
+        tag_id,
+        response,
+        weight
     )
-    .fetch_optional(pool)
+    .fetch_one(executor)
     .await
     .unwrap()
 }
 
-pub async fn delete_tag_blacklisted_user(user_id: i64, pool: &PgPool) -> u64 {
+pub async fn delete_tag_response(id: i32, tag_id: i32, pool: &PgPool) -> u64 {
     sqlx::query!(
         r#"
-        DELETE FROM tag_blacklisted_users
-        WHERE user_id= $1
+        DELETE FROM tag_responses
+        WHERE id = $1 AND tag_id = $2
         "#,
-        user_id
+        id,
+        tag_id
     )
     .execute(pool)
     .await
@@ -130,21 +272,165 @@ pub async fn delete_tag_blacklisted_user(user_id: i64, pool: &PgPool) -> u64 {
     .rows_affected()
 }
 
-pub async fn create_tag_blacklisted_user(user_id: i64, pool: &PgPool) -> TagBlacklistedUser {
+pub async fn delete_tag_responses_by_tag_id(tag_id: i32, executor: impl PgExecutor<'_>) -> u64 {
     sqlx::query!(
         r#"
-		INSERT INTO tag_blacklisted_users ( user_id )
-		VALUES ( $1 )
-		"#,
-        user_id
+        DELETE FROM tag_responses
+        WHERE tag_id = $1
+        "#,
+        tag_id
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn get_tag_responses_by_tag_id(tag_id: i32, pool: &PgPool) -> Vec<TagResponse> {
+    sqlx::query_as!(
+        TagResponse,
+        r#"
+        SELECT * FROM tag_responses
+        WHERE tag_id = $1
+        ORDER BY id
+        "#,
+        tag_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_tag_responses_by_server_id(server_id: i64, pool: &PgPool) -> Vec<TagResponse> {
+    sqlx::query_as!(
+        TagResponse,
+        r#"
+        SELECT tag_responses.* FROM tag_responses
+        JOIN tags ON tags.id = tag_responses.tag_id
+        WHERE tags.server_id = $1
+        "#,
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_tag_settings(server_id: i64, pool: &PgPool) -> Option<TagSettings> {
+    sqlx::query_as!(
+        TagSettings,
+        r#"
+        SELECT * FROM tag_settings
+        WHERE server_id = $1
+        "#,
+        server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_tag_default_cooldowns(
+    server_id: i64,
+    cooldown_seconds: i32,
+    channel_cooldown_seconds: i32,
+    user_cooldown_seconds: i32,
+    pool: &PgPool,
+) -> TagSettings {
+    sqlx::query_as!(
+        TagSettings,
+        r#"
+        INSERT INTO tag_settings ( server_id, cooldown_seconds, channel_cooldown_seconds, user_cooldown_seconds )
+        VALUES ( $1, $2, $3, $4 )
+        ON CONFLICT ( server_id ) DO UPDATE
+        SET cooldown_seconds = $2, channel_cooldown_seconds = $3, user_cooldown_seconds = $4
+        RETURNING *
+        "#,
+        server_id,
+        cooldown_seconds,
+        channel_cooldown_seconds,
+        user_cooldown_seconds
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_tag_default_delivery(
+    server_id: i64,
+    delivery_mode: String,
+    delete_after_seconds: i32,
+    pool: &PgPool,
+) -> TagSettings {
+    sqlx::query_as!(
+        TagSettings,
+        r#"
+        INSERT INTO tag_settings ( server_id, delivery_mode, delete_after_seconds )
+        VALUES ( $1, $2, $3 )
+        ON CONFLICT ( server_id ) DO UPDATE
+        SET delivery_mode = $2, delete_after_seconds = $3
+        RETURNING *
+        "#,
+        server_id,
+        delivery_mode,
+        delete_after_seconds
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_tag_user_preferences(pool: &PgPool) -> Vec<TagUserPreference> {
+    sqlx::query_as!(
+        TagUserPreference,
+        r#"
+        SELECT * FROM tag_user_preferences
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn upsert_tag_user_preference(
+    user_id: i64,
+    server_id: i64,
+    preference: String,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_user_preferences ( user_id, server_id, preference )
+        VALUES ( $1, $2, $3 )
+        ON CONFLICT (user_id, server_id) DO UPDATE
+        SET preference = EXCLUDED.preference
+        "#,
+        user_id,
+        server_id,
+        preference
     )
     .execute(pool)
     .await
-    .unwrap();
+    .unwrap()
+    .rows_affected()
+}
 
-    get_tag_blacklisted_user(user_id, pool).await.unwrap()
+pub async fn delete_tag_user_preference(user_id: i64, server_id: i64, pool: &PgPool) -> u64 {
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_user_preferences
+        WHERE user_id = $1 AND server_id = $2
+        "#,
+        user_id,
+        server_id
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
 }
 
+/// Gets the ban of the user if it hasn't run out
 pub async fn get_tag_banned_user(
     user_id: i64,
     server_id: i64,
@@ -154,7 +440,7 @@ pub async fn get_tag_banned_user(
         TagBannedUser,
         r#"
         SELECT * FROM tag_bans
-        WHERE user_id = $1 AND server_id = $2
+        WHERE user_id = $1 AND server_id = $2 AND (expires_at IS NULL OR expires_at > now())
         "#,
         user_id,
         server_id
@@ -164,6 +450,25 @@ pub async fn get_tag_banned_user(
     .unwrap()
 }
 
+/// Gets the bans of the server that haven't run out, the newest first
+pub async fn get_tag_banned_users_by_server_id(
+    server_id: i64,
+    pool: &PgPool,
+) -> Vec<TagBannedUser> {
+    sqlx::query_as!(
+        TagBannedUser,
+        r#"
+        SELECT * FROM tag_bans
+        WHERE server_id = $1 AND (expires_at IS NULL OR expires_at > now())
+        ORDER BY banned_at DESC
+        "#,
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
 pub async fn delete_tag_banned_user(user_id: i64, server_id: i64, pool: &PgPool) -> u64 {
     sqlx::query!(
         r#"
@@ -179,20 +484,178 @@ pub async fn delete_tag_banned_user(user_id: i64, server_id: i64, pool: &PgPool)
     .rows_affected()
 }
 
-pub async fn create_tag_banned_user(user_id: i64, server_id: i64, pool: &PgPool) -> TagBannedUser {
+/// Deletes the bans that ran out and returns them
+pub async fn delete_expired_tag_banned_users(pool: &PgPool) -> Vec<TagBannedUser> {
+    sqlx::query_as!(
+        TagBannedUser,
+        r#"
+        DELETE FROM tag_bans
+        WHERE expires_at <= now()
+        RETURNING *
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+/// Bans the user or replaces their ban
+pub async fn upsert_tag_banned_user(
+    user_id: i64,
+    server_id: i64,
+    reason: Option<String>,
+    banned_by: i64,
+    expires_at: Option<DateTime<Utc>>,
+    pool: &PgPool,
+) -> TagBannedUser {
+    sqlx::query_as!(
+        TagBannedUser,
+        r#"
+        INSERT INTO tag_bans ( user_id, server_id, reason, banned_by, expires_at )
+        VALUES ( $1, $2, $3, $4, $5 )
+        ON CONFLICT (user_id, server_id) DO UPDATE
+        SET reason = EXCLUDED.reason, banned_by = EXCLUDED.banned_by,
+            banned_at = now(), expires_at = EXCLUDED.expires_at
+        RETURNING *
+        "#,
+        user_id,
+        server_id,
+        reason,
+        banned_by,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn create_tag_audit_entry(
+    server_id: i64,
+    user_id: i64,
+    action: String,
+    tag_listener: Option<String>,
+    target_user_id: Option<i64>,
+    details: Option<String>,
+    executor: impl PgExecutor<'_>,
+) {
     sqlx::query!(
         r#"
-		INSERT INTO tag_bans ( user_id, server_id )
-		VALUES ( $1, $2 )
-		"#,
+        INSERT INTO tag_audit_log ( server_id, user_id, action, tag_listener, target_user_id, details )
+        VALUES ( $1, $2, $3, $4, $5, $6 )
+        "#,
+        server_id,
+        user_id,
+        action,
+        tag_listener,
+        target_user_id,
+        details
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Gets a page of the audit log of the server, the newest first
+pub async fn get_tag_audit_entries(
+    server_id: i64,
+    user_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> Vec<TagAuditEntry> {
+    sqlx::query_as!(
+        TagAuditEntry,
+        r#"
+        SELECT * FROM tag_audit_log
+        WHERE server_id = $1 AND ($2::BIGINT IS NULL OR user_id = $2 OR target_user_id = $2)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        server_id,
         user_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_tag_permission_roles(server_id: i64, pool: &PgPool) -> Vec<TagPermissionRole> {
+    sqlx::query_as!(
+        TagPermissionRole,
+        r#"
+        SELECT * FROM tag_permission_roles
+        WHERE server_id = $1
+        ORDER BY action, role_id
+        "#,
         server_id
     )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn create_tag_permission_role(
+    server_id: i64,
+    action: String,
+    role_id: i64,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_permission_roles ( server_id, action, role_id )
+        VALUES ( $1, $2, $3 )
+        ON CONFLICT DO NOTHING
+        "#,
+        server_id,
+        action,
+        role_id
+    )
     .execute(pool)
     .await
-    .unwrap();
+    .unwrap()
+    .rows_affected()
+}
 
-    get_tag_banned_user(user_id, server_id, pool).await.unwrap()
+pub async fn delete_tag_permission_role(
+    server_id: i64,
+    action: String,
+    role_id: i64,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_permission_roles
+        WHERE server_id = $1 AND action = $2 AND role_id = $3
+        "#,
+        server_id,
+        action,
+        role_id
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn delete_tag_permission_roles_by_action(
+    server_id: i64,
+    action: String,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_permission_roles
+        WHERE server_id = $1 AND action = $2
+        "#,
+        server_id,
+        action
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
 }
 
 pub async fn get_tag_channel(server_id: i64, pool: &PgPool) -> Option<TagChannel> {
@@ -240,3 +703,224 @@ pub async fn create_tag_channel(server_id: i64, channel_id: i64, pool: &PgPool)
 
     get_tag_channel(server_id, pool).await.unwrap()
 }
+
+pub async fn delete_tag_channel(server_id: i64, channel_id: i64, pool: &PgPool) -> u64 {
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_channels
+        WHERE server_id = $1 AND channel_id = $2
+        "#,
+        server_id,
+        channel_id,
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn create_tag_use(
+    tag_id: i32,
+    server_id: i64,
+    channel_id: i64,
+    user_id: i64,
+    pool: &PgPool,
+) {
+    sqlx::query!(
+        r#"
+		INSERT INTO tag_uses ( tag_id, server_id, channel_id, user_id )
+		VALUES ( $1, $2, $3, $4 )
+		"#,
+        tag_id,
+        server_id,
+        channel_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Folds the uses before `used_before` into the totals of their tags and deletes them.
+/// Returns how many uses were folded.
+pub async fn archive_tag_uses(used_before: DateTime<Utc>, pool: &PgPool) -> u64 {
+    let mut tx = pool.begin().await.unwrap();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_use_totals ( tag_id, uses, last_used )
+        SELECT tag_id, COUNT(*), MAX(used_at)
+        FROM tag_uses
+        WHERE used_at < $1
+        GROUP BY tag_id
+        ON CONFLICT ( tag_id ) DO UPDATE SET
+            uses = tag_use_totals.uses + EXCLUDED.uses,
+            last_used = GREATEST(tag_use_totals.last_used, EXCLUDED.last_used)
+        "#,
+        used_before
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    let archived = sqlx::query!(
+        r#"
+        DELETE FROM tag_uses
+        WHERE used_at < $1
+        "#,
+        used_before
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap()
+    .rows_affected();
+
+    tx.commit().await.unwrap();
+
+    archived
+}
+
+/// Counts every use of the tag and the uses since `recent_since`
+pub async fn get_tag_use_stats(
+    tag_id: i32,
+    recent_since: DateTime<Utc>,
+    pool: &PgPool,
+) -> TagUseStats {
+    sqlx::query_as!(
+        TagUseStats,
+        r#"
+        SELECT
+            COALESCE(MAX(tag_use_totals.uses), 0) + COUNT(tag_uses.id) AS "total!",
+            COUNT(tag_uses.id) FILTER (WHERE tag_uses.used_at >= $2) AS "recent!",
+            GREATEST(MAX(tag_use_totals.last_used), MAX(tag_uses.used_at)) AS last_used
+        FROM tags
+        LEFT JOIN tag_uses ON tag_uses.tag_id = tags.id
+        LEFT JOIN tag_use_totals ON tag_use_totals.tag_id = tags.id
+        WHERE tags.id = $1
+        "#,
+        tag_id,
+        recent_since
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+/// The most used tags of the server, ordered by their total uses
+pub async fn get_top_tags(
+    server_id: i64,
+    recent_since: DateTime<Utc>,
+    limit: i64,
+    pool: &PgPool,
+) -> Vec<TopTag> {
+    sqlx::query_as!(
+        TopTag,
+        r#"
+        SELECT
+            tags.listener,
+            COALESCE(MAX(tag_use_totals.uses), 0) + COUNT(tag_uses.id) AS "total!",
+            COUNT(tag_uses.id) FILTER (WHERE tag_uses.used_at >= $2) AS "recent!"
+        FROM tags
+        LEFT JOIN tag_uses ON tag_uses.tag_id = tags.id
+        LEFT JOIN tag_use_totals ON tag_use_totals.tag_id = tags.id
+        WHERE tags.server_id = $1
+        GROUP BY tags.id, tags.listener
+        HAVING COUNT(tag_uses.id) > 0 OR MAX(tag_use_totals.uses) > 0
+        ORDER BY "total!" DESC, tags.listener
+        LIMIT $3
+        "#,
+        server_id,
+        recent_since,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn update_tag_channel_scope(
+    id: i32,
+    channel_scope: String,
+    disabled_in_nsfw: bool,
+    disabled_in_announcements: bool,
+    executor: impl PgExecutor<'_>,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE tags
+        SET channel_scope = $2, disabled_in_nsfw = $3, disabled_in_announcements = $4
+        WHERE id = $1
+        "#,
+        id,
+        channel_scope,
+        disabled_in_nsfw,
+        disabled_in_announcements
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn get_tag_scope_channels_by_tag_id(tag_id: i32, pool: &PgPool) -> Vec<TagScopeChannel> {
+    sqlx::query_as!(
+        TagScopeChannel,
+        r#"
+        SELECT * FROM tag_scope_channels
+        WHERE tag_id = $1
+        "#,
+        tag_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_tag_scope_channels_by_server_id(
+    server_id: i64,
+    pool: &PgPool,
+) -> Vec<TagScopeChannel> {
+    sqlx::query_as!(
+        TagScopeChannel,
+        r#"
+        SELECT tag_scope_channels.* FROM tag_scope_channels
+        JOIN tags ON tags.id = tag_scope_channels.tag_id
+        WHERE tags.server_id = $1
+        "#,
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+/// Replaces the channels in the allow-list or deny-list of the tag
+pub async fn set_tag_scope_channels(tag_id: i32, channel_ids: &[i64], pool: &PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM tag_scope_channels
+        WHERE tag_id = $1
+        "#,
+        tag_id
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_scope_channels ( tag_id, channel_id )
+        SELECT $1, * FROM UNNEST($2::BIGINT[])
+        ON CONFLICT DO NOTHING
+        "#,
+        tag_id,
+        channel_ids
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    tx.commit().await.unwrap();
+}
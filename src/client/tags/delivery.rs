@@ -0,0 +1,659 @@
+use std::{borrow::Cow, str::FromStr, time::Duration};
+
+use anyhow::Context as _;
+use serenity::{
+    all::{
+        ButtonStyle, ChannelId, CommandInteraction, Context, CreateComponent, GenericChannelId,
+        GuildId, Message, Permissions, ReactionType, ThreadId,
+    },
+    builder::{CreateActionRow, CreateButton, CreateMessage, CreateThread, EditMessage},
+    prelude::Mentionable,
+};
+use sqlx::PgPool;
+use tokio::task;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::client::{
+    ComponentIds, get_option_from_command::GetOptionFromCommand, global_data::GetBotState,
+    helper_funcs::command_response,
+};
+
+use super::{
+    TagMatch,
+    audit_log::TagAuditLog,
+    can_manage_tag,
+    cooldowns::seconds,
+    data_access::{
+        delete_tag_channel, get_tag_channel, get_tag_settings, update_tag_delivery,
+        upsert_tag_default_delivery,
+    },
+    find_tag_by_listener, is_moderator,
+    model::{Tag, TagAction, TagAuditAction, TagChannel, TagDeliveryMode, TagSettings},
+    not_tag_owner_response, permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+    response_types::{TagResponseMessage, allowed_mentions},
+};
+
+/// How long the button to stop pinging stays under a response sent to another channel
+const STOP_PINGING_BUTTON_DURATION: Duration = Duration::from_secs(20);
+
+/// Discord doesn't accept longer thread names
+const MAX_THREAD_NAME_LENGTH: usize = 100;
+
+/// How a tag posts its response
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TagDelivery {
+    pub mode: TagDeliveryMode,
+    /// How long until the response is deleted, a zero duration keeps it
+    pub delete_after: Duration,
+}
+
+impl TagDelivery {
+    pub fn from_settings(settings: Option<&TagSettings>) -> Self {
+        settings.map_or_else(Self::default, |s| Self {
+            mode: TagDeliveryMode::from_str(&s.delivery_mode).unwrap_or_default(),
+            delete_after: seconds(s.delete_after_seconds),
+        })
+    }
+
+    /// The options the tag sets itself, with these as the defaults for the ones it doesn't
+    pub fn for_tag(self, tag: &Tag) -> Self {
+        Self {
+            mode: tag
+                .delivery_mode
+                .as_deref()
+                .and_then(|m| TagDeliveryMode::from_str(m).ok())
+                .unwrap_or(self.mode),
+            delete_after: tag.delete_after_seconds.map_or(self.delete_after, seconds),
+        }
+    }
+}
+
+/// What the cache knows about a channel of a guild
+enum CachedChannel {
+    /// The guild isn't cached so nothing is known about the channel
+    Unknown,
+    /// The guild is cached but the channel isn't in it, it was deleted or it's an archived thread
+    Missing,
+    Found {
+        /// The channel, or the channel the thread is in
+        channel_id: ChannelId,
+        is_thread: bool,
+        /// What the bot can do in the channel, unknown if the bot's member isn't cached
+        permissions: Option<Permissions>,
+    },
+}
+
+impl CachedChannel {
+    fn find(ctx: &Context, guild_id: GuildId, channel_id: GenericChannelId) -> Self {
+        let bot_id = ctx.cache.current_user().id;
+        let Some(guild) = ctx.cache.guild(guild_id) else {
+            return Self::Unknown;
+        };
+
+        // Threads take the permissions of the channel they're in
+        let (channel, is_thread) = match guild.channels.iter().find(|c| c.id.widen() == channel_id)
+        {
+            Some(channel) => (channel, false),
+            None => {
+                let parent = guild
+                    .threads
+                    .iter()
+                    .find(|t| t.id.widen() == channel_id)
+                    .and_then(|t| guild.channels.get(&t.parent_id));
+
+                match parent {
+                    Some(parent) => (parent, true),
+                    None => return Self::Missing,
+                }
+            }
+        };
+
+        Self::Found {
+            channel_id: channel.id,
+            is_thread,
+            permissions: guild
+                .members
+                .get(&bot_id)
+                .map(|m| guild.user_permissions_in(channel, m)),
+        }
+    }
+
+    /// Whether the bot can send a message that needs the permissions, which is assumed when it can't be checked
+    fn can_send(&self, needed: Permissions) -> bool {
+        match self {
+            Self::Found {
+                is_thread,
+                permissions: Some(permissions),
+                ..
+            } => {
+                let send = if *is_thread {
+                    Permissions::SEND_MESSAGES_IN_THREADS
+                } else {
+                    Permissions::SEND_MESSAGES
+                };
+                permissions.contains(needed | send | Permissions::VIEW_CHANNEL)
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the bot can react to messages, which is assumed when it can't be checked
+    fn can_react(&self) -> bool {
+        match self {
+            Self::Found {
+                permissions: Some(permissions),
+                ..
+            } => permissions.contains(
+                Permissions::ADD_REACTIONS
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::VIEW_CHANNEL,
+            ),
+            _ => true,
+        }
+    }
+
+    /// The channel to start a thread in if the bot can start one and send the message in it
+    fn thread_channel(&self, needed: Permissions) -> Option<ChannelId> {
+        match self {
+            Self::Found {
+                channel_id,
+                is_thread: false,
+                permissions: Some(permissions),
+            } if permissions.contains(
+                needed
+                    | Permissions::VIEW_CHANNEL
+                    | Permissions::CREATE_PUBLIC_THREADS
+                    | Permissions::SEND_MESSAGES_IN_THREADS,
+            ) =>
+            {
+                Some(*channel_id)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reacts to the message that tripped off the tag if the bot is allowed to
+///
+/// Returns whether it reacted.
+pub async fn react_to_tag(ctx: &Context, msg: &Message, reaction: &ReactionType) -> bool {
+    let Some(guild_id) = msg.guild_id else {
+        return false;
+    };
+
+    if !CachedChannel::find(ctx, guild_id, msg.channel_id).can_react() {
+        info!("Not allowed to react to the message that tripped off the tag");
+        return false;
+    }
+
+    match msg
+        .react(&ctx.http, reaction.clone())
+        .instrument(info_span!("Reacting to message"))
+        .await
+    {
+        Ok(_) => true,
+        Err(err) => {
+            error!("Couldn't react to the message: {err}");
+            false
+        }
+    }
+}
+
+/// Sends the response of a tag where it can be seen and deletes it later if the tag or server asks for it
+///
+/// The permissions of the bot are checked before anything is sent.
+/// It's sent in the first of these that works:
+/// - the tag response channel of the server if it has one and the tag is posted as a message,
+///   the channel is forgotten if it was deleted
+/// - a thread started on the message that tripped off the tag if the tag is posted in threads
+/// - the channel the tag was tripped off in, as a reply if the tag is posted as a reply
+/// - a thread started on the message that tripped off the tag
+/// - the DMs of the author, unless they asked not to be pinged or got a response there a moment ago
+///
+/// Nobody but the author is pinged, and they aren't either if they asked not to be.
+/// Returns whether the response was sent anywhere.
+#[tracing::instrument(skip(ctx, msg, tag_match, response, pool))]
+pub async fn deliver_tag_response(
+    ctx: &Context,
+    msg: &Message,
+    tag_match: &TagMatch,
+    response: &TagResponseMessage,
+    ping: bool,
+    pool: &PgPool,
+) -> bool {
+    let Some(guild_id) = msg.guild_id else {
+        return false;
+    };
+
+    match send_tag_response(ctx, guild_id, msg, tag_match, response, ping, pool).await {
+        Some(sent) => {
+            clean_up_later(ctx, sent, tag_match.delivery.delete_after);
+            true
+        }
+        None => false,
+    }
+}
+
+async fn send_tag_response(
+    ctx: &Context,
+    guild_id: GuildId,
+    msg: &Message,
+    tag_match: &TagMatch,
+    response: &TagResponseMessage,
+    ping: bool,
+    pool: &PgPool,
+) -> Option<Message> {
+    let mode = tag_match.delivery.mode;
+    let listener = &tag_match.tag.listener;
+    let needed = response.needed_permissions();
+    let pinged_user = ping.then_some(msg.author.id);
+
+    // Replies and threads stay with the message that tripped off the tag
+    if mode == TagDeliveryMode::Message
+        && let Some(tag_channel) = get_tag_channel(guild_id.get() as i64, pool).await
+        && let Some(sent) =
+            send_in_tag_channel(ctx, guild_id, &tag_channel, msg, response, ping, pool).await
+    {
+        return Some(sent);
+    }
+
+    let channel = CachedChannel::find(ctx, guild_id, msg.channel_id);
+    let mut thread_channel = channel.thread_channel(needed);
+
+    if mode == TagDeliveryMode::Thread
+        && let Some(channel_id) = thread_channel.take()
+        && let Some(sent) =
+            send_in_thread(ctx, guild_id, channel_id, msg, listener, response, ping).await
+    {
+        return Some(sent);
+    }
+
+    let (tag_response, needed_in_channel) = if mode == TagDeliveryMode::Reply {
+        (
+            reply_to(msg, response, ping),
+            needed | Permissions::READ_MESSAGE_HISTORY,
+        )
+    } else {
+        (response.to_message(None, pinged_user), needed)
+    };
+
+    if channel.can_send(needed_in_channel) {
+        match msg
+            .channel_id
+            .send_message(&ctx.http, tag_response)
+            .instrument(info_span!("Sending message"))
+            .await
+        {
+            Ok(sent) => return Some(sent),
+            Err(err) => warn!("Couldn't send the tag response in the channel: {err}"),
+        }
+    }
+
+    if let Some(channel_id) = thread_channel
+        && let Some(sent) =
+            send_in_thread(ctx, guild_id, channel_id, msg, listener, response, ping).await
+    {
+        return Some(sent);
+    }
+
+    if !ping {
+        info!("Nowhere to send the tag response and the author doesn't want to be pinged");
+        return None;
+    }
+
+    // Someone who keeps tripping off tags where the bot can't respond would get flooded with DMs
+    if !ctx
+        .bot_state()
+        .write()
+        .await
+        .tag_cooldowns
+        .start_direct_message(msg.author.id)
+    {
+        info!("Nowhere to send the tag response and the author got one in DMs a moment ago");
+        return None;
+    }
+
+    match msg
+        .author
+        .id
+        .direct_message(&ctx.http, response.to_message(Some(msg.link()), None))
+        .instrument(info_span!("Sending message"))
+        .await
+    {
+        Ok(sent) => Some(sent),
+        Err(err) => {
+            warn!("Couldn't send the tag response anywhere, not even in DMs: {err}");
+            None
+        }
+    }
+}
+
+async fn send_in_tag_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    tag_channel: &TagChannel,
+    msg: &Message,
+    response: &TagResponseMessage,
+    ping: bool,
+    pool: &PgPool,
+) -> Option<Message> {
+    let channel_id = GenericChannelId::new(tag_channel.channel_id as u64);
+    let channel = CachedChannel::find(ctx, guild_id, channel_id);
+
+    if !channel.can_send(response.needed_permissions()) {
+        warn!("Not allowed to send tag responses in the tag response channel");
+        return None;
+    }
+
+    let tag_response = if msg.channel_id == channel_id {
+        response.to_message(None, ping.then_some(msg.author.id))
+    } else {
+        mention_author(msg, response, ping)
+    };
+
+    match channel_id
+        .send_message(&ctx.http, tag_response)
+        .instrument(info_span!("Sending message"))
+        .await
+    {
+        Ok(sent) => Some(sent),
+        Err(err) => {
+            warn!("Couldn't send the tag response in the tag response channel: {err}");
+
+            // Neither the cache nor Discord know the channel anymore
+            if matches!(channel, CachedChannel::Missing) {
+                delete_tag_channel(tag_channel.server_id, tag_channel.channel_id, pool).await;
+                info!("Forgot the deleted tag response channel");
+            }
+            None
+        }
+    }
+}
+
+/// Responds in the thread the bot started for the tag in the channel,
+/// or starts one on the message that tripped off the tag if there isn't an open one
+async fn send_in_thread(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    msg: &Message,
+    listener: &str,
+    response: &TagResponseMessage,
+    ping: bool,
+) -> Option<Message> {
+    let name = listener
+        .chars()
+        .take(MAX_THREAD_NAME_LENGTH)
+        .collect::<String>();
+
+    let thread_id = match find_tag_thread(ctx, guild_id, channel_id, &name) {
+        Some(thread_id) => thread_id,
+        None => match channel_id
+            .create_thread_from_message(&ctx.http, msg.id, CreateThread::new(name))
+            .instrument(info_span!("Creating thread"))
+            .await
+        {
+            Ok(thread) => thread.id,
+            Err(err) => {
+                warn!("Couldn't start a thread for the tag response: {err}");
+                return None;
+            }
+        },
+    };
+
+    match thread_id
+        .widen()
+        .send_message(&ctx.http, mention_author(msg, response, ping))
+        .instrument(info_span!("Sending message"))
+        .await
+    {
+        Ok(sent) => Some(sent),
+        Err(err) => {
+            warn!("Couldn't send the tag response in the thread: {err}");
+            None
+        }
+    }
+}
+
+/// The open thread of the channel that the bot started for the tag
+fn find_tag_thread(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    name: &str,
+) -> Option<ThreadId> {
+    let bot_id = ctx.cache.current_user().id;
+    let guild = ctx.cache.guild(guild_id)?;
+
+    guild
+        .threads
+        .iter()
+        .find(|t| t.parent_id == channel_id && t.owner_id == bot_id && t.name.as_str() == name)
+        .map(|t| t.id)
+}
+
+/// Replies to the message that tripped off the tag, its author is only pinged if they didn't ask not to be
+fn reply_to(msg: &Message, response: &TagResponseMessage, ping: bool) -> CreateMessage<'static> {
+    response
+        .to_message(None, None)
+        .reference_message(msg)
+        .allowed_mentions(allowed_mentions(ping.then_some(msg.author.id)).replied_user(ping))
+}
+
+/// Mentions the author of the message in a response sent to another channel
+///
+/// The author is only pinged if they didn't ask not to be, and then they also get a button to stop it
+fn mention_author(
+    msg: &Message,
+    response: &TagResponseMessage,
+    ping: bool,
+) -> CreateMessage<'static> {
+    let tag_response = response.to_message(
+        Some(msg.author.mention().to_string()),
+        ping.then_some(msg.author.id),
+    );
+
+    if !ping {
+        return tag_response;
+    }
+
+    stop_pinging_me_button(tag_response)
+}
+
+fn stop_pinging_me_button(tag_response: CreateMessage) -> CreateMessage {
+    tag_response.components(Cow::Owned(vec![CreateComponent::ActionRow(
+        CreateActionRow::Buttons(Cow::Owned(vec![
+            CreateButton::new(Cow::Owned(ComponentIds::BlacklistMeFromTags.to_string()))
+                .label("Stop pinging me")
+                .style(ButtonStyle::Primary),
+        ])),
+    )]))
+}
+
+/// Removes the button to stop pinging after a while and deletes the response when it's time
+fn clean_up_later(ctx: &Context, mut sent: Message, delete_after: Duration) {
+    let has_buttons = !sent.components.is_empty();
+    if !has_buttons && delete_after.is_zero() {
+        return;
+    }
+
+    let http = ctx.http.clone();
+    task::spawn(async move {
+        let mut waited = Duration::ZERO;
+
+        // The button goes away with the response if it's deleted first
+        if has_buttons && (delete_after.is_zero() || delete_after > STOP_PINGING_BUTTON_DURATION) {
+            tokio::time::sleep(STOP_PINGING_BUTTON_DURATION).await;
+            waited = STOP_PINGING_BUTTON_DURATION;
+
+            if let Err(err) = sent
+                .edit(&http, EditMessage::new().components(vec![]))
+                .await
+            {
+                error!("Couldn't remove the buttons of the tag response: {err}");
+            }
+        }
+
+        if delete_after.is_zero() {
+            return;
+        }
+
+        tokio::time::sleep(delete_after - waited).await;
+        if let Err(err) = sent.delete(&http, None).await {
+            error!("Couldn't delete the tag response: {err}");
+        }
+    });
+}
+
+/// Forgets the tag response channel of the server if it's the deleted channel
+pub async fn forget_deleted_tag_channel(guild_id: GuildId, channel_id: ChannelId, pool: &PgPool) {
+    delete_tag_channel(guild_id.get() as i64, channel_id.get() as i64, pool).await;
+}
+
+/// Lets the owner of a tag and moderators choose how the tag posts its responses
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_delivery_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    if !can_manage_tag(command, &tag) {
+        not_tag_owner_response(command, ctx).await;
+        return Ok(());
+    }
+
+    let (delivery_mode, delete_after_seconds) =
+        if command.data.get_optional_bool("use-default") == Some(true) {
+            (None, None)
+        } else {
+            (
+                get_delivery_mode(command)
+                    .map(|m| m.to_string())
+                    .or(tag.delivery_mode),
+                get_seconds(command).or(tag.delete_after_seconds),
+            )
+        };
+
+    update_tag_delivery(tag.id, delivery_mode.clone(), delete_after_seconds, pool).await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+    TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Edit)
+        .tag(&tag.listener)
+        .details("changed how its responses are posted")
+        .save(pool)
+        .await;
+
+    let mode = delivery_mode
+        .as_deref()
+        .and_then(|m| TagDeliveryMode::from_str(m).ok())
+        .map_or("the way the server chooses", describe_mode);
+    let deletion = delete_after_seconds.map_or(
+        "deleted when the server chooses".to_owned(),
+        describe_deletion,
+    );
+    let response = format!(
+        "The responses of the tag \"{}\" are now posted {mode} and {deletion}",
+        tag.listener
+    );
+
+    command_response(ctx, command, &response).await
+}
+
+/// Lets moderators choose how the tags that don't choose themselves post their responses
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_default_delivery_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if !is_moderator(command) {
+        return command_response(
+            ctx,
+            command,
+            "Only moderators can change how tags are posted",
+        )
+        .await;
+    }
+
+    let current =
+        TagDelivery::from_settings(get_tag_settings(guild_id.get() as i64, pool).await.as_ref());
+    let settings = upsert_tag_default_delivery(
+        guild_id.get() as i64,
+        get_delivery_mode(command)
+            .unwrap_or(current.mode)
+            .to_string(),
+        get_seconds(command).unwrap_or_else(|| {
+            current
+                .delete_after
+                .as_secs()
+                .try_into()
+                .unwrap_or_default()
+        }),
+        pool,
+    )
+    .await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+
+    let delivery = TagDelivery::from_settings(Some(&settings));
+    let response = format!(
+        "Tags that don't choose themselves are now posted {} and {}",
+        describe_mode(delivery.mode),
+        describe_deletion(settings.delete_after_seconds)
+    );
+
+    command_response(ctx, command, &response).await
+}
+
+fn describe_mode(mode: TagDeliveryMode) -> &'static str {
+    match mode {
+        TagDeliveryMode::Message => "as a message",
+        TagDeliveryMode::Reply => "as a reply",
+        TagDeliveryMode::Thread => "in a thread",
+    }
+}
+
+fn describe_deletion(delete_after_seconds: i32) -> String {
+    if delete_after_seconds == 0 {
+        "kept".to_owned()
+    } else {
+        format!("deleted after {delete_after_seconds}s")
+    }
+}
+
+fn get_delivery_mode(command: &CommandInteraction) -> Option<TagDeliveryMode> {
+    command
+        .data
+        .get_optional_string("mode")
+        .and_then(|m| TagDeliveryMode::from_str(&m).ok())
+}
+
+fn get_seconds(command: &CommandInteraction) -> Option<i32> {
+    command
+        .data
+        .get_optional_int("delete-after")
+        .and_then(|s| s.try_into().ok())
+}
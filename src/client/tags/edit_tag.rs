@@ -0,0 +1,215 @@
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use serenity::all::{CommandInteraction, Context};
+use sqlx::PgPool;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    audit_log::TagAuditLog,
+    can_manage_tag,
+    channel_scope::ChannelScope,
+    create_tag::is_tag_valid,
+    data_access::{CreateTagError, get_tag_banned_user, update_tag, update_tag_creator},
+    find_tag_by_listener,
+    matching::TagMatchMode,
+    model::{TagAction, TagAuditAction, TagResponseType},
+    not_tag_owner_response, permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+    response_modal::{TagCommandReply, ask_for_response},
+    response_types::is_valid_reaction,
+};
+
+/// Changes the listener, response or match mode of a tag
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn edit_tag_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    if !can_manage_tag(command, &tag) {
+        not_tag_owner_response(command, ctx).await;
+        return Ok(());
+    }
+
+    let match_mode = command
+        .data
+        .get_optional_string("match-mode")
+        .and_then(|m| TagMatchMode::from_str(&m).ok())
+        .unwrap_or_else(|| tag.match_mode());
+    let new_listener = command
+        .data
+        .get_optional_string("new-tag")
+        .unwrap_or_else(|| tag.listener.clone());
+
+    if let Err(e) = match_mode.validate(&new_listener) {
+        return command_response(ctx, command, &e.to_string()).await;
+    }
+
+    let channel_scope = match ChannelScope::from_command(ctx, command, Some(&tag), pool).await {
+        Ok(channel_scope) => channel_scope,
+        Err(e) => return command_response(ctx, command, &e).await,
+    };
+
+    // The multi-line option opens a modal since slash command options can't hold line breaks,
+    // it starts out with the response option if there is one so it isn't lost
+    let typed_response = command.data.get_optional_string("response");
+    let (response, reply) = if command.data.get_optional_bool("multi-line") == Some(true) {
+        let current_response = typed_response.as_deref().unwrap_or(&tag.response);
+        match ask_for_response(ctx, command, "Edit tag", Some(current_response)).await? {
+            Some((response, reply)) => (response, reply),
+            None => return Ok(()),
+        }
+    } else {
+        let response = typed_response.unwrap_or_else(|| tag.response.clone());
+        (response, TagCommandReply::Command(command))
+    };
+
+    if let Err(e) = is_tag_valid(&response, &new_listener, match_mode) {
+        reply.send(ctx, &e.to_string()).await?;
+        return Ok(());
+    }
+
+    if tag.response_type() == TagResponseType::Reaction
+        && !is_valid_reaction(ctx, guild_id, &response)
+    {
+        reply
+            .send(ctx, "The response of a reaction tag has to be an emoji")
+            .await?;
+        return Ok(());
+    }
+
+    let response = match update_tag(
+        tag.id,
+        match_mode.normalize_listener(&new_listener),
+        response.trim().to_owned(),
+        match_mode.to_string(),
+        pool,
+    )
+    .await
+    {
+        Ok(edited_tag) => {
+            channel_scope.save(edited_tag.id, pool).await;
+            refresh_tag_matcher(ctx, guild_id, pool).await;
+
+            let mut audit_log = TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Edit)
+                .tag(&tag.listener);
+            if edited_tag.listener != tag.listener {
+                audit_log = audit_log.details(format!("renamed to \"{}\"", edited_tag.listener));
+            }
+            audit_log.save(pool).await;
+
+            format!("Edited the tag {}", edited_tag.listener)
+        }
+        Err(CreateTagError::TagWithSameListenerExists) => {
+            format!("The tag \"{new_listener}\" already exists")
+        }
+    };
+
+    reply.send(ctx, &response).await?;
+
+    Ok(())
+}
+
+/// Hands a tag over to another member of the server
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn transfer_tag_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    if !can_manage_tag(command, &tag) {
+        not_tag_owner_response(command, ctx).await;
+        return Ok(());
+    }
+
+    let new_owner = command
+        .data
+        .get_optional_user("user")
+        .and_then(|id| command.data.resolved.users.get(&id))
+        .context("User should be resolved")?;
+
+    if new_owner.bot() {
+        return command_response(ctx, command, "Tags can't be given to bots").await;
+    }
+
+    // Discord only resolves the member of users who are in the server
+    if command.data.resolved.members.get(&new_owner.id).is_none() {
+        return command_response(
+            ctx,
+            command,
+            "Tags can only be given to members of this server",
+        )
+        .await;
+    }
+
+    if get_tag_banned_user(new_owner.id.get() as i64, guild_id.get() as i64, pool)
+        .await
+        .is_some()
+    {
+        return command_response(ctx, command, "That user isn't allowed to edit tags").await;
+    }
+
+    update_tag_creator(
+        tag.id,
+        new_owner.name.to_string(),
+        new_owner.id.get() as i64,
+        pool,
+    )
+    .await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+    TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Transfer)
+        .tag(&tag.listener)
+        .target(new_owner.id.get() as i64)
+        .save(pool)
+        .await;
+
+    command_response(
+        ctx,
+        command,
+        &format!("{} now owns the tag {}", new_owner.name, tag.listener),
+    )
+    .await
+}
@@ -0,0 +1,598 @@
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    CommandInteraction, Context, CreateAttachment, CreateInteractionResponse,
+    CreateInteractionResponseMessage, GuildId, User,
+};
+use sqlx::{Connection, PgConnection, PgPool};
+use strum_macros::{Display, EnumIter, EnumString};
+use tracing::{Instrument, info_span};
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand,
+    helper_funcs::{command_response, edit_command_response},
+};
+
+use super::{
+    Tag,
+    audit_log::TagAuditLog,
+    commands::{MAX_COOLDOWN_SECONDS, MAX_DELETE_AFTER_SECONDS},
+    create_tag::is_tag_valid,
+    data_access::{
+        self, create_tag_response, delete_tag_responses_by_tag_id, get_tag_by_listener,
+        get_tag_responses_by_server_id, get_tags_by_server_id, update_tag,
+        update_tag_channel_scope, update_tag_cooldowns, update_tag_delivery,
+        update_tag_response_type,
+    },
+    matching::{TagMatchMode, is_response_valid},
+    model::{TagAction, TagAuditAction, TagChannelScope, TagDeliveryMode, TagResponseType},
+    permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+    response_types::{delete_tag_attachment, is_valid_reaction},
+    responses::MAX_EXTRA_RESPONSES,
+};
+
+/// Bumped when the format changes in a way older versions of the bot can't read
+const EXPORT_VERSION: u32 = 1;
+const MAX_IMPORT_SIZE_MB: u64 = 1;
+const MAX_IMPORTED_TAGS: usize = 500;
+/// How many of the tags that couldn't be imported are listed in the response
+const MAX_SHOWN_PROBLEMS: usize = 10;
+/// How many numbers are tried when renaming a tag before giving up
+const MAX_RENAME_ATTEMPTS: usize = 100;
+
+/// What happens to an imported tag when the server already has a tag with the same listener
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ImportConflictPolicy {
+    /// Keep the tag of the server and don't import the other one
+    #[default]
+    Skip,
+    /// Replace the tag of the server with the imported one, it keeps its owner and usage.
+    /// Needs the permission to edit tags as well
+    Overwrite,
+    /// Import the tag with a number added to its listener
+    Rename,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TagExport {
+    version: u32,
+    tags: Vec<ExportedTag>,
+}
+
+/// A tag the way it's written in an export file.
+///
+/// Everything except the listener and response is optional so the files can be written by hand
+#[derive(Serialize, Deserialize)]
+struct ExportedTag {
+    listener: String,
+    response: String,
+    #[serde(default)]
+    responses: Vec<ExportedTagResponse>,
+    #[serde(default)]
+    match_mode: Option<String>,
+    #[serde(default)]
+    cooldown_seconds: Option<i32>,
+    #[serde(default)]
+    channel_cooldown_seconds: Option<i32>,
+    #[serde(default)]
+    user_cooldown_seconds: Option<i32>,
+    /// Attachment tags are exported without their file and imported as text tags
+    #[serde(default)]
+    response_type: Option<String>,
+    #[serde(default)]
+    embed_title: Option<String>,
+    #[serde(default)]
+    embed_colour: Option<i32>,
+    /// The channel lists of tags aren't exported since they only exist in one server
+    #[serde(default)]
+    disabled_in_nsfw: bool,
+    #[serde(default)]
+    disabled_in_announcements: bool,
+    #[serde(default)]
+    delivery_mode: Option<String>,
+    #[serde(default)]
+    delete_after_seconds: Option<i32>,
+    /// Whoever imports the tag becomes its creator, the original one is only noted in the audit log
+    #[serde(default)]
+    creator_name: Option<String>,
+    #[serde(default)]
+    creator_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedTagResponse {
+    response: String,
+    #[serde(default = "default_weight")]
+    weight: i32,
+}
+
+fn default_weight() -> i32 {
+    1
+}
+
+impl ExportedTag {
+    fn new(tag: Tag, responses: Vec<ExportedTagResponse>) -> Self {
+        Self {
+            listener: tag.listener,
+            response: tag.response,
+            responses,
+            match_mode: Some(tag.match_mode),
+            cooldown_seconds: tag.cooldown_seconds,
+            channel_cooldown_seconds: tag.channel_cooldown_seconds,
+            user_cooldown_seconds: tag.user_cooldown_seconds,
+            response_type: Some(tag.response_type),
+            embed_title: tag.embed_title,
+            embed_colour: tag.embed_colour,
+            disabled_in_nsfw: tag.disabled_in_nsfw,
+            disabled_in_announcements: tag.disabled_in_announcements,
+            delivery_mode: tag.delivery_mode,
+            delete_after_seconds: tag.delete_after_seconds,
+            creator_name: Some(tag.creator_name),
+            creator_id: Some(tag.creator_id),
+        }
+    }
+
+    fn match_mode(&self) -> Result<TagMatchMode, String> {
+        match &self.match_mode {
+            Some(mode) => TagMatchMode::from_str(mode)
+                .map_err(|_| problem(&self.listener, &format!("unknown match mode \"{mode}\""))),
+            None => Ok(TagMatchMode::from_listener(&self.listener)),
+        }
+    }
+
+    fn response_type(&self) -> Result<TagResponseType, String> {
+        match self.response_type.as_deref().map(TagResponseType::from_str) {
+            None | Some(Ok(TagResponseType::Attachment)) => Ok(TagResponseType::Text),
+            Some(Ok(response_type)) => Ok(response_type),
+            Some(Err(_)) => Err(problem(&self.listener, "unknown response type")),
+        }
+    }
+
+    /// Checks the tag the same way the commands that change tags do
+    fn validate(
+        &self,
+        match_mode: TagMatchMode,
+        ctx: &Context,
+        guild_id: GuildId,
+    ) -> Result<(), String> {
+        let error = |message: &str| Err(problem(&self.listener, message));
+
+        if let Err(e) = is_tag_valid(&self.response, &self.listener, match_mode) {
+            return error(&e.to_string());
+        }
+
+        if self.responses.len() > MAX_EXTRA_RESPONSES {
+            return error(&format!(
+                "a tag can't have more than {MAX_EXTRA_RESPONSES} extra responses"
+            ));
+        }
+
+        if self
+            .responses
+            .iter()
+            .any(|r| !is_response_valid(&r.response))
+        {
+            return error("responses can't contain mentions");
+        }
+
+        if self
+            .responses
+            .iter()
+            .any(|r| !(1..=100).contains(&r.weight))
+        {
+            return error("response weights have to be between 1 and 100");
+        }
+
+        if self.response_type()? == TagResponseType::Reaction
+            && (!is_valid_reaction(ctx, guild_id, &self.response)
+                || self
+                    .responses
+                    .iter()
+                    .any(|r| !is_valid_reaction(ctx, guild_id, &r.response)))
+        {
+            return error("every response of a reaction tag has to be a single emoji");
+        }
+
+        if self
+            .embed_colour
+            .is_some_and(|c| !(0..=0x00FF_FFFF).contains(&c))
+        {
+            return error("the embed colour isn't a valid colour");
+        }
+
+        let max_cooldown = i32::try_from(MAX_COOLDOWN_SECONDS).unwrap_or(i32::MAX);
+        if [
+            self.cooldown_seconds,
+            self.channel_cooldown_seconds,
+            self.user_cooldown_seconds,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|c| !(0..=max_cooldown).contains(&c))
+        {
+            return error(&format!(
+                "cooldowns have to be between 0 and {MAX_COOLDOWN_SECONDS} seconds"
+            ));
+        }
+
+        if self
+            .delivery_mode
+            .as_deref()
+            .is_some_and(|m| TagDeliveryMode::from_str(m).is_err())
+        {
+            return error("unknown delivery mode");
+        }
+
+        let max_delete_after = i32::try_from(MAX_DELETE_AFTER_SECONDS).unwrap_or(i32::MAX);
+        if self
+            .delete_after_seconds
+            .is_some_and(|s| !(0..=max_delete_after).contains(&s))
+        {
+            return error(&format!(
+                "responses have to be deleted between 0 and {MAX_DELETE_AFTER_SECONDS} seconds after they're posted"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+enum ImportOutcome {
+    Created,
+    /// Holds the tag the way it was before so its attachment can be deleted once the import is saved
+    Overwritten(Tag),
+    Renamed,
+    Skipped,
+}
+
+/// Sends a JSON file of every tag in the server
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn export_tags_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let mut responses: HashMap<i32, Vec<ExportedTagResponse>> = HashMap::new();
+    for response in get_tag_responses_by_server_id(guild_id.get() as i64, pool).await {
+        responses
+            .entry(response.tag_id)
+            .or_default()
+            .push(ExportedTagResponse {
+                response: response.response,
+                weight: response.weight,
+            });
+    }
+
+    let mut tags = get_tags_by_server_id(guild_id.get() as i64, pool).await;
+    if tags.is_empty() {
+        return command_response(ctx, command, "There are no tags in this server").await;
+    }
+    tags.sort_by(|a, b| a.listener.cmp(&b.listener));
+
+    let tag_count = tags.len();
+    let export = TagExport {
+        version: EXPORT_VERSION,
+        tags: tags
+            .into_iter()
+            .map(|tag| {
+                let tag_responses = responses.remove(&tag.id).unwrap_or_default();
+                ExportedTag::new(tag, tag_responses)
+            })
+            .collect(),
+    };
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!(
+                        "Exported {tag_count} tags, the files of attachment tags aren't included"
+                    ))
+                    .add_file(CreateAttachment::bytes(
+                        serde_json::to_vec_pretty(&export)?,
+                        "tags.json",
+                    )),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+/// Creates the tags of a file made by `/tag export`
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn import_tags_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let policy = command
+        .data
+        .get_optional_string("conflict")
+        .and_then(|p| ImportConflictPolicy::from_str(&p).ok())
+        .unwrap_or_default();
+
+    let mut required_actions = vec![TagAction::Create];
+    if policy == ImportConflictPolicy::Overwrite {
+        required_actions.push(TagAction::Edit);
+    }
+    for action in required_actions {
+        if let Err(reason) = check_tag_permission(command, guild_id, action, pool).await {
+            permission_denied_response(command, ctx, &reason).await;
+            return Ok(());
+        }
+    }
+
+    let file = command
+        .data
+        .get_optional_attachment("file")
+        .and_then(|id| command.data.resolved.attachments.get(&id))
+        .context("Attachment should be resolved")?;
+
+    if u64::from(file.size) > MAX_IMPORT_SIZE_MB * 1024 * 1024 {
+        return command_response(
+            ctx,
+            command,
+            &format!("The file can't be larger than {MAX_IMPORT_SIZE_MB}MB"),
+        )
+        .await;
+    }
+
+    command.defer(&ctx.http).await?;
+
+    let export = match serde_json::from_slice::<TagExport>(&file.download().await?) {
+        Ok(export) if export.version > EXPORT_VERSION => {
+            return edit_command_response(
+                ctx,
+                command,
+                "The file was made by a newer version of the bot",
+            )
+            .await;
+        }
+        Ok(export) if export.tags.len() > MAX_IMPORTED_TAGS => {
+            return edit_command_response(
+                ctx,
+                command,
+                &format!("Only {MAX_IMPORTED_TAGS} tags can be imported at once"),
+            )
+            .await;
+        }
+        Ok(export) => export,
+        Err(e) => {
+            return edit_command_response(ctx, command, &format!("That isn't a tag export: {e}"))
+                .await;
+        }
+    };
+
+    // Everything is saved at once so the tags aren't half imported if the bot stops in between,
+    // every tag gets a savepoint so one that can't be saved doesn't undo the others
+    let mut tx = pool.begin().await?;
+    let (mut created, mut overwritten, mut renamed, mut skipped) = (0, 0, 0, 0);
+    let mut replaced_tags = vec![];
+    let mut problems = vec![];
+    for tag in &export.tags {
+        let mut savepoint = tx.begin().await?;
+        match import_tag(ctx, tag, guild_id, &command.user, policy, &mut savepoint).await {
+            Ok(outcome) => {
+                savepoint.commit().await?;
+                match outcome {
+                    ImportOutcome::Created => created += 1,
+                    ImportOutcome::Overwritten(replaced) => {
+                        overwritten += 1;
+                        replaced_tags.push(replaced);
+                    }
+                    ImportOutcome::Renamed => renamed += 1,
+                    ImportOutcome::Skipped => skipped += 1,
+                }
+            }
+            Err(problem) => {
+                savepoint.rollback().await?;
+                problems.push(problem);
+            }
+        }
+    }
+    tx.commit().await?;
+
+    for tag in replaced_tags {
+        if let Err(e) = delete_tag_attachment(&tag).await {
+            tracing::error!("Couldn't delete the attachment of an overwritten tag: {e}");
+        }
+    }
+
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+
+    let mut message = format!(
+        "Imported {created} new tags, overwrote {overwritten}, renamed {renamed} \
+        and skipped {skipped} that already existed"
+    );
+    if !problems.is_empty() {
+        write!(message, "\n{} tags couldn't be imported:", problems.len())?;
+        for problem in problems.iter().take(MAX_SHOWN_PROBLEMS) {
+            write!(message, "\n{problem}")?;
+        }
+    }
+
+    edit_command_response(ctx, command, &message).await
+}
+
+async fn import_tag(
+    ctx: &Context,
+    tag: &ExportedTag,
+    guild_id: GuildId,
+    importer: &User,
+    policy: ImportConflictPolicy,
+    conn: &mut PgConnection,
+) -> Result<ImportOutcome, String> {
+    let match_mode = tag.match_mode()?;
+    tag.validate(match_mode, ctx, guild_id)?;
+
+    let server_id = guild_id.get() as i64;
+    let listener = match_mode.normalize_listener(&tag.listener);
+
+    let (listener, outcome) = match get_tag_by_listener(&listener, server_id, &mut *conn).await {
+        None => (listener, ImportOutcome::Created),
+        Some(_) if policy == ImportConflictPolicy::Skip => return Ok(ImportOutcome::Skipped),
+        Some(existing) if policy == ImportConflictPolicy::Overwrite => {
+            update_tag(
+                existing.id,
+                listener.clone(),
+                tag.response.trim().to_owned(),
+                match_mode.to_string(),
+                &mut *conn,
+            )
+            .await
+            .map_err(|e| problem(&listener, &e.to_string()))?;
+
+            set_imported_settings(existing.id, tag, existing.channel_scope(), &mut *conn).await?;
+            TagAuditLog::new(guild_id, importer.id, TagAuditAction::Edit)
+                .tag(&listener)
+                .details(import_details("overwritten by an import", tag))
+                .save(&mut *conn)
+                .await;
+            return Ok(ImportOutcome::Overwritten(existing));
+        }
+        Some(_) => (
+            free_listener(&listener, match_mode, server_id, &mut *conn).await?,
+            ImportOutcome::Renamed,
+        ),
+    };
+
+    let created = data_access::create_tag(
+        listener.clone(),
+        tag.response.trim().to_owned(),
+        importer.name.to_string(),
+        importer.id.get() as i64,
+        server_id,
+        match_mode.to_string(),
+        &mut *conn,
+    )
+    .await
+    .map_err(|e| problem(&listener, &e.to_string()))?;
+
+    set_imported_settings(created.id, tag, TagChannelScope::Everywhere, &mut *conn).await?;
+    TagAuditLog::new(guild_id, importer.id, TagAuditAction::Create)
+        .tag(&listener)
+        .details(import_details("imported", tag))
+        .save(&mut *conn)
+        .await;
+
+    Ok(outcome)
+}
+
+/// Notes who made the tag in the server it was exported from
+fn import_details(details: &str, tag: &ExportedTag) -> String {
+    match &tag.creator_name {
+        Some(creator_name) => format!("{details}, originally made by {creator_name}"),
+        None => details.to_owned(),
+    }
+}
+
+/// Finds a listener that isn't taken by adding a number to the end of it
+async fn free_listener(
+    listener: &str,
+    match_mode: TagMatchMode,
+    server_id: i64,
+    conn: &mut PgConnection,
+) -> Result<String, String> {
+    for number in 2..MAX_RENAME_ATTEMPTS + 2 {
+        let renamed = format!("{listener}{number}");
+
+        if match_mode.validate(&renamed).is_ok()
+            && get_tag_by_listener(&renamed, server_id, &mut *conn)
+                .await
+                .is_none()
+        {
+            return Ok(renamed);
+        }
+    }
+
+    Err(problem(listener, "couldn't find a free name for the tag"))
+}
+
+/// Describes why a tag couldn't be imported, with long listeners cut short
+fn problem(listener: &str, message: &str) -> String {
+    const MAX_SHOWN_CHARACTERS: usize = 50;
+
+    let mut shown = listener
+        .chars()
+        .take(MAX_SHOWN_CHARACTERS)
+        .collect::<String>();
+    if listener.chars().count() > MAX_SHOWN_CHARACTERS {
+        shown += "...";
+    }
+
+    format!("{shown}: {message}")
+}
+
+/// Saves everything besides the listener and main response,
+/// overwritten tags keep the channels they're limited to in this server
+async fn set_imported_settings(
+    id: i32,
+    tag: &ExportedTag,
+    channel_scope: TagChannelScope,
+    conn: &mut PgConnection,
+) -> Result<(), String> {
+    update_tag_channel_scope(
+        id,
+        channel_scope.to_string(),
+        tag.disabled_in_nsfw,
+        tag.disabled_in_announcements,
+        &mut *conn,
+    )
+    .await;
+
+    update_tag_cooldowns(
+        id,
+        tag.cooldown_seconds,
+        tag.channel_cooldown_seconds,
+        tag.user_cooldown_seconds,
+        &mut *conn,
+    )
+    .await;
+
+    update_tag_delivery(
+        id,
+        tag.delivery_mode.clone(),
+        tag.delete_after_seconds,
+        &mut *conn,
+    )
+    .await;
+
+    update_tag_response_type(
+        id,
+        tag.response_type()?.to_string(),
+        tag.embed_title.clone(),
+        tag.embed_colour,
+        None,
+        &mut *conn,
+    )
+    .await;
+
+    delete_tag_responses_by_tag_id(id, &mut *conn).await;
+    for response in &tag.responses {
+        create_tag_response(
+            id,
+            response.response.trim().to_owned(),
+            response.weight,
+            &mut *conn,
+        )
+        .await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,650 @@
+use std::{collections::HashMap, iter, sync::LazyLock};
+
+use aho_corasick::AhoCorasick;
+use rand::seq::SliceRandom;
+use regex::{Regex, RegexBuilder};
+use strum_macros::{Display, EnumIter, EnumString};
+use thiserror::Error;
+
+use super::{
+    channel_scope::{MessageChannel, ScopeChannels},
+    cooldowns::Cooldowns,
+    delivery::TagDelivery,
+    model::{Tag, TagResponse},
+};
+
+/// Shortest listener a tag can have if it matches inside of words, shorter ones
+/// would trip off on most messages
+pub const MIN_PARTIAL_LISTENER_LENGTH: usize = 3;
+/// Longest pattern a regex tag can have
+pub const MAX_REGEX_LENGTH: usize = 200;
+/// How big a compiled regex tag can get, this keeps patterns like `(a{100}){100}` out
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+const REGEX_NEST_LIMIT: u32 = 10;
+
+static USER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<@!?(\d+)>").expect("Invalid regular expression"));
+static ROLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<@&(\d+)>").expect("Invalid regular expression"));
+static WORD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[a-z0-9]+").expect("Invalid regular expression"));
+static NON_ALPHANUMERIC_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[^A-Za-z0-9 ]").expect("Invalid regular expression"));
+
+/// How the listener of a tag is compared to messages.
+///
+/// Every mode ignores case. When several tags match a message the mode declared first wins.
+#[derive(
+    Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagMatchMode {
+    /// The words of the listener appear one after another in the message
+    Phrase,
+    /// The listener is one of the words in the message
+    #[default]
+    Word,
+    /// The message starts with the listener
+    Prefix,
+    /// The listener appears anywhere in the message, even inside of words
+    Substring,
+    /// The whole message matches the listener where `*` is any text and `?` is any character
+    Glob,
+    /// The listener is a regular expression found anywhere in the message
+    Regex,
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidTagError {
+    #[error("Tags can't contain mentions")]
+    ContainsMention,
+    #[error("Word and phrase tags can only contain letters, numbers and spaces")]
+    NonAlphanumericListener,
+    #[error("A word tag can only be a single word, use the phrase match mode for multiple words")]
+    MultipleWords,
+    #[error("The tag can't be empty")]
+    EmptyListener,
+    #[error("A glob tag needs at least one character that isn't a wildcard")]
+    OnlyWildcards,
+    #[error(
+        "Prefix, substring, glob and regex tags need at least {MIN_PARTIAL_LISTENER_LENGTH} characters that aren't wildcards"
+    )]
+    ListenerTooShort,
+    #[error("A regex tag can't be longer than {MAX_REGEX_LENGTH} characters")]
+    RegexTooLong,
+    #[error("The regex is invalid or too complex: {0}")]
+    InvalidRegex(String),
+    #[error("The tag would match every message")]
+    MatchesEverything,
+}
+
+/// A message prepared for matching tags against it
+pub struct MessageToMatch {
+    /// The message as it was sent
+    original: String,
+    /// The lowercased message
+    content: String,
+    /// The lowercased words of the message without symbols
+    words: Vec<String>,
+    /// Where each of the words starts in the lowercased message
+    word_starts: Vec<usize>,
+}
+
+impl MessageToMatch {
+    pub fn new(message: &str) -> Self {
+        let original = message.trim().to_owned();
+        let content = original.to_lowercase();
+        let (words, word_starts) = WORD_REGEX
+            .find_iter(&content)
+            .map(|w| (w.as_str().to_owned(), w.start()))
+            .unzip();
+
+        Self {
+            original,
+            content,
+            words,
+            word_starts,
+        }
+    }
+
+    /// The text of the message after the byte offset into the lowercased message
+    fn text_after(&self, offset: usize) -> String {
+        // Lowercasing changes the length of some characters and then
+        // the offset only fits the lowercased message
+        let text = if self.original.len() == self.content.len() {
+            self.original.get(offset..)
+        } else {
+            self.content.get(offset..)
+        };
+
+        text.unwrap_or_default().trim().to_owned()
+    }
+}
+
+/// A tag that was tripped off by a message
+pub struct TagMatch {
+    pub tag: Tag,
+    /// The response picked at random from the responses of the tag
+    pub response: String,
+    /// The text of the message after the listener
+    pub args: String,
+    pub cooldowns: Cooldowns,
+    pub delivery: TagDelivery,
+}
+
+impl TagMatchMode {
+    /// The mode of tags created before tags had match modes
+    pub fn from_listener(listener: &str) -> Self {
+        if listener.split_whitespace().count() > 1 {
+            Self::Phrase
+        } else {
+            Self::Word
+        }
+    }
+
+    /// Prepares the listener for storing. Every mode except regex is stored lowercased
+    /// since lowercasing a regex changes its meaning, e.g. `\S` to `\s`.
+    pub fn normalize_listener(self, listener: &str) -> String {
+        match self {
+            Self::Regex => listener.trim().to_owned(),
+            Self::Word | Self::Phrase => listener
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase(),
+            Self::Prefix | Self::Substring | Self::Glob => listener.trim().to_lowercase(),
+        }
+    }
+
+    /// Checks that a listener can be used with this mode
+    pub fn validate(self, listener: &str) -> Result<(), InvalidTagError> {
+        if listener.trim().is_empty() {
+            return Err(InvalidTagError::EmptyListener);
+        }
+
+        if USER_REGEX.is_match(listener) || ROLE_REGEX.is_match(listener) {
+            return Err(InvalidTagError::ContainsMention);
+        }
+
+        match self {
+            Self::Word | Self::Phrase => {
+                if NON_ALPHANUMERIC_REGEX.is_match(listener) {
+                    return Err(InvalidTagError::NonAlphanumericListener);
+                }
+                if self == Self::Word && listener.split_whitespace().count() > 1 {
+                    return Err(InvalidTagError::MultipleWords);
+                }
+            }
+            Self::Prefix | Self::Substring => {
+                if listener.trim().chars().count() < MIN_PARTIAL_LISTENER_LENGTH {
+                    return Err(InvalidTagError::ListenerTooShort);
+                }
+            }
+            Self::Glob => {
+                let literal_chars = listener.chars().filter(|c| *c != '*' && *c != '?').count();
+                if literal_chars == 0 {
+                    return Err(InvalidTagError::OnlyWildcards);
+                }
+                if literal_chars < MIN_PARTIAL_LISTENER_LENGTH {
+                    return Err(InvalidTagError::ListenerTooShort);
+                }
+            }
+            Self::Regex => {
+                if listener.len() > MAX_REGEX_LENGTH {
+                    return Err(InvalidTagError::RegexTooLong);
+                }
+                if listener.trim().chars().count() < MIN_PARTIAL_LISTENER_LENGTH {
+                    return Err(InvalidTagError::ListenerTooShort);
+                }
+
+                let regex = compile_regex(listener)
+                    .map_err(|e| InvalidTagError::InvalidRegex(e.to_string()))?;
+
+                if regex.is_match("") {
+                    return Err(InvalidTagError::MatchesEverything);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Every tag of a guild compiled so a message is checked for all of them in a single pass
+pub struct TagMatcher {
+    tags: Vec<(TagMatchMode, Tag)>,
+    /// The responses of each tag besides its main response
+    extra_responses: HashMap<i32, Vec<TagResponse>>,
+    /// The cooldowns of the server for tags that don't set their own
+    default_cooldowns: Cooldowns,
+    /// How the server posts the responses of tags that don't choose themselves
+    default_delivery: TagDelivery,
+    /// Word and phrase listeners surrounded by spaces, searched in the words of the message
+    words: AhoCorasick,
+    word_tags: Vec<usize>,
+    /// Prefix and substring listeners, searched in the whole message
+    content: AhoCorasick,
+    content_tags: Vec<usize>,
+    /// Glob and regex listeners
+    regexes: Vec<(Regex, usize)>,
+    /// The channels tags are limited to or kept out of
+    scope_channels: ScopeChannels,
+}
+
+impl TagMatcher {
+    pub fn new(
+        tags: Vec<Tag>,
+        responses: Vec<TagResponse>,
+        scope_channels: ScopeChannels,
+        default_cooldowns: Cooldowns,
+        default_delivery: TagDelivery,
+    ) -> Self {
+        let tags = tags
+            .into_iter()
+            .map(|t| (t.match_mode(), t))
+            .collect::<Vec<_>>();
+
+        let mut extra_responses: HashMap<i32, Vec<TagResponse>> = HashMap::new();
+        for response in responses {
+            extra_responses
+                .entry(response.tag_id)
+                .or_default()
+                .push(response);
+        }
+
+        let mut word_patterns = vec![];
+        let mut word_tags = vec![];
+        let mut content_patterns = vec![];
+        let mut content_tags = vec![];
+        let mut regexes = vec![];
+
+        for (index, (mode, tag)) in tags.iter().enumerate() {
+            match mode {
+                TagMatchMode::Word | TagMatchMode::Phrase => {
+                    word_patterns.push(format!(" {} ", tag.listener));
+                    word_tags.push(index);
+                }
+                TagMatchMode::Prefix | TagMatchMode::Substring => {
+                    content_patterns.push(tag.listener.clone());
+                    content_tags.push(index);
+                }
+                TagMatchMode::Glob => {
+                    if let Ok(regex) = glob_to_regex(&tag.listener) {
+                        regexes.push((regex, index));
+                    }
+                }
+                TagMatchMode::Regex => {
+                    if let Ok(regex) = compile_regex(&tag.listener) {
+                        regexes.push((regex, index));
+                    }
+                }
+            }
+        }
+
+        Self {
+            tags,
+            extra_responses,
+            default_cooldowns,
+            default_delivery,
+            words: AhoCorasick::new(word_patterns).expect("Couldn't build the tag automaton"),
+            word_tags,
+            content: AhoCorasick::new(content_patterns).expect("Couldn't build the tag automaton"),
+            content_tags,
+            regexes,
+            scope_channels,
+        }
+    }
+
+    /// The listeners of every tag in the server
+    pub fn listeners(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(|(_, tag)| tag.listener.as_str())
+    }
+
+    /// Finds the tag the message trips off out of the tags that can respond in the channel.
+    ///
+    /// When several tags match, the one with the match mode declared first wins
+    /// and after that the tag that was created first.
+    pub fn find(&self, message: &MessageToMatch, channel: &MessageChannel) -> Option<TagMatch> {
+        let words = format!(" {} ", message.words.join(" "));
+
+        // Each match is the index of the tag and where the listener ends in the lowercased message
+        let word_matches = self.words.find_overlapping_iter(&words).map(|m| {
+            // Every word before the end of the match is followed by a space
+            let matched_words = words[..m.end()].matches(' ').count() - 1;
+            let end = message
+                .word_starts
+                .get(matched_words)
+                .copied()
+                .unwrap_or(message.content.len());
+
+            (self.word_tags[m.pattern().as_usize()], end)
+        });
+
+        let content_matches = self
+            .content
+            .find_overlapping_iter(&message.content)
+            .filter(|m| {
+                let index = self.content_tags[m.pattern().as_usize()];
+                self.tags[index].0 == TagMatchMode::Substring || m.start() == 0
+            })
+            .map(|m| (self.content_tags[m.pattern().as_usize()], m.end()));
+
+        let regex_matches = self
+            .regexes
+            .iter()
+            .filter_map(|(regex, index)| regex.find(&message.content).map(|m| (*index, m.end())));
+
+        word_matches
+            .chain(content_matches)
+            .chain(regex_matches)
+            .filter(|(index, _)| self.scope_channels.allows(&self.tags[*index].1, channel))
+            .min_by_key(|(index, _)| (self.tags[*index].0, *index))
+            .map(|(index, end)| {
+                let tag = &self.tags[index].1;
+
+                TagMatch {
+                    tag: tag.clone(),
+                    response: self.pick_response(tag),
+                    args: message.text_after(end),
+                    cooldowns: self.default_cooldowns.for_tag(tag),
+                    delivery: self.default_delivery.for_tag(tag),
+                }
+            })
+    }
+
+    /// Picks the main response of the tag or one of its extra responses by their weights
+    fn pick_response(&self, tag: &Tag) -> String {
+        let Some(extra_responses) = self.extra_responses.get(&tag.id) else {
+            return tag.response.clone();
+        };
+
+        let responses = iter::once((tag.response.as_str(), 1))
+            .chain(
+                extra_responses
+                    .iter()
+                    .map(|r| (r.response.as_str(), r.weight.max(0))),
+            )
+            .collect::<Vec<_>>();
+
+        responses
+            .choose_weighted(&mut rand::thread_rng(), |(_, weight)| *weight)
+            .map_or_else(
+                |_| tag.response.clone(),
+                |(response, _)| (*response).to_owned(),
+            )
+    }
+}
+
+/// Compiles a regex tag with limits on how big it can get
+pub fn compile_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .nest_limit(REGEX_NEST_LIMIT)
+        .build()
+}
+
+/// Turns a glob into a regex that matches the whole message
+pub fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .dot_matches_new_line(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// Checks that the response as it's written won't ping anyone it shouldn't.
+///
+/// Only the template is checked, what its placeholders render to is escaped by
+/// [`render_response`][super::template::render_response].
+pub fn is_response_valid(response: &str) -> bool {
+    !(USER_REGEX.is_match(response)
+        || ROLE_REGEX.is_match(response)
+        || response.contains("@everyone")
+        || response.contains("@here"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(tags: Vec<Tag>) -> TagMatcher {
+        TagMatcher::new(
+            tags,
+            vec![],
+            ScopeChannels::new(vec![]),
+            Cooldowns::default(),
+            TagDelivery::default(),
+        )
+    }
+
+    /// The ID and args of the tag the message trips off
+    fn find(matcher: &TagMatcher, message: &str) -> Option<(i32, String)> {
+        matcher
+            .find(
+                &MessageToMatch::new(message),
+                &MessageChannel::for_tests(vec![1], false, false),
+            )
+            .map(|m| (m.tag.id, m.args))
+    }
+
+    #[test]
+    fn word_tags_match_whole_words() {
+        let matcher = matcher(vec![Tag::for_tests(1, "Hi", TagMatchMode::Word)]);
+
+        assert_eq!(find(&matcher, "oh HI there"), Some((1, "there".to_owned())));
+        assert_eq!(find(&matcher, "hi!"), Some((1, String::new())));
+        assert_eq!(find(&matcher, "this is it"), None);
+    }
+
+    #[test]
+    fn phrase_tags_match_consecutive_words() {
+        let matcher = matcher(vec![Tag::for_tests(
+            1,
+            "good  morning",
+            TagMatchMode::Phrase,
+        )]);
+
+        assert_eq!(
+            find(&matcher, "Good morning, Everyone!"),
+            Some((1, "Everyone!".to_owned()))
+        );
+        assert_eq!(find(&matcher, "good sunny morning"), None);
+    }
+
+    #[test]
+    fn prefix_tags_match_the_start_of_the_message() {
+        let matcher = matcher(vec![Tag::for_tests(1, "hey", TagMatchMode::Prefix)]);
+
+        assert_eq!(find(&matcher, "heya you"), Some((1, "a you".to_owned())));
+        assert_eq!(find(&matcher, "oh hey"), None);
+    }
+
+    #[test]
+    fn substring_tags_match_inside_words() {
+        let matcher = matcher(vec![Tag::for_tests(1, "cat", TagMatchMode::Substring)]);
+
+        assert_eq!(find(&matcher, "concatenate"), Some((1, "enate".to_owned())));
+        assert_eq!(find(&matcher, "dog"), None);
+    }
+
+    #[test]
+    fn glob_tags_match_the_whole_message() {
+        let matcher = matcher(vec![Tag::for_tests(1, "I am *", TagMatchMode::Glob)]);
+
+        assert!(find(&matcher, "I am tired").is_some());
+        assert_eq!(find(&matcher, "so I am tired"), None);
+    }
+
+    #[test]
+    fn regex_tags_match_anywhere() {
+        let matcher = matcher(vec![Tag::for_tests(1, r"\bcolou?r\b", TagMatchMode::Regex)]);
+
+        assert_eq!(
+            find(&matcher, "What COLOUR is it"),
+            Some((1, "is it".to_owned()))
+        );
+        assert_eq!(find(&matcher, "colours"), None);
+    }
+
+    #[test]
+    fn earlier_match_modes_and_then_older_tags_win() {
+        let matcher = matcher(vec![
+            Tag::for_tests(1, "hello", TagMatchMode::Substring),
+            Tag::for_tests(2, "hello", TagMatchMode::Word),
+            Tag::for_tests(3, "hello there", TagMatchMode::Phrase),
+            Tag::for_tests(4, "hello", TagMatchMode::Word),
+        ]);
+
+        assert_eq!(find(&matcher, "hello").map(|(id, _)| id), Some(2));
+        assert_eq!(find(&matcher, "hello there").map(|(id, _)| id), Some(3));
+        assert_eq!(find(&matcher, "othello").map(|(id, _)| id), Some(1));
+    }
+
+    #[test]
+    fn tags_scoped_out_of_the_channel_are_skipped() {
+        let matcher = TagMatcher::new(
+            vec![
+                Tag {
+                    channel_scope: "allow-list".to_owned(),
+                    ..Tag::for_tests(1, "hello", TagMatchMode::Word)
+                },
+                Tag::for_tests(2, "hello", TagMatchMode::Substring),
+            ],
+            vec![],
+            ScopeChannels::new(vec![]),
+            Cooldowns::default(),
+            TagDelivery::default(),
+        );
+
+        assert_eq!(find(&matcher, "hello").map(|(id, _)| id), Some(2));
+    }
+
+    #[test]
+    fn validates_word_and_phrase_listeners() {
+        assert!(TagMatchMode::Word.validate("hello").is_ok());
+        assert!(TagMatchMode::Phrase.validate("hello there").is_ok());
+        assert!(matches!(
+            TagMatchMode::Word.validate("hello there"),
+            Err(InvalidTagError::MultipleWords)
+        ));
+        assert!(matches!(
+            TagMatchMode::Phrase.validate("hello!"),
+            Err(InvalidTagError::NonAlphanumericListener)
+        ));
+        assert!(matches!(
+            TagMatchMode::Word.validate("  "),
+            Err(InvalidTagError::EmptyListener)
+        ));
+        assert!(matches!(
+            TagMatchMode::Substring.validate("hi <@123>"),
+            Err(InvalidTagError::ContainsMention)
+        ));
+        assert!(matches!(
+            TagMatchMode::Substring.validate("hi <@&123>"),
+            Err(InvalidTagError::ContainsMention)
+        ));
+    }
+
+    #[test]
+    fn partial_listeners_need_the_minimum_length() {
+        for mode in [TagMatchMode::Prefix, TagMatchMode::Substring] {
+            assert!(matches!(
+                mode.validate(" ab "),
+                Err(InvalidTagError::ListenerTooShort)
+            ));
+            assert!(mode.validate("abc").is_ok());
+        }
+
+        // Single letter words are fine since they can't match inside of other words
+        assert!(TagMatchMode::Word.validate("a").is_ok());
+    }
+
+    #[test]
+    fn glob_listeners_need_literal_characters() {
+        assert!(matches!(
+            TagMatchMode::Glob.validate("*?*"),
+            Err(InvalidTagError::OnlyWildcards)
+        ));
+        assert!(matches!(
+            TagMatchMode::Glob.validate("a*b?"),
+            Err(InvalidTagError::ListenerTooShort)
+        ));
+        assert!(TagMatchMode::Glob.validate("abc*").is_ok());
+    }
+
+    #[test]
+    fn validates_regex_listeners() {
+        assert!(TagMatchMode::Regex.validate(r"\bhi\b").is_ok());
+        assert!(matches!(
+            TagMatchMode::Regex.validate(".*"),
+            Err(InvalidTagError::ListenerTooShort)
+        ));
+        assert!(matches!(
+            TagMatchMode::Regex.validate("a*b*"),
+            Err(InvalidTagError::MatchesEverything)
+        ));
+        assert!(matches!(
+            TagMatchMode::Regex.validate("(abc"),
+            Err(InvalidTagError::InvalidRegex(_))
+        ));
+        assert!(matches!(
+            TagMatchMode::Regex.validate("(a{100}){100}"),
+            Err(InvalidTagError::InvalidRegex(_))
+        ));
+        assert!(matches!(
+            TagMatchMode::Regex.validate(&"a".repeat(MAX_REGEX_LENGTH + 1)),
+            Err(InvalidTagError::RegexTooLong)
+        ));
+    }
+
+    #[test]
+    fn normalizes_listeners_for_their_mode() {
+        assert_eq!(
+            TagMatchMode::Phrase.normalize_listener(" Good   Morning "),
+            "good morning"
+        );
+        assert_eq!(TagMatchMode::Glob.normalize_listener(" I Am * "), "i am *");
+        assert_eq!(TagMatchMode::Regex.normalize_listener(r" \S+ "), r"\S+");
+    }
+
+    #[test]
+    fn glob_wildcards_match_any_text_and_single_characters() {
+        let regex = glob_to_regex("a?c*").unwrap();
+
+        assert!(regex.is_match("abc"));
+        assert!(regex.is_match("ABCDEF"));
+        assert!(regex.is_match("abc\ndef"));
+        assert!(!regex.is_match("ac"));
+        assert!(!regex.is_match("xabc"));
+    }
+
+    #[test]
+    fn glob_escapes_regex_syntax() {
+        let regex = glob_to_regex("*.txt").unwrap();
+
+        assert!(regex.is_match("notes.txt"));
+        assert!(!regex.is_match("notesatxt"));
+        assert!(glob_to_regex("(a+)[b]").unwrap().is_match("(a+)[b]"));
+    }
+
+    #[test]
+    fn responses_with_mentions_are_invalid() {
+        assert!(is_response_valid("hello {user}"));
+        assert!(!is_response_valid("hello <@123>"));
+        assert!(!is_response_valid("hello <@&123>"));
+        assert!(!is_response_valid("hello @everyone"));
+        assert!(!is_response_valid("hello @here"));
+    }
+}
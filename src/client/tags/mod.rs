@@ -1,175 +1,147 @@
+mod audit_log;
+mod autocomplete;
+mod channel_scope;
 pub mod commands;
+mod cooldowns;
 mod create_tag;
 mod data_access;
+mod delivery;
+mod edit_tag;
+mod import_export;
+mod matching;
 mod model;
+mod permissions;
+mod preferences;
 mod remove_tag;
+mod response_modal;
+mod response_types;
+mod responses;
+mod stats;
 mod tag_ban;
-
+mod tag_list;
+mod template;
+
+pub use audit_log::tag_audit_log_command;
+pub use autocomplete::tag_listener_choices;
+use channel_scope::{MessageChannel, ScopeChannels};
+use cooldowns::Cooldowns;
+pub use cooldowns::{TagCooldowns, tag_cooldown_command, tag_default_cooldown_command};
 pub use create_tag::create_tag;
-pub use tag_ban::ban_user_from_editing_tags;
-use model::TagChannel;
-use regex::Regex;
+use delivery::{TagDelivery, deliver_tag_response, react_to_tag};
+pub use delivery::{
+    forget_deleted_tag_channel, tag_default_delivery_command, tag_delivery_command,
+};
+pub use edit_tag::{edit_tag_command, transfer_tag_command};
+pub use import_export::{export_tags_command, import_tags_command};
+use matching::MessageToMatch;
+pub use matching::{TagMatch, TagMatcher};
+pub use model::{TAG_ATTACHMENTS_FOLDER, Tag, TagPreference};
+pub use permissions::tag_permissions_command;
+use preferences::get_tag_preference;
+pub use preferences::{TagUserPreferences, tag_preference_command, toggle_tag_preference};
 pub use remove_tag::remove_tag;
-use tokio::task;
+use response_types::TagResponseMessage;
+pub use response_types::tag_response_type_command;
+pub use responses::{
+    add_tag_response_command, list_tag_responses_command, remove_tag_response_command,
+};
+pub use stats::{archive_tag_uses_loop, tag_info_command, top_tags_command};
+pub use tag_ban::{
+    ban_user_from_editing_tags, lift_expired_tag_bans_loop, list_tag_bans_command,
+    unban_user_from_editing_tags,
+};
+pub use tag_list::{change_tag_list_page, list_tags};
+use template::render_response;
 use tracing::{Instrument, info_span};
 
 use self::data_access::{
-    create_tag_blacklisted_user, create_tag_channel, delete_tag_blacklisted_user,
-    get_tag_blacklisted_user, get_tag_channel, update_tag_channel,
+    create_tag_channel, get_tag_channel, get_tag_settings, update_tag_channel,
 };
-use super::ComponentIds;
-pub use model::Tag;
+use super::global_data::GetBotState;
 use serenity::{
-    all::{
-        ButtonStyle, CommandInteraction, Context, CreateComponent,
-        CreateInteractionResponseMessage, GuildChannel, GuildId, User,
-    },
-    builder::{
-        CreateActionRow, CreateAllowedMentions, CreateButton, CreateInteractionResponse,
-        CreateMessage, EditMessage,
-    },
-    model::{
-        channel::Message,
-        id::{ChannelId, UserId},
-    },
-    prelude::Mentionable,
+    all::{CommandInteraction, Context, CreateInteractionResponseMessage, GuildId},
+    builder::{CreateAllowedMentions, CreateInteractionResponse},
+    model::channel::Message,
 };
-use sqlx::{PgPool, Pool, Postgres};
-use std::{borrow::Cow, fmt::Write, time::Duration};
-
-#[tracing::instrument(skip(ctx))]
-pub async fn list_tags(ctx: &Context, command: &CommandInteraction, pool: &Pool<Postgres>) {
-    let tags =
-        data_access::get_tags_by_server_id(command.guild_id.unwrap().get() as i64, pool).await;
-
-    let mut message = String::new();
-
-    for tag in tags {
-        write!(&mut message, "{}, ", tag.listener).unwrap();
-    }
-    message.pop();
-    message.pop();
-
-    if message.is_empty() {
-        message = "There are no tags in this server".to_string();
-    }
-
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new().content(message),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
-        .await
-        .expect("Error creating interaction response");
-}
-
-#[tracing::instrument(skip(ctx))]
-pub async fn blacklist_user_from_tags_command(
-    ctx: &Context,
-    user: &User,
-    command: &CommandInteraction,
-    pool: &PgPool,
-) {
-    let response = blacklist_user(user, pool).await;
-
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new().content(response),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
-        .await
-        .expect("Error creating interaction response");
-}
-
-#[tracing::instrument(skip(pool))]
-pub async fn blacklist_user(user: &User, pool: &PgPool) -> String {
-    let is_user_blacklisted = get_tag_blacklisted_user(user.id.get() as i64, pool)
-        .await
-        .is_some();
-
-    if is_user_blacklisted {
-        delete_tag_blacklisted_user(user.id.get() as i64, pool).await;
-
-        "I will now ping you when you trip off a tag".to_string()
-    } else {
-        create_tag_blacklisted_user(user.id.get() as i64, pool).await;
-
-        "I won't ping you anymore when you trip off a tag".to_string()
-    }
-}
+use sqlx::PgPool;
+use std::sync::Arc;
 
 /// Checks for all the tag [`Listeners`][L] in the message
 ///
-/// If a [`Listener`][L] is found it returns the response for that [`Listener`][L]
+/// If a [`Listener`][L] is found and none of the tag's cooldowns are running it returns the tag
+/// for that [`Listener`][L]. The cooldowns are started by [`respond_to_tag`] once it responded.
+/// The tags of the guild are compiled into a [`TagMatcher`] the first time they're needed
+/// so after that no database queries are made.
 ///
 /// [L]: self::global_data::Listener
 pub async fn check_for_tag_listeners(
-    words_in_message: &[String],
-    user_id: UserId,
-    server_id: u64,
-    pool: &Pool<Postgres>,
-) -> Option<String> {
-    let alphanumeric_regex = Regex::new(r"[^A-Za-z0-9 ]").expect("Invalid regular expression");
-    let words_in_message: Vec<String> = words_in_message
-        .iter()
-        .map(|w| alphanumeric_regex.replace_all(w, " ").to_string())
-        .collect();
-
-    let tags = data_access::get_tags_by_server_id(server_id as i64, pool).await;
-    let is_user_blacklisted = get_tag_blacklisted_user(user_id.get() as i64, pool)
-        .await
-        .is_some();
+    ctx: &Context,
+    msg: &Message,
+    pool: &PgPool,
+) -> Option<TagMatch> {
+    let guild_id = msg.guild_id?;
 
-    if is_user_blacklisted {
+    if get_tag_preference(ctx, msg.author.id, guild_id, pool).await == Some(TagPreference::Ignore) {
         return None;
     }
 
-    for tag in &tags {
-        let listener = &tag.listener;
-        let response = &tag.response;
+    let matcher = get_tag_matcher(ctx, guild_id, pool).await;
+    let tag_match = matcher.find(
+        &MessageToMatch::new(&msg.content),
+        &MessageChannel::new(ctx, msg),
+    )?;
 
-        let listener_words = listener
-            .split(' ')
-            .map(ToString::to_string)
-            .collect::<Vec<String>>();
+    let is_running = ctx.bot_state().read().await.tag_cooldowns.is_running(
+        tag_match.tag.id,
+        msg.channel_id,
+        msg.author.id,
+    );
 
-        let mut listener_iterator = listener_words.iter();
-
-        if listener_words.len() > 1 {
-            let mut count = 0;
-            for message_word in &words_in_message {
-                if message_word == listener_iterator.next()? {
-                    count += 1;
-                } else {
-                    count = 0;
-                    listener_iterator = listener_words.iter();
-                }
+    (!is_running).then_some(tag_match)
+}
 
-                if count == listener_words.len() {
-                    return Some(response.clone());
-                }
-            }
-        }
+async fn get_tag_matcher(ctx: &Context, guild_id: GuildId, pool: &PgPool) -> Arc<TagMatcher> {
+    if let Some(matcher) = ctx.bot_state().read().await.tag_matchers.get(&guild_id) {
+        return matcher.clone();
     }
 
-    for tag in tags {
-        let listener = &tag.listener;
-        let response = &tag.response;
+    let matcher = Arc::new(compile_tag_matcher(guild_id, pool).await);
 
-        let listener_words = listener.split(' ').map(ToString::to_string);
+    // A matcher inserted by a refresh in the meantime is newer than the tags read here
+    ctx.bot_state()
+        .write()
+        .await
+        .tag_matchers
+        .entry(guild_id)
+        .or_insert(matcher)
+        .clone()
+}
 
-        if words_in_message.contains(listener) && listener_words.count() < 2 {
-            return Some(response.clone());
-        }
-    }
+async fn compile_tag_matcher(guild_id: GuildId, pool: &PgPool) -> TagMatcher {
+    let tags = data_access::get_tags_by_server_id(guild_id.get() as i64, pool).await;
+    let responses = data_access::get_tag_responses_by_server_id(guild_id.get() as i64, pool).await;
+    let scope_channels =
+        data_access::get_tag_scope_channels_by_server_id(guild_id.get() as i64, pool).await;
+    let settings = get_tag_settings(guild_id.get() as i64, pool).await;
+
+    TagMatcher::new(
+        tags,
+        responses,
+        ScopeChannels::new(scope_channels),
+        Cooldowns::from_settings(settings.as_ref()),
+        TagDelivery::from_settings(settings.as_ref()),
+    )
+}
+
+/// Recompiles the tags of the guild, has to be called whenever its tags or tag settings change
+pub async fn refresh_tag_matcher(ctx: &Context, guild_id: GuildId, pool: &PgPool) {
+    let matcher = Arc::new(compile_tag_matcher(guild_id, pool).await);
 
-    None
+    ctx.bot_state()
+        .write()
+        .await
+        .tag_matchers
+        .insert(guild_id, matcher);
 }
 
 #[tracing::instrument(skip(ctx))]
@@ -213,147 +185,116 @@ pub async fn set_tag_response_channel(ctx: &Context, command: &CommandInteractio
         .expect("Error creating interaction response");
 }
 
-/// It first checks if a tag response channel exists for the guild the message is in.
+/// The response of the tag is rendered with [`render_response`] first.
 ///
-/// If there is it sends the response there.
-///
-/// If there is no tag response channel set then it first tries to send a message in the same channel.
-/// If that fails then it sends the message to the tag response channel if one is set
-/// If that fails then it iterates through every channel in the guild until it finds one it can send a message in
-#[tracing::instrument(skip(ctx, pool))]
-pub async fn respond_to_tag(ctx: &Context, msg: &Message, message: &str, pool: &PgPool) {
-    let tag_channel = get_tag_channel(msg.guild_id.unwrap().get() as i64, pool).await;
+/// Reaction tags react to the message, every other tag is sent with [`deliver_tag_response`]
+/// which picks where the response can be sent.
+/// Users who asked not to be pinged are still mentioned when the response is sent elsewhere, just without a ping.
+#[tracing::instrument(skip(ctx, tag_match, pool))]
+pub async fn respond_to_tag(ctx: &Context, msg: &Message, tag_match: &TagMatch, pool: &PgPool) {
+    let message = render_response(ctx, msg, &tag_match.response, &tag_match.args).await;
+    let response = match TagResponseMessage::new(&tag_match.tag, message).await {
+        Ok(response) => response,
+        Err(err) => {
+            tracing::error!("Couldn't build the response of the tag: {err:?}");
+            return;
+        }
+    };
 
-    //If the guild has a tag response channel send the response there
-    if let Some(tag_channel) = tag_channel {
-        send_response_in_tag_channel(ctx, tag_channel, msg, message).await;
-        return;
-    }
+    // Reaction tags react to the message instead of answering it
+    let responded = if let Some(reaction) = response.reaction() {
+        react_to_tag(ctx, msg, reaction).await
+    } else {
+        let Some(guild_id) = msg.guild_id else {
+            return;
+        };
+        let ping = get_tag_preference(ctx, msg.author.id, guild_id, pool).await
+            != Some(TagPreference::NoPing);
 
-    //Try sending a message to the channel the tag listener was tripped off
-    if msg
-        .channel_id
-        .say(&ctx.http, message)
-        .instrument(info_span!("Sending message"))
-        .await
-        .is_err()
-    {
-        //If sending a message fails iterate through the guild channels until it manages to send a message
-        let channels = &msg
-            .guild(&ctx.cache)
-            .expect("Couldn't retrieve guild from cache")
-            .channels
-            .clone();
+        deliver_tag_response(ctx, msg, tag_match, &response, ping, pool).await
+    };
+
+    // A tag that couldn't respond can try again on the next message and doesn't count as used
+    if responded {
+        data_access::create_tag_use(
+            tag_match.tag.id,
+            tag_match.tag.server_id,
+            msg.channel_id.get() as i64,
+            msg.author.id.get() as i64,
+            pool,
+        )
+        .await;
+
+        ctx.bot_state().write().await.tag_cooldowns.start(
+            tag_match.tag.id,
+            tag_match.cooldowns,
+            msg.channel_id,
+            msg.author.id,
+        );
+    }
+}
 
-        for channel in channels {
-            if let Ok(mut msg) = tag_response(channel, ctx, msg, message).await {
-                let http = ctx.http.clone();
-                task::spawn(async move {
-                    tokio::time::sleep(Duration::from_secs(10)).await;
-                    msg.edit(&http, EditMessage::new().components(vec![]))
-                        .await
-                        .unwrap();
-                });
+/// Finds a tag by its listener the way it was typed or the way it's stored
+async fn find_tag_by_listener(listener: &str, guild_id: GuildId, pool: &PgPool) -> Option<Tag> {
+    let listener = listener.trim();
 
-                break;
-            }
+    match data_access::get_tag_by_listener(listener, guild_id.get() as i64, pool).await {
+        Some(tag) => Some(tag),
+        None => {
+            data_access::get_tag_by_listener(&listener.to_lowercase(), guild_id.get() as i64, pool)
+                .await
         }
     }
 }
 
-async fn tag_response(
-    channel: &GuildChannel,
-    ctx: &Context,
-    msg: &Message,
-    message: &str,
-) -> Result<Message, serenity::Error> {
-    channel
-        .id
-        .widen()
-        .send_message(
-            &ctx.http,
-            CreateMessage::new()
-                .components(Cow::Owned(vec![CreateComponent::ActionRow(
-                    CreateActionRow::Buttons(Cow::Owned(vec![
-                        CreateButton::new(ComponentIds::BlacklistMeFromTags.to_string())
-                            .label("Stop pinging me")
-                            .style(ButtonStyle::Primary),
-                    ])),
-                )]))
-                .allowed_mentions(CreateAllowedMentions::new().all_users(true))
-                .content(msg.author.mention().to_string() + " " + message),
-        )
-        .instrument(info_span!("Sending message"))
-        .await
+fn is_moderator(command: &CommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.moderate_members())
 }
 
-async fn send_response_in_tag_channel(
-    ctx: &Context,
-    tag_channel: TagChannel,
-    msg: &Message,
-    message: &str,
-) {
-    let tag_response_channel = ctx
-        .cache
-        .guild(GuildId::new(tag_channel.server_id as u64))
-        .unwrap()
-        .channels
-        .get(&ChannelId::new(tag_channel.channel_id as u64))
-        .unwrap()
-        .clone();
-
-    let mut tag_response = CreateMessage::new();
-
-    let response_content = if msg.channel_id == tag_response_channel.id.widen() {
-        message.to_owned()
-    } else {
-        // Create this button only if the user is pinged
-        tag_response = stop_pinging_me_button(tag_response);
-
-        msg.author.mention().to_string() + " " + message
-    };
+/// Admins can change the tag policy and aren't limited by it
+fn is_admin(command: &CommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_guild())
+}
 
-    tag_response = tag_response
-        .allowed_mentions(CreateAllowedMentions::new().all_users(true))
-        .content(response_content);
+/// The creator of a tag and moderators can change it
+fn can_manage_tag(command: &CommandInteraction, tag: &Tag) -> bool {
+    command.user.id.get() as i64 == tag.creator_id || is_moderator(command)
+}
 
-    match tag_response_channel
-        .send_message(&ctx.http, tag_response)
+async fn not_tag_owner_response(command: &CommandInteraction, ctx: &Context) {
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Only the owner of the tag and moderators can change it"),
+            ),
+        )
         .instrument(info_span!("Sending message"))
         .await
-    {
-        Ok(mut msg) => {
-            let http = ctx.http.clone();
-            task::spawn(async move {
-                tokio::time::sleep(Duration::from_secs(20)).await;
-                msg.edit(&http, EditMessage::new().components(vec![]))
-                    .await
-                    .unwrap();
-            });
-        }
-        Err(err) => eprintln!("{err}"),
-    }
-}
-
-fn stop_pinging_me_button(tag_response: CreateMessage) -> CreateMessage {
-    tag_response.components(Cow::Owned(vec![CreateComponent::ActionRow(
-        CreateActionRow::Buttons(Cow::Owned(vec![
-            CreateButton::new(Cow::Owned(ComponentIds::BlacklistMeFromTags.to_string()))
-                .label("Stop pinging me")
-                .style(ButtonStyle::Primary),
-        ])),
-    )]))
+        .expect("Error creating interaction response");
 }
 
-pub async fn user_banned_response(command: &CommandInteraction, ctx: &Context) {
+/// Tells the user why [`permissions::check_tag_permission`] didn't let them through
+async fn permission_denied_response(command: &CommandInteraction, ctx: &Context, reason: &str) {
     command
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new().content("You're not allowed to edit tags"),
+                CreateInteractionResponseMessage::new()
+                    .content(reason)
+                    .allowed_mentions(CreateAllowedMentions::new()),
             ),
         )
         .instrument(info_span!("Sending message"))
         .await
         .expect("Error creating interaction response");
-}
\ No newline at end of file
+}
@@ -1,4 +1,10 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+use super::matching::TagMatchMode;
 
 #[derive(Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Tag {
@@ -8,19 +14,193 @@ pub struct Tag {
     pub creator_name: String,
     pub creator_id: i64,
     pub server_id: i64,
+    pub match_mode: String,
+    pub cooldown_seconds: Option<i32>,
+    pub channel_cooldown_seconds: Option<i32>,
+    pub user_cooldown_seconds: Option<i32>,
+    pub response_type: String,
+    pub embed_title: Option<String>,
+    pub embed_colour: Option<i32>,
+    /// The name of the file in [`TAG_ATTACHMENTS_FOLDER`]
+    pub attachment_file: Option<String>,
+    /// Tags created before usage statistics were added don't have a creation date
+    pub created_at: Option<DateTime<Utc>>,
+    /// Whether the tag is limited to or kept out of the channels in `tag_scope_channels`
+    pub channel_scope: String,
+    pub disabled_in_nsfw: bool,
+    pub disabled_in_announcements: bool,
+    pub delivery_mode: Option<String>,
+    pub delete_after_seconds: Option<i32>,
 }
 
-pub struct TagBlacklistedUser {
-    #[allow(dead_code)]
+impl Tag {
+    pub fn match_mode(&self) -> TagMatchMode {
+        TagMatchMode::from_str(&self.match_mode)
+            .unwrap_or_else(|_| TagMatchMode::from_listener(&self.listener))
+    }
+
+    pub fn response_type(&self) -> TagResponseType {
+        TagResponseType::from_str(&self.response_type).unwrap_or_default()
+    }
+
+    pub fn channel_scope(&self) -> TagChannelScope {
+        TagChannelScope::from_str(&self.channel_scope).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl Tag {
+    /// A tag with the default settings of the server for the tests of the tag modules
+    pub fn for_tests(id: i32, listener: &str, match_mode: TagMatchMode) -> Self {
+        Self {
+            id,
+            listener: match_mode.normalize_listener(listener),
+            response: format!("response {id}"),
+            creator_name: "creator".to_owned(),
+            creator_id: 1,
+            server_id: 1,
+            match_mode: match_mode.to_string(),
+            cooldown_seconds: None,
+            channel_cooldown_seconds: None,
+            user_cooldown_seconds: None,
+            response_type: TagResponseType::Text.to_string(),
+            embed_title: None,
+            embed_colour: None,
+            attachment_file: None,
+            created_at: None,
+            channel_scope: TagChannelScope::Everywhere.to_string(),
+            disabled_in_nsfw: false,
+            disabled_in_announcements: false,
+            delivery_mode: None,
+            delete_after_seconds: None,
+        }
+    }
+}
+
+/// How the response of a tag is posted
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagDeliveryMode {
+    /// A message in the channel, or in the tag response channel if the server has one
+    #[default]
+    Message,
+    /// A reply to the message that tripped off the tag
+    Reply,
+    /// A message in a thread started on the message that tripped off the tag
+    Thread,
+}
+
+/// What kind of message a tag responds with
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagResponseType {
+    /// The response is sent as a message
+    #[default]
+    Text,
+    /// The response is sent with an embed with the response as its description
+    Embed,
+    /// The response is sent with an uploaded image or video
+    Attachment,
+    /// The response is an emoji the triggering message is reacted with
+    Reaction,
+}
+
+/// Which channels a tag responds in
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagChannelScope {
+    /// The tag responds in every channel
+    #[default]
+    Everywhere,
+    /// The tag only responds in the listed channels
+    AllowList,
+    /// The tag responds everywhere except the listed channels
+    DenyList,
+}
+
+/// A channel in the allow-list or deny-list of a tag
+pub struct TagScopeChannel {
+    pub tag_id: i32,
+    pub channel_id: i64,
+}
+
+/// Whether a user wants to be pinged or responded to when they trip off a tag
+pub struct TagUserPreference {
     pub user_id: i64,
+    /// [`GLOBAL_PREFERENCE_SERVER_ID`] when it applies to every server
+    pub server_id: i64,
+    pub preference: String,
+}
+
+pub const GLOBAL_PREFERENCE_SERVER_ID: i64 = 0;
+
+/// How the bot treats a user who trips off a tag
+#[derive(Display, EnumString, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagPreference {
+    /// Respond to the user without pinging them
+    NoPing,
+    /// Don't respond to the messages of the user at all
+    Ignore,
+    /// Respond to and ping the user in a server, whatever they chose for every server
+    Normal,
+}
+
+/// A role allowed to do a [`TagAction`] in a server
+pub struct TagPermissionRole {
+    pub server_id: i64,
+    pub action: String,
+    pub role_id: i64,
+}
+
+/// What the tag policy of a server can limit to roles
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagAction {
+    Create,
+    Edit,
+    Remove,
+    Ban,
 }
 
 /// A user banned from editing tags in a server
 pub struct TagBannedUser {
-    #[allow(dead_code)]
     pub user_id: i64,
-    #[allow(dead_code)]
     pub server_id: i64,
+    pub reason: Option<String>,
+    /// The moderator who issued the ban, [`None`] for bans from before they were recorded
+    pub banned_by: Option<i64>,
+    pub banned_at: DateTime<Utc>,
+    /// [`None`] if the ban doesn't run out
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Something that was done to the tags of a server
+pub struct TagAuditEntry {
+    pub id: i32,
+    pub server_id: i64,
+    /// Who did it, [`TAG_AUDIT_BOT_USER_ID`] if the bot did it on its own
+    pub user_id: i64,
+    pub action: String,
+    pub tag_listener: Option<String>,
+    pub target_user_id: Option<i64>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub const TAG_AUDIT_BOT_USER_ID: i64 = 0;
+
+/// What a [`TagAuditEntry`] records
+#[derive(Display, EnumString, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagAuditAction {
+    Create,
+    Edit,
+    Transfer,
+    Remove,
+    Ban,
+    Unban,
+    BanExpired,
 }
 
 ///Guild, Channel
@@ -28,3 +208,49 @@ pub struct TagChannel {
     pub server_id: i64,
     pub channel_id: i64,
 }
+
+/// A response a tag can pick instead of its main response
+pub struct TagResponse {
+    pub id: i32,
+    pub tag_id: i32,
+    pub response: String,
+    /// How likely the response is to be picked compared to the others,
+    /// the main response of the tag has a weight of 1
+    pub weight: i32,
+}
+
+/// A tag with what `/tag list` shows and filters by
+pub struct TagListEntry {
+    pub listener: String,
+    pub response: String,
+    pub creator_name: String,
+    pub match_mode: String,
+    pub uses: i64,
+}
+
+/// How often a tag was tripped off
+pub struct TagUseStats {
+    pub total: i64,
+    pub recent: i64,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// A tag ranked by how often it was tripped off in its server
+pub struct TopTag {
+    pub listener: String,
+    pub total: i64,
+    pub recent: i64,
+}
+
+pub const TAG_ATTACHMENTS_FOLDER: &str = "data/tag attachments";
+
+/// Settings for all of the tags of a server
+pub struct TagSettings {
+    #[allow(dead_code)]
+    pub server_id: i64,
+    pub cooldown_seconds: i32,
+    pub channel_cooldown_seconds: i32,
+    pub user_cooldown_seconds: i32,
+    pub delivery_mode: String,
+    pub delete_after_seconds: i32,
+}
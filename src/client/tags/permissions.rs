@@ -0,0 +1,171 @@
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::Context as _;
+use serenity::all::{CommandInteraction, Context, GuildId};
+use sqlx::PgPool;
+use strum::IntoEnumIterator;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    data_access::{
+        create_tag_permission_role, delete_tag_permission_role,
+        delete_tag_permission_roles_by_action, get_tag_banned_user, get_tag_permission_roles,
+    },
+    is_admin, is_moderator,
+    model::{TagAction, TagPermissionRole},
+};
+
+/// Checks the tag policy of the server and the tag bans before a user changes tags
+///
+/// Admins can always do everything so they can't lock themselves out.
+/// Actions without roles keep the defaults: anyone who isn't banned can create, edit and remove tags
+/// and moderators can ban.
+/// The error is the reason the user isn't allowed.
+pub async fn check_tag_permission(
+    command: &CommandInteraction,
+    guild_id: GuildId,
+    action: TagAction,
+    pool: &PgPool,
+) -> Result<(), String> {
+    if is_admin(command) {
+        return Ok(());
+    }
+
+    if action != TagAction::Ban
+        && get_tag_banned_user(command.user.id.get() as i64, guild_id.get() as i64, pool)
+            .await
+            .is_some()
+    {
+        return Err("You're not allowed to edit tags".to_owned());
+    }
+
+    let roles = get_tag_permission_roles(guild_id.get() as i64, pool)
+        .await
+        .into_iter()
+        .filter(|r| r.action == action.to_string())
+        .map(|r| r.role_id)
+        .collect::<Vec<_>>();
+
+    if roles.is_empty() {
+        return if action == TagAction::Ban && !is_moderator(command) {
+            Err(format!("Only moderators can {}", describe_action(action)))
+        } else {
+            Ok(())
+        };
+    }
+
+    let has_role = command
+        .member
+        .as_ref()
+        .is_some_and(|m| m.roles.iter().any(|r| roles.contains(&(r.get() as i64))));
+
+    if has_role {
+        Ok(())
+    } else {
+        Err(format!(
+            "Only members with {} can {}",
+            mention_roles(&roles),
+            describe_action(action)
+        ))
+    }
+}
+
+/// Lets admins choose which roles can create, edit, remove and ban, anyone can see the policy
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_permissions_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+    let server_id = guild_id.get() as i64;
+
+    let action = command
+        .data
+        .get_optional_string("action")
+        .and_then(|a| TagAction::from_str(&a).ok());
+
+    let Some(action) = action else {
+        let policy = describe_policy(&get_tag_permission_roles(server_id, pool).await);
+        return command_response(ctx, command, &policy).await;
+    };
+
+    if !is_admin(command) {
+        return command_response(ctx, command, "Only admins can choose who can manage tags").await;
+    }
+
+    let change = match command.data.get_optional_role("role") {
+        Some(role) => {
+            let role_id = role.get() as i64;
+
+            // The role is toggled
+            if delete_tag_permission_role(server_id, action.to_string(), role_id, pool).await == 0 {
+                create_tag_permission_role(server_id, action.to_string(), role_id, pool).await;
+                format!(
+                    "Members with <@&{role_id}> can now {}",
+                    describe_action(action)
+                )
+            } else {
+                format!(
+                    "Having <@&{role_id}> no longer lets members {}",
+                    describe_action(action)
+                )
+            }
+        }
+        None => {
+            delete_tag_permission_roles_by_action(server_id, action.to_string(), pool).await;
+            format!("No roles are needed to {} anymore", describe_action(action))
+        }
+    };
+
+    let policy = describe_policy(&get_tag_permission_roles(server_id, pool).await);
+
+    command_response(ctx, command, &format!("{change}\n\n{policy}")).await
+}
+
+fn describe_action(action: TagAction) -> &'static str {
+    match action {
+        TagAction::Create => "create tags",
+        TagAction::Edit => "edit tags",
+        TagAction::Remove => "remove tags",
+        TagAction::Ban => "ban users from editing tags",
+    }
+}
+
+fn mention_roles(roles: &[i64]) -> String {
+    roles
+        .iter()
+        .map(|r| format!("<@&{r}>"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Lists who can do each action in the server
+fn describe_policy(roles: &[TagPermissionRole]) -> String {
+    let mut policy = String::new();
+
+    for action in TagAction::iter() {
+        let action_roles = roles
+            .iter()
+            .filter(|r| r.action == action.to_string())
+            .map(|r| r.role_id)
+            .collect::<Vec<_>>();
+
+        let who = match (action_roles.is_empty(), action) {
+            (false, _) => format!("members with {}", mention_roles(&action_roles)),
+            (true, TagAction::Ban) => "moderators".to_owned(),
+            (true, _) => "anyone".to_owned(),
+        };
+
+        writeln!(policy, "Who can {}: {who}", describe_action(action)).unwrap();
+    }
+
+    policy += "Admins can always do all of these and only moderators can edit or remove tags they don't own";
+
+    policy
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use serenity::all::{
+    CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    GuildId, UserId,
+};
+use sqlx::PgPool;
+use tracing::{Instrument, info_span};
+
+use crate::client::{get_option_from_command::GetOptionFromCommand, global_data::GetBotState};
+
+use super::{
+    data_access::{
+        delete_tag_user_preference, get_tag_user_preferences, upsert_tag_user_preference,
+    },
+    model::{GLOBAL_PREFERENCE_SERVER_ID, TagPreference, TagUserPreference},
+};
+
+/// The tag preferences of every user, keyed by the user and the server they apply to
+#[derive(Debug, Default)]
+pub struct TagUserPreferences(HashMap<(UserId, i64), TagPreference>);
+
+impl TagUserPreferences {
+    fn new(preferences: Vec<TagUserPreference>) -> Self {
+        Self(
+            preferences
+                .into_iter()
+                .filter_map(|p| {
+                    let preference = p.preference.parse().ok()?;
+                    Some(((UserId::new(p.user_id as u64), p.server_id), preference))
+                })
+                .collect(),
+        )
+    }
+
+    /// The preference of the user in the server, which overrides the one they set for every server
+    fn get(&self, user_id: UserId, guild_id: GuildId) -> Option<TagPreference> {
+        self.0
+            .get(&(user_id, guild_id.get() as i64))
+            .or_else(|| self.0.get(&(user_id, GLOBAL_PREFERENCE_SERVER_ID)))
+            .copied()
+    }
+}
+
+/// How the user wants to be treated when they trip off a tag in the server
+///
+/// The preferences are read from the database the first time they're needed
+pub async fn get_tag_preference(
+    ctx: &Context,
+    user_id: UserId,
+    guild_id: GuildId,
+    pool: &PgPool,
+) -> Option<TagPreference> {
+    if let Some(preferences) = &ctx.bot_state().read().await.tag_user_preferences {
+        return preferences.get(user_id, guild_id);
+    }
+
+    load_tag_preferences(ctx, pool).await;
+    ctx.bot_state()
+        .read()
+        .await
+        .tag_user_preferences
+        .as_ref()
+        .and_then(|p| p.get(user_id, guild_id))
+}
+
+/// Reads the preferences from the database if they aren't cached yet
+async fn load_tag_preferences(ctx: &Context, pool: &PgPool) {
+    if ctx.bot_state().read().await.tag_user_preferences.is_some() {
+        return;
+    }
+
+    let preferences = TagUserPreferences::new(get_tag_user_preferences(pool).await);
+
+    // Preferences loaded in the meantime may already have a toggle in them
+    ctx.bot_state()
+        .write()
+        .await
+        .tag_user_preferences
+        .get_or_insert(preferences);
+}
+
+/// What the user's own preference for a server or every server becomes when they toggle `preference`.
+///
+/// `fallback` is the preference that applies without their own one, which is the one
+/// for every server when toggling it in a server. Turning off a preference that comes from
+/// the fallback needs a [`TagPreference::Normal`] to override it.
+fn toggled_preference(
+    own: Option<TagPreference>,
+    fallback: Option<TagPreference>,
+    preference: TagPreference,
+) -> Option<TagPreference> {
+    let is_on = own.or(fallback) == Some(preference);
+
+    if fallback == Some(preference) {
+        is_on.then_some(TagPreference::Normal)
+    } else {
+        (!is_on).then_some(preference)
+    }
+}
+
+/// Turns the preference on or off for the user in the server, or in every server if there's no server
+#[tracing::instrument(skip(ctx, pool))]
+pub async fn toggle_tag_preference(
+    ctx: &Context,
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    preference: TagPreference,
+    pool: &PgPool,
+) -> String {
+    let server_id = guild_id.map_or(GLOBAL_PREFERENCE_SERVER_ID, |g| g.get() as i64);
+
+    load_tag_preferences(ctx, pool).await;
+    let (own, fallback) = {
+        let bot_state = ctx.bot_state().read().await;
+        let get = |server_id| {
+            bot_state
+                .tag_user_preferences
+                .as_ref()
+                .and_then(|p| p.0.get(&(user_id, server_id)).copied())
+        };
+
+        let fallback = guild_id.and_then(|_| get(GLOBAL_PREFERENCE_SERVER_ID));
+        (get(server_id), fallback)
+    };
+    let new_preference = toggled_preference(own, fallback, preference);
+
+    // The database is updated before the lock is taken so messages aren't held up by it
+    match new_preference {
+        Some(new_preference) => {
+            upsert_tag_user_preference(
+                user_id.get() as i64,
+                server_id,
+                new_preference.to_string(),
+                pool,
+            )
+            .await;
+        }
+        None => delete_tag_user_preference(user_id.get() as i64, server_id, pool).await,
+    }
+
+    let mut bot_state = ctx.bot_state().write().await;
+    let preferences = bot_state.tag_user_preferences.get_or_insert_default();
+    match new_preference {
+        Some(new_preference) => preferences.0.insert((user_id, server_id), new_preference),
+        None => preferences.0.remove(&(user_id, server_id)),
+    };
+
+    describe_preference(
+        new_preference.or(fallback),
+        if guild_id.is_some() {
+            "in this server"
+        } else {
+            "in any server"
+        },
+    )
+}
+
+fn describe_preference(preference: Option<TagPreference>, place: &str) -> String {
+    match preference {
+        None | Some(TagPreference::Normal) => {
+            format!("I will now respond to you and ping you when you trip off a tag {place}")
+        }
+        Some(TagPreference::NoPing) => {
+            format!("I won't ping you anymore when you trip off a tag {place}")
+        }
+        Some(TagPreference::Ignore) => {
+            format!("I won't respond to your messages anymore when they trip off a tag {place}")
+        }
+    }
+}
+
+/// `/tag stop-pinging-me` and `/tag ignore-me`, for this server or every server
+#[tracing::instrument(skip(ctx, command, pool))]
+pub async fn tag_preference_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    preference: TagPreference,
+    pool: &PgPool,
+) {
+    let guild_id = if command.data.get_optional_bool("everywhere") == Some(true) {
+        None
+    } else {
+        command.guild_id
+    };
+
+    let response = toggle_tag_preference(ctx, command.user.id, guild_id, preference, pool).await;
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new().content(response),
+            ),
+        )
+        .instrument(info_span!("Sending message"))
+        .await
+        .expect("Error creating interaction response");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use TagPreference::{Ignore, NoPing, Normal};
+
+    #[test]
+    fn toggles_the_preference_for_every_server() {
+        assert_eq!(toggled_preference(None, None, NoPing), Some(NoPing));
+        assert_eq!(toggled_preference(Some(NoPing), None, NoPing), None);
+        assert_eq!(toggled_preference(Some(Ignore), None, NoPing), Some(NoPing));
+    }
+
+    #[test]
+    fn overrides_the_preference_for_every_server_in_a_server() {
+        // Pinging can be turned back on in one server
+        assert_eq!(toggled_preference(None, Some(NoPing), NoPing), Some(Normal));
+        // and turned off again by going back to the preference for every server
+        assert_eq!(toggled_preference(Some(Normal), Some(NoPing), NoPing), None);
+        assert_eq!(toggled_preference(Some(Ignore), Some(NoPing), NoPing), None);
+    }
+
+    #[test]
+    fn toggles_preferences_the_fallback_doesnt_have() {
+        assert_eq!(toggled_preference(None, Some(NoPing), Ignore), Some(Ignore));
+        assert_eq!(toggled_preference(Some(Ignore), Some(NoPing), Ignore), None);
+    }
+
+    #[test]
+    fn server_preferences_come_before_the_ones_for_every_server() {
+        let user_id = UserId::new(1);
+        let guild_id = GuildId::new(2);
+        let preferences = TagUserPreferences(HashMap::from([
+            ((user_id, GLOBAL_PREFERENCE_SERVER_ID), NoPing),
+            ((user_id, 2), Normal),
+        ]));
+
+        assert_eq!(preferences.get(user_id, guild_id), Some(Normal));
+        assert_eq!(preferences.get(user_id, GuildId::new(3)), Some(NoPing));
+        assert_eq!(preferences.get(UserId::new(4), guild_id), None);
+    }
+}
@@ -6,42 +6,49 @@ use serenity::{
 use sqlx::{Pool, Postgres};
 use tracing::{Instrument, info_span};
 
-use crate::client::tags::{data_access::get_tag_banned_user, user_banned_response};
+use crate::client::tags::{
+    audit_log::TagAuditLog,
+    can_manage_tag, find_tag_by_listener,
+    model::{TagAction, TagAuditAction},
+    not_tag_owner_response, permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+    response_types::delete_tag_attachment,
+};
 
 use super::data_access;
 
 #[tracing::instrument(skip(ctx))]
 pub async fn remove_tag(ctx: &Context, command: &CommandInteraction, pool: &Pool<Postgres>) {
     let listener = get_listener(command);
+    let guild_id = command
+        .guild_id
+        .expect("This command can't be called outside guilds");
 
-    let tag = data_access::get_tag_by_listener(
-        &listener,
-        command
-            .guild_id
-            .expect("This command can't be called outside guilds")
-            .get() as i64,
-        pool,
-    )
-    .await;
-
-    if get_tag_banned_user(
-        command.user.id.get() as i64,
-        command
-            .guild_id
-            .expect("This command can't be called outside guilds")
-            .get() as i64,
-        pool,
-    )
-    .await
-    .is_some()
-    {
-        user_banned_response(command, ctx).await;
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Remove, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
         return;
     }
 
+    let tag = find_tag_by_listener(&listener, guild_id, pool).await;
+
     match tag {
+        Some(tag) if !can_manage_tag(command, &tag) => {
+            not_tag_owner_response(command, ctx).await;
+        }
         Some(tag) => {
             data_access::delete_tag(tag.id, pool).await;
+            if let Err(err) = delete_tag_attachment(&tag).await {
+                tracing::error!(
+                    "Couldn't delete the attachment of tag {}: {err}",
+                    tag.listener
+                );
+            }
+            refresh_tag_matcher(ctx, guild_id, pool).await;
+            TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Remove)
+                .tag(&tag.listener)
+                .save(pool)
+                .await;
 
             println!(
                 "{} removed tag {} in server {}",
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use serenity::all::{
+    CommandInteraction, Context, CreateInputText, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateQuickModal, InputTextStyle, ModalInteraction,
+    QuickModal,
+};
+use tracing::{Instrument, info_span};
+
+/// Discord doesn't allow more characters in a text box, longer responses aren't filled in
+const MAX_INPUT_CHARACTERS: usize = 4000;
+
+/// The interaction a tag command is answered through
+pub enum TagCommandReply<'a> {
+    Command(&'a CommandInteraction),
+    /// The command opened a modal, which took its place
+    Modal(ModalInteraction),
+}
+
+impl TagCommandReply<'_> {
+    pub async fn send(&self, ctx: &Context, content: &str) -> serenity::Result<()> {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new().content(content),
+        );
+
+        async {
+            match self {
+                Self::Command(command) => command.create_response(&ctx.http, response).await,
+                Self::Modal(modal) => modal.create_response(&ctx.http, response).await,
+            }
+        }
+        .instrument(info_span!("Sending message"))
+        .await
+    }
+}
+
+/// Asks for the response of a tag in a modal since the options of slash commands can't hold line breaks
+///
+/// The text box starts out with `current_response` so it can be edited instead of rewritten.
+/// Returns the response and the interaction the command has to be answered through now,
+/// or [`None`] if the user closed the modal or let it time out.
+pub async fn ask_for_response(
+    ctx: &Context,
+    command: &CommandInteraction,
+    title: &str,
+    current_response: Option<&str>,
+) -> anyhow::Result<Option<(String, TagCommandReply<'static>)>> {
+    let mut input = CreateInputText::new(InputTextStyle::Paragraph, "Response", "response");
+    if let Some(current_response) =
+        current_response.filter(|r| r.chars().count() <= MAX_INPUT_CHARACTERS)
+    {
+        input = input.value(current_response.to_owned());
+    }
+
+    let modal = CreateQuickModal::new(title)
+        .timeout(Duration::from_mins(10))
+        .field(input);
+
+    let Some(modal_response) = command.quick_modal(ctx, modal).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some((
+        modal_response.inputs[0].to_string(),
+        TagCommandReply::Modal(modal_response.interaction),
+    )))
+}
@@ -0,0 +1,346 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context as _;
+use serenity::all::{
+    Colour, CommandInteraction, Context, CreateAllowedMentions, CreateAttachment, CreateEmbed,
+    CreateMessage, EditInteractionResponse, GuildId, Permissions, ReactionType, UserId,
+};
+use sqlx::PgPool;
+use tracing::{Instrument, info_span};
+use unicode_properties::UnicodeEmoji;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    Tag,
+    audit_log::TagAuditLog,
+    can_manage_tag,
+    data_access::{get_tag_responses_by_tag_id, update_tag_response_type},
+    find_tag_by_listener,
+    model::{TAG_ATTACHMENTS_FOLDER, TagAction, TagAuditAction, TagResponseType},
+    not_tag_owner_response, permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+};
+
+/// Bots can't upload files larger than this without boosts
+const MAX_ATTACHMENT_SIZE_MB: u64 = 8;
+
+/// The message a tag responds with, built once and sent wherever the response ends up
+pub struct TagResponseMessage {
+    content: String,
+    embed: Option<CreateEmbed<'static>>,
+    attachment: Option<CreateAttachment<'static>>,
+    reaction: Option<ReactionType>,
+}
+
+impl TagResponseMessage {
+    /// Builds the message of the tag with the rendered response as its content
+    pub async fn new(tag: &Tag, content: String) -> anyhow::Result<Self> {
+        let mut message = Self {
+            content,
+            embed: None,
+            attachment: None,
+            reaction: None,
+        };
+
+        match tag.response_type() {
+            TagResponseType::Text => {}
+            TagResponseType::Embed => {
+                let mut embed =
+                    CreateEmbed::new().description(std::mem::take(&mut message.content));
+                if let Some(title) = &tag.embed_title {
+                    embed = embed.title(title.clone());
+                }
+                if let Some(colour) = tag.embed_colour {
+                    embed = embed.colour(Colour::new(colour.cast_unsigned()));
+                }
+
+                message.embed = Some(embed);
+            }
+            TagResponseType::Attachment => {
+                let file_name = tag
+                    .attachment_file
+                    .clone()
+                    .context("Attachment tags should have a file")?;
+                let bytes = tokio::fs::read(attachment_path(&file_name)).await?;
+
+                message.attachment = Some(CreateAttachment::bytes(bytes, file_name));
+            }
+            TagResponseType::Reaction => {
+                message.reaction = Some(ReactionType::from_str(message.content.trim())?);
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// The emoji to react with instead of sending a message
+    pub fn reaction(&self) -> Option<&ReactionType> {
+        self.reaction.as_ref()
+    }
+
+    /// The permissions the message needs besides being able to send messages
+    pub fn needed_permissions(&self) -> Permissions {
+        let mut permissions = Permissions::empty();
+        if self.embed.is_some() {
+            permissions |= Permissions::EMBED_LINKS;
+        }
+        if self.attachment.is_some() {
+            permissions |= Permissions::ATTACH_FILES;
+        }
+
+        permissions
+    }
+
+    /// Creates the message to send, optionally mentioning the user who tripped off the tag.
+    ///
+    /// Nobody but `ping` is pinged, whether by the mention or by the response itself
+    pub fn to_message(
+        &self,
+        mention: Option<String>,
+        ping: Option<UserId>,
+    ) -> CreateMessage<'static> {
+        let content = match mention {
+            Some(mention) if self.content.is_empty() => mention,
+            Some(mention) => mention + " " + &self.content,
+            None => self.content.clone(),
+        };
+
+        let mut message = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(allowed_mentions(ping));
+        if let Some(embed) = &self.embed {
+            message = message.embed(embed.clone());
+        }
+        if let Some(attachment) = &self.attachment {
+            message = message.add_file(attachment.clone());
+        }
+
+        message
+    }
+}
+
+/// Only lets the user be pinged, if there is one
+pub fn allowed_mentions(ping: Option<UserId>) -> CreateAllowedMentions<'static> {
+    match ping {
+        Some(user_id) => CreateAllowedMentions::new().users(vec![user_id]),
+        None => CreateAllowedMentions::new(),
+    }
+}
+
+/// Checks that the response is a single emoji that can be reacted with in the guild.
+///
+/// Custom emojis have to belong to the guild, they're only taken on trust if the guild isn't cached.
+pub fn is_valid_reaction(ctx: &Context, guild_id: GuildId, response: &str) -> bool {
+    let response = response.trim();
+
+    match ReactionType::from_str(response) {
+        Ok(ReactionType::Custom { id, .. }) => ctx
+            .cache
+            .guild(guild_id)
+            .is_none_or(|guild| guild.emojis.get(&id).is_some()),
+        Ok(ReactionType::Unicode(_)) => is_unicode_emoji(response),
+        _ => false,
+    }
+}
+
+/// Whether the text is a single emoji, including ones joined from several emojis like 👨‍👩‍👧
+fn is_unicode_emoji(text: &str) -> bool {
+    // Digits, `#` and `*` are emojis too but only when they're part of a keycap like 1️⃣
+    text.graphemes(true).count() == 1
+        && text
+            .chars()
+            .all(UnicodeEmoji::is_emoji_char_or_emoji_component)
+        && !text.is_ascii()
+}
+
+fn attachment_path(file_name: &str) -> PathBuf {
+    Path::new(TAG_ATTACHMENTS_FOLDER).join(file_name)
+}
+
+/// Deletes the uploaded file of a tag if it has one
+pub async fn delete_tag_attachment(tag: &Tag) -> std::io::Result<()> {
+    let Some(file_name) = &tag.attachment_file else {
+        return Ok(());
+    };
+
+    let path = attachment_path(file_name);
+    if path.exists() {
+        tokio::fs::remove_file(path).await?;
+    }
+
+    Ok(())
+}
+
+/// Changes what kind of message a tag responds with
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_response_type_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    if !can_manage_tag(command, &tag) {
+        not_tag_owner_response(command, ctx).await;
+        return Ok(());
+    }
+
+    let response_type = TagResponseType::from_str(&command.data.get_string("type"))?;
+
+    let embed_title = command
+        .data
+        .get_optional_string("title")
+        .or(tag.embed_title.clone());
+
+    let embed_colour = match command.data.get_optional_string("colour") {
+        Some(colour) => match u32::from_str_radix(colour.trim().trim_start_matches('#'), 16) {
+            Ok(colour) if colour <= 0x00FF_FFFF => Some(colour.cast_signed()),
+            _ => {
+                return command_response(
+                    ctx,
+                    command,
+                    "The colour has to be a hex colour like #FF0000",
+                )
+                .await;
+            }
+        },
+        None => tag.embed_colour,
+    };
+
+    let attachment = command
+        .data
+        .get_optional_attachment("attachment")
+        .and_then(|id| command.data.resolved.attachments.get(&id));
+
+    let mut attachment_file = tag.attachment_file.clone();
+
+    match response_type {
+        TagResponseType::Text | TagResponseType::Embed => {}
+        TagResponseType::Reaction => {
+            let extra_responses = get_tag_responses_by_tag_id(tag.id, pool).await;
+            if !is_valid_reaction(ctx, guild_id, &tag.response)
+                || extra_responses
+                    .iter()
+                    .any(|r| !is_valid_reaction(ctx, guild_id, &r.response))
+            {
+                return command_response(
+                    ctx,
+                    command,
+                    "Every response of a reaction tag has to be a single emoji",
+                )
+                .await;
+            }
+        }
+        TagResponseType::Attachment => {
+            if let Some(attachment) = attachment {
+                if u64::from(attachment.size) > MAX_ATTACHMENT_SIZE_MB * 1024 * 1024 {
+                    return command_response(
+                        ctx,
+                        command,
+                        &format!("The file can't be larger than {MAX_ATTACHMENT_SIZE_MB}MB"),
+                    )
+                    .await;
+                }
+
+                command.defer(&ctx.http).await?;
+
+                let extension = Path::new(&attachment.filename)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or("bin")
+                    .to_lowercase();
+                let file_name = format!("{}.{extension}", tag.id);
+
+                delete_tag_attachment(&tag).await?;
+                tokio::fs::write(attachment_path(&file_name), attachment.download().await?).await?;
+
+                attachment_file = Some(file_name);
+            } else if attachment_file.is_none() {
+                return command_response(
+                    ctx,
+                    command,
+                    "Attachment tags need a file, add one with the attachment option",
+                )
+                .await;
+            }
+        }
+    }
+
+    // The file of an attachment tag isn't needed anymore once it responds with something else
+    if response_type != TagResponseType::Attachment && attachment_file.is_some() {
+        delete_tag_attachment(&tag).await?;
+        attachment_file = None;
+    }
+
+    update_tag_response_type(
+        tag.id,
+        response_type.to_string(),
+        embed_title,
+        embed_colour,
+        attachment_file,
+        pool,
+    )
+    .await;
+    refresh_tag_matcher(ctx, guild_id, pool).await;
+    TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Edit)
+        .tag(&tag.listener)
+        .details(format!("responds with {response_type}"))
+        .save(pool)
+        .await;
+
+    let response = format!("The tag {} now responds with {response_type}", tag.listener);
+    if response_type == TagResponseType::Attachment && attachment.is_some() {
+        command
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(response))
+            .instrument(info_span!("Sending message"))
+            .await?;
+
+        return Ok(());
+    }
+
+    command_response(ctx, command, &response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_emojis_are_unicode_emojis() {
+        for emoji in ["👍", "👍🏽", "👨‍👩‍👧", "🇫🇮", "1️⃣", "❤️"] {
+            assert!(is_unicode_emoji(emoji), "{emoji} should be an emoji");
+        }
+    }
+
+    #[test]
+    fn text_and_several_emojis_are_not_unicode_emojis() {
+        for text in ["", "a", "1", "#", "é", "👍👍", "👍 ", "hi👍"] {
+            assert!(!is_unicode_emoji(text), "{text} shouldn't be an emoji");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::Write;
+
+use anyhow::Context as _;
+use serenity::all::{CommandInteraction, Context, GuildId};
+use sqlx::PgPool;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    Tag,
+    audit_log::TagAuditLog,
+    can_manage_tag,
+    data_access::{create_tag_response, delete_tag_response, get_tag_responses_by_tag_id},
+    find_tag_by_listener,
+    matching::is_response_valid,
+    model::{TagAction, TagAuditAction, TagResponseType},
+    not_tag_owner_response, permission_denied_response,
+    permissions::check_tag_permission,
+    refresh_tag_matcher,
+    response_types::is_valid_reaction,
+};
+
+/// How many responses a tag can have besides its main response
+pub const MAX_EXTRA_RESPONSES: usize = 25;
+
+/// Adds a response the tag picks at random
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn add_tag_response_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let Some(tag) = get_tag_to_change(ctx, command, pool).await? else {
+        return Ok(());
+    };
+
+    let response = command.data.get_string("response");
+    if !is_response_valid(&response) {
+        return command_response(ctx, command, "Responses can't contain mentions").await;
+    }
+
+    if tag.response_type() == TagResponseType::Reaction
+        && !is_valid_reaction(ctx, GuildId::new(tag.server_id as u64), &response)
+    {
+        return command_response(
+            ctx,
+            command,
+            "The responses of a reaction tag have to be emojis",
+        )
+        .await;
+    }
+
+    if get_tag_responses_by_tag_id(tag.id, pool).await.len() >= MAX_EXTRA_RESPONSES {
+        return command_response(
+            ctx,
+            command,
+            &format!("A tag can't have more than {MAX_EXTRA_RESPONSES} extra responses"),
+        )
+        .await;
+    }
+
+    let weight = command
+        .data
+        .get_optional_int("weight")
+        .and_then(|w| w.try_into().ok())
+        .unwrap_or(1);
+
+    let tag_response = create_tag_response(tag.id, response.trim().to_owned(), weight, pool).await;
+    refresh_tag_matcher(ctx, GuildId::new(tag.server_id as u64), pool).await;
+    TagAuditLog::new(
+        GuildId::new(tag.server_id as u64),
+        command.user.id,
+        TagAuditAction::Edit,
+    )
+    .tag(&tag.listener)
+    .details(format!("added response {}", tag_response.id))
+    .save(pool)
+    .await;
+
+    command_response(
+        ctx,
+        command,
+        &format!(
+            "Added response {} with a weight of {} to the tag {}",
+            tag_response.id, tag_response.weight, tag.listener
+        ),
+    )
+    .await
+}
+
+/// Removes one of the extra responses of a tag
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn remove_tag_response_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let Some(tag) = get_tag_to_change(ctx, command, pool).await? else {
+        return Ok(());
+    };
+
+    let id = command
+        .data
+        .get_optional_int("id")
+        .and_then(|id| id.try_into().ok())
+        .context("Response id should be an integer")?;
+
+    let response = if delete_tag_response(id, tag.id, pool).await == 0 {
+        format!("The tag {} doesn't have a response {id}", tag.listener)
+    } else {
+        refresh_tag_matcher(ctx, GuildId::new(tag.server_id as u64), pool).await;
+        TagAuditLog::new(
+            GuildId::new(tag.server_id as u64),
+            command.user.id,
+            TagAuditAction::Edit,
+        )
+        .tag(&tag.listener)
+        .details(format!("removed response {id}"))
+        .save(pool)
+        .await;
+
+        format!("Removed response {id} from the tag {}", tag.listener)
+    };
+
+    command_response(ctx, command, &response).await
+}
+
+/// Lists the responses of a tag and how likely they are
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn list_tag_responses_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    let extra_responses = get_tag_responses_by_tag_id(tag.id, pool).await;
+    let total_weight = 1 + extra_responses
+        .iter()
+        .map(|r| i64::from(r.weight.max(0)))
+        .sum::<i64>();
+
+    let mut message = format!(
+        "Responses of the tag {}:\nmain (weight 1/{total_weight}): {}",
+        tag.listener,
+        shorten(&tag.response)
+    );
+    for response in &extra_responses {
+        write!(
+            message,
+            "\n{} (weight {}/{total_weight}): {}",
+            response.id,
+            response.weight,
+            shorten(&response.response)
+        )?;
+    }
+
+    command_response(ctx, command, &message).await
+}
+
+/// Cuts long responses short so the whole list fits in a message
+fn shorten(response: &str) -> String {
+    const MAX_SHOWN_CHARACTERS: usize = 50;
+
+    if response.chars().count() > MAX_SHOWN_CHARACTERS {
+        response
+            .chars()
+            .take(MAX_SHOWN_CHARACTERS)
+            .collect::<String>()
+            + "..."
+    } else {
+        response.to_owned()
+    }
+}
+
+/// Finds the tag from the command if the user is allowed to change it and responds if they aren't
+async fn get_tag_to_change(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<Option<Tag>> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Edit, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(None);
+    }
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await?;
+        return Ok(None);
+    };
+
+    if !can_manage_tag(command, &tag) {
+        not_tag_owner_response(command, ctx).await;
+        return Ok(None);
+    }
+
+    Ok(Some(tag))
+}
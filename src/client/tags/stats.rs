@@ -0,0 +1,130 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use serenity::all::{CommandInteraction, Context};
+use sqlx::PgPool;
+use tokio::time::interval;
+
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand, helper_funcs::command_response,
+};
+
+use super::{
+    channel_scope::describe_channel_scope,
+    data_access::{archive_tag_uses, get_tag_use_stats, get_top_tags},
+    find_tag_by_listener,
+};
+
+/// Uses within this many days count as recent
+const RECENT_DAYS: i64 = 7;
+/// How many tags `/tag top` ranks
+const TOP_TAGS_LIMIT: i64 = 10;
+/// Uses older than this many days are only kept as a total per tag
+const USE_RETENTION_DAYS: i64 = 90;
+
+/// Shows who made a tag and how often it's used
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn tag_info_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let listener = command.data.get_string("tag");
+    let Some(tag) = find_tag_by_listener(&listener, guild_id, pool).await else {
+        return command_response(
+            ctx,
+            command,
+            &format!("Couldn't find the tag \"{listener}\""),
+        )
+        .await;
+    };
+
+    let stats = get_tag_use_stats(tag.id, recent_since(), pool).await;
+
+    let created = tag
+        .created_at
+        .map_or_else(|| "unknown".to_owned(), |c| discord_timestamp(c, 'D'));
+    let last_used = stats
+        .last_used
+        .map_or_else(|| "never".to_owned(), |l| discord_timestamp(l, 'R'));
+
+    let channels = describe_channel_scope(&tag, pool).await;
+
+    let message = format!(
+        "Tag: {}\nCreated by: {}\nCreated: {created}\nMatch mode: {}\nResponds with: {}\n\
+        Responds in: {channels}\nUses: {}\nUses in the last {RECENT_DAYS} days: {}\nLast used: {last_used}",
+        tag.listener,
+        tag.creator_name,
+        tag.match_mode(),
+        tag.response_type(),
+        stats.total,
+        stats.recent,
+    );
+
+    command_response(ctx, command, &message).await
+}
+
+/// Ranks the most used tags of the server
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn top_tags_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let top_tags = get_top_tags(guild_id.get() as i64, recent_since(), TOP_TAGS_LIMIT, pool).await;
+
+    if top_tags.is_empty() {
+        return command_response(ctx, command, "No tags have been used in this server yet").await;
+    }
+
+    let mut message = String::from("The most used tags in this server:");
+    for (rank, tag) in top_tags.iter().enumerate() {
+        write!(
+            message,
+            "\n{}. {}: {} uses, {} in the last {RECENT_DAYS} days",
+            rank + 1,
+            tag.listener,
+            tag.total,
+            tag.recent
+        )?;
+    }
+
+    command_response(ctx, command, &message).await
+}
+
+/// Archives the old tag uses once a day
+pub async fn archive_tag_uses_loop(pool: PgPool) -> ! {
+    let mut interval = interval(Duration::from_secs(24 * 60 * 60));
+    loop {
+        interval.tick().await;
+
+        archive_old_tag_uses(&pool).await;
+    }
+}
+
+/// Folds the uses past the retention into the totals of their tags so `tag_uses` doesn't grow forever
+async fn archive_old_tag_uses(pool: &PgPool) {
+    let used_before = Utc::now() - chrono::Duration::days(USE_RETENTION_DAYS);
+    let archived = archive_tag_uses(used_before, pool).await;
+    if archived > 0 {
+        tracing::info!("Archived {archived} tag uses");
+    }
+}
+
+fn recent_since() -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(RECENT_DAYS)
+}
+
+/// Formats the time so Discord shows it in the timezone of whoever reads it
+pub fn discord_timestamp(time: DateTime<Utc>, style: char) -> String {
+    format!("<t:{}:{style}>", time.timestamp())
+}
@@ -1,15 +1,39 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Context as _;
+use chrono::Utc;
 use serenity::{
-    all::{CommandDataOptionValue, CommandInteraction, CreateInteractionResponseMessage, UserId},
+    all::{
+        CommandInteraction, CreateEmbed, CreateInteractionResponseMessage, GuildId, Permissions,
+        UserId,
+    },
     builder::CreateInteractionResponse,
     prelude::Context,
 };
-use sqlx::{Pool, Postgres};
+use sqlx::{PgPool, Pool, Postgres};
+use tokio::time::interval;
 use tracing::{Instrument, info_span};
 
-use crate::client::tags::data_access::{
-    create_tag_banned_user, delete_tag_banned_user, get_tag_banned_user,
+use crate::client::{
+    get_option_from_command::GetOptionFromCommand,
+    helper_funcs::{command_response, command_response_message},
+    tags::{
+        audit_log::TagAuditLog,
+        data_access::{
+            delete_expired_tag_banned_users, delete_tag_banned_user, get_tag_banned_user,
+            get_tag_banned_users_by_server_id, upsert_tag_banned_user,
+        },
+        model::{TagAction, TagAuditAction, TagBannedUser},
+        permission_denied_response,
+        permissions::check_tag_permission,
+        stats::discord_timestamp,
+    },
 };
 
+/// How many bans `/tag bans` shows
+const MAX_SHOWN_BANS: usize = 10;
+
+/// Bans a user from editing tags, for good or until the ban runs out
 #[tracing::instrument(skip(ctx))]
 pub async fn ban_user_from_editing_tags(
     ctx: &Context,
@@ -21,82 +45,254 @@ pub async fn ban_user_from_editing_tags(
         return;
     };
 
-    if !command
-        .user
-        .member
-        .as_ref()
-        .unwrap()
-        .permissions
-        .unwrap()
-        .moderate_members()
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Ban, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return;
+    }
+
+    let target = command
+        .data
+        .get_optional_user("user")
+        .expect("The user option is required");
+
+    if !rank_of_issuer(ctx, command, guild_id)
+        .can_ban(rank_of_target(ctx, command, guild_id, target))
     {
-        admins_only_response(command, ctx).await;
+        command_response(
+            ctx,
+            command,
+            "You can't ban the owner, admins or moderators unless you're above them",
+        )
+        .await
+        .expect("Error creating interaction response");
+        return;
     }
 
-    let user = get_user(command);
-    let user_id = user.get() as i64;
+    let user_id = target.get() as i64;
     let server_id = guild_id.get() as i64;
 
-    let tag_banned_user = get_tag_banned_user(user_id, server_id, pool).await;
+    let hours = command.data.get_optional_int("days").unwrap_or(0) * 24
+        + command.data.get_optional_int("hours").unwrap_or(0);
+    let expires_at = (hours > 0).then(|| Utc::now() + chrono::Duration::hours(hours));
+    let reason = command.data.get_optional_string("reason");
 
-    if tag_banned_user.is_none() {
-        create_tag_banned_user(user_id, server_id, pool).await;
-        tag_ban_created_response(command, ctx).await;
-    } else {
-        delete_tag_banned_user(user_id, server_id, pool).await;
-        tag_ban_deleted_response(command, ctx).await;
+    let was_banned = get_tag_banned_user(user_id, server_id, pool)
+        .await
+        .is_some();
+
+    let ban = upsert_tag_banned_user(
+        user_id,
+        server_id,
+        reason.clone(),
+        command.user.id.get() as i64,
+        expires_at,
+        pool,
+    )
+    .await;
+
+    let mut audit_log =
+        TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Ban).target(user_id);
+    if let Some(reason) = reason {
+        audit_log = audit_log.details(reason);
     }
-}
+    audit_log.save(pool).await;
 
-async fn tag_ban_created_response(command: &CommandInteraction, ctx: &Context) {
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content(format!("Banned user from editing tags")),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
+    let action = if was_banned {
+        "Updated the ban of"
+    } else {
+        "Banned"
+    };
+    let response = format!(
+        "{action} <@{user_id}> from editing tags {}",
+        describe_ban(&ban)
+    );
+
+    command_response(ctx, command, response)
         .await
         .expect("Error creating interaction response");
 }
 
-async fn tag_ban_deleted_response(command: &CommandInteraction, ctx: &Context) {
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content(format!("Unbanned user from editing tags")),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
+/// Lifts the tag ban of a user before it runs out
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn unban_user_from_editing_tags(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Ban, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let user_id = command
+        .data
+        .get_optional_user("user")
+        .context("The user option is required")?
+        .get() as i64;
+    let server_id = guild_id.get() as i64;
+
+    let response = if get_tag_banned_user(user_id, server_id, pool)
         .await
-        .expect("Error creating interaction response");
+        .is_some()
+    {
+        delete_tag_banned_user(user_id, server_id, pool).await;
+        TagAuditLog::new(guild_id, command.user.id, TagAuditAction::Unban)
+            .target(user_id)
+            .save(pool)
+            .await;
+
+        format!("Unbanned <@{user_id}> from editing tags")
+    } else {
+        format!("<@{user_id}> isn't banned from editing tags")
+    };
+
+    command_response(ctx, command, response).await
 }
 
-async fn tag_outside_server_response(command: &CommandInteraction, ctx: &Context) {
-    command
-        .create_response(
-            &ctx.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .content("Can't run this command outside of a server"),
-            ),
-        )
-        .instrument(info_span!("Sending message"))
-        .await
-        .expect("Error creating interaction response");
+/// Lists the tag bans of the server that haven't run out
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn list_tag_bans_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    if let Err(reason) = check_tag_permission(command, guild_id, TagAction::Ban, pool).await {
+        permission_denied_response(command, ctx, &reason).await;
+        return Ok(());
+    }
+
+    let bans = get_tag_banned_users_by_server_id(guild_id.get() as i64, pool).await;
+
+    if bans.is_empty() {
+        return command_response(ctx, command, "Nobody is banned from editing tags").await;
+    }
+
+    let mut description = String::new();
+    for ban in bans.iter().take(MAX_SHOWN_BANS) {
+        let banned_by = ban
+            .banned_by
+            .map_or_else(String::new, |b| format!(" by <@{b}>"));
+        writeln!(
+            description,
+            "<@{}>{banned_by} {} {}",
+            ban.user_id,
+            discord_timestamp(ban.banned_at, 'R'),
+            describe_ban(ban)
+        )?;
+    }
+    if bans.len() > MAX_SHOWN_BANS {
+        writeln!(description, "and {} more", bans.len() - MAX_SHOWN_BANS)?;
+    }
+
+    let embed = CreateEmbed::new()
+        .title("Users banned from editing tags")
+        .description(description);
+
+    command_response_message(
+        ctx,
+        command,
+        CreateInteractionResponseMessage::new().embed(embed),
+    )
+    .await
+}
+
+/// Lifts the tag bans that ran out every minute and records it in the audit logs
+pub async fn lift_expired_tag_bans_loop(pool: PgPool) -> ! {
+    let mut interval = interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+
+        for ban in delete_expired_tag_banned_users(&pool).await {
+            TagAuditLog::by_bot(ban.server_id, TagAuditAction::BanExpired)
+                .target(ban.user_id)
+                .save(&pool)
+                .await;
+        }
+    }
+}
+
+/// How far up the server someone is, only members can be banned by someone at the same rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Member,
+    Moderator,
+    Admin,
+    Owner,
+}
+
+impl Rank {
+    fn new(
+        ctx: &Context,
+        guild_id: GuildId,
+        user_id: UserId,
+        permissions: Option<Permissions>,
+    ) -> Self {
+        let owner_id = guild_id.to_guild_cached(&ctx.cache).map(|g| g.owner_id);
+
+        if owner_id == Some(user_id) {
+            Self::Owner
+        } else if permissions.is_some_and(|p| p.manage_guild()) {
+            Self::Admin
+        } else if permissions.is_some_and(|p| p.moderate_members()) {
+            Self::Moderator
+        } else {
+            Self::Member
+        }
+    }
+
+    fn can_ban(self, target: Self) -> bool {
+        target == Self::Member || target < self
+    }
+}
+
+fn rank_of_issuer(ctx: &Context, command: &CommandInteraction, guild_id: GuildId) -> Rank {
+    let permissions = command.member.as_ref().and_then(|m| m.permissions);
+    Rank::new(ctx, guild_id, command.user.id, permissions)
+}
+
+fn rank_of_target(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+    target: UserId,
+) -> Rank {
+    let permissions = command
+        .data
+        .resolved
+        .members
+        .get(&target)
+        .and_then(|m| m.permissions);
+    Rank::new(ctx, guild_id, target, permissions)
 }
 
-async fn admins_only_response(command: &CommandInteraction, ctx: &Context) {
+/// How long the ban lasts and why it was issued
+fn describe_ban(ban: &TagBannedUser) -> String {
+    let mut description = match ban.expires_at {
+        Some(expires_at) => format!("until {}", discord_timestamp(expires_at, 'f')),
+        None => "for good".to_owned(),
+    };
+    if let Some(reason) = &ban.reason {
+        write!(description, ", reason: {reason}").unwrap();
+    }
+
+    description
+}
+
+async fn tag_outside_server_response(command: &CommandInteraction, ctx: &Context) {
     command
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Message(
                 CreateInteractionResponseMessage::new()
-                    .content("Only moderators can run this command"),
+                    .content("Can't run this command outside of a server"),
             ),
         )
         .instrument(info_span!("Sending message"))
@@ -104,18 +300,24 @@ async fn admins_only_response(command: &CommandInteraction, ctx: &Context) {
         .expect("Error creating interaction response");
 }
 
-fn get_user(command: &CommandInteraction) -> UserId {
-    let user = if let CommandDataOptionValue::SubCommand(sub_command) =
-        command.data.options.first().unwrap().value.clone()
-    {
-        sub_command.first().unwrap().value.clone()
-    } else {
-        panic!("The first option should be a SubCommand");
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let CommandDataOptionValue::User(user) = user else {
-        panic!("Expected user to be a user")
-    };
+    #[test]
+    fn only_members_can_be_banned_by_their_own_rank() {
+        assert!(Rank::Member.can_ban(Rank::Member));
+        assert!(!Rank::Moderator.can_ban(Rank::Moderator));
+        assert!(!Rank::Admin.can_ban(Rank::Admin));
+        assert!(!Rank::Owner.can_ban(Rank::Owner));
+    }
 
-    user
+    #[test]
+    fn nobody_can_ban_someone_above_them() {
+        assert!(Rank::Admin.can_ban(Rank::Moderator));
+        assert!(Rank::Owner.can_ban(Rank::Admin));
+        assert!(!Rank::Member.can_ban(Rank::Moderator));
+        assert!(!Rank::Moderator.can_ban(Rank::Admin));
+        assert!(!Rank::Admin.can_ban(Rank::Owner));
+    }
 }
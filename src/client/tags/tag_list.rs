@@ -0,0 +1,353 @@
+use std::{borrow::Cow, str::FromStr};
+
+use anyhow::Context as _;
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, Context, CreateActionRow,
+        CreateAllowedMentions, CreateButton, CreateComponent, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+        GuildId, ReactionType, UserId,
+    },
+    model::prelude::Colour,
+    small_fixed_array::FixedString,
+};
+use sqlx::PgPool;
+use strum_macros::{Display, EnumIter, EnumString};
+use tracing::{Instrument, info_span};
+
+use crate::client::{ComponentIds, get_option_from_command::GetOptionFromCommand};
+
+use super::{data_access::get_tag_list_entries, model::TagListEntry};
+
+const TAGS_PER_PAGE: usize = 10;
+/// Keeps the custom IDs of the buttons under Discord's limit of 100 characters
+pub const MAX_SEARCH_LENGTH: u16 = 40;
+
+/// How the tags in `/tag list` are ordered
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TagListSort {
+    #[default]
+    Name,
+    Creator,
+    Usage,
+}
+
+/// The page, sort and search of a tag list message and who asked for it.
+///
+/// It's kept in the custom IDs of the buttons so they know what to show next
+/// and only the one who listed the tags can turn its pages
+struct TagListState {
+    invoker: UserId,
+    page: usize,
+    sort: TagListSort,
+    search: Option<String>,
+}
+
+impl TagListState {
+    fn to_content(&self, pages: usize) -> String {
+        let mut content = format!("Page {} of {pages}, sorted by {}", self.page, self.sort);
+        if let Some(search) = &self.search {
+            content += &format!(", searching for \"{search}\"");
+        }
+
+        content
+    }
+
+    fn to_custom_id(&self, button_id: ComponentIds) -> String {
+        format!(
+            "{button_id}:{}:{}:{}:{}",
+            self.invoker,
+            self.page,
+            self.sort,
+            self.search.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Reads the state after the button ID, the search is last because it can contain colons
+    fn from_custom_id(state: &str) -> Option<Self> {
+        let mut parts = state.splitn(4, ':');
+
+        Some(Self {
+            invoker: UserId::new(parts.next()?.parse().ok()?),
+            page: parts.next()?.parse().ok()?,
+            sort: TagListSort::from_str(parts.next()?).ok()?,
+            search: Some(parts.next()?.to_owned()).filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+/// Lists the tags of the server in pages that can be searched and sorted
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn list_tags(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    command.defer(&ctx.http).await?;
+
+    let state = TagListState {
+        invoker: command.user.id,
+        page: 1,
+        sort: command
+            .data
+            .get_optional_string("sort")
+            .and_then(|s| TagListSort::from_str(&s).ok())
+            .unwrap_or_default(),
+        search: command
+            .data
+            .get_optional_string("search")
+            .map(|s| {
+                s.trim()
+                    .to_lowercase()
+                    .chars()
+                    .take(usize::from(MAX_SEARCH_LENGTH))
+                    .collect::<String>()
+            })
+            .filter(|s| !s.is_empty()),
+    };
+
+    let response = create_tag_list_response(guild_id, state, pool).await;
+
+    command
+        .edit_response(&ctx.http, response)
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+/// Moves a tag list message to another page when one of its buttons is pressed
+#[tracing::instrument(err, skip(ctx, button, pool))]
+pub async fn change_tag_list_page(
+    ctx: &Context,
+    button: &ComponentInteraction,
+    button_id: ComponentIds,
+    button_state: &str,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = button
+        .guild_id
+        .context("Tag lists should only be in guilds")?;
+
+    let mut state = TagListState::from_custom_id(button_state)
+        .context("Tag list buttons should have the state in their custom ID")?;
+
+    if button.user.id != state.invoker {
+        button
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Only the one who listed the tags can turn the pages")
+                        .ephemeral(true),
+                ),
+            )
+            .instrument(info_span!("Sending message"))
+            .await?;
+        return Ok(());
+    }
+
+    button.defer(&ctx.http).await?;
+
+    state.page = match button_id {
+        ComponentIds::TagListStart => 1,
+        ComponentIds::TagListPrevious => state.page.saturating_sub(1),
+        ComponentIds::TagListNext => state.page + 1,
+        // Clamped to the last page when the list is created
+        ComponentIds::TagListEnd => usize::MAX,
+        _ => unreachable!("Only tag list buttons change the tag list page"),
+    };
+
+    let response = create_tag_list_response(guild_id, state, pool).await;
+
+    button
+        .edit_response(&ctx.http, response)
+        .instrument(info_span!("Sending message"))
+        .await?;
+
+    Ok(())
+}
+
+async fn create_tag_list_response(
+    guild_id: GuildId,
+    mut state: TagListState,
+    pool: &PgPool,
+) -> EditInteractionResponse<'static> {
+    let mut tags = get_tag_list_entries(guild_id.get() as i64, pool).await;
+
+    if let Some(search) = &state.search {
+        tags.retain(|t| {
+            t.listener.to_lowercase().contains(search) || t.response.to_lowercase().contains(search)
+        });
+    }
+
+    sort_tags(&mut tags, state.sort);
+
+    if tags.is_empty() {
+        let message = match &state.search {
+            Some(search) => format!("There are no tags matching \"{search}\""),
+            None => "There are no tags in this server".to_owned(),
+        };
+
+        return EditInteractionResponse::new()
+            .content(message)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .embeds(vec![])
+            .components(vec![]);
+    }
+
+    let pages = tags.len().div_ceil(TAGS_PER_PAGE);
+    state.page = state.page.clamp(1, pages);
+
+    let mut embed = CreateEmbed::new()
+        .title("Tags:")
+        .description(format!("Tags found: **{}**", tags.len()))
+        .colour(Colour::from_rgb(149, 8, 2));
+
+    for (index, tag) in tags
+        .iter()
+        .enumerate()
+        .skip((state.page - 1) * TAGS_PER_PAGE)
+        .take(TAGS_PER_PAGE)
+    {
+        embed = embed.field(
+            format!("{}. {}", index + 1, shorten(&tag.listener)),
+            format!(
+                "{} | Created by **{}** | {} uses",
+                tag.match_mode, tag.creator_name, tag.uses
+            ),
+            false,
+        );
+    }
+
+    // The search is in the content so it mustn't be able to mention anyone
+    EditInteractionResponse::new()
+        .content(state.to_content(pages))
+        .allowed_mentions(CreateAllowedMentions::new())
+        .embed(embed)
+        .components(vec![CreateComponent::ActionRow(create_tag_list_buttons(
+            &state,
+        ))])
+}
+
+fn sort_tags(tags: &mut [TagListEntry], sort: TagListSort) {
+    match sort {
+        TagListSort::Name => tags.sort_by_cached_key(|t| t.listener.to_lowercase()),
+        TagListSort::Creator => {
+            tags.sort_by_cached_key(|t| (t.creator_name.to_lowercase(), t.listener.to_lowercase()));
+        }
+        TagListSort::Usage => {
+            tags.sort_by_cached_key(|t| (std::cmp::Reverse(t.uses), t.listener.to_lowercase()));
+        }
+    }
+}
+
+/// Cuts long listeners short so they fit in the name of an embed field
+fn shorten(listener: &str) -> String {
+    const MAX_SHOWN_CHARACTERS: usize = 100;
+
+    if listener.chars().count() > MAX_SHOWN_CHARACTERS {
+        listener
+            .chars()
+            .take(MAX_SHOWN_CHARACTERS)
+            .collect::<String>()
+            + "..."
+    } else {
+        listener.to_owned()
+    }
+}
+
+fn create_tag_list_buttons(state: &TagListState) -> CreateActionRow<'static> {
+    CreateActionRow::Buttons(Cow::Owned(vec![
+        CreateButton::new(state.to_custom_id(ComponentIds::TagListStart))
+            .emoji(ReactionType::Unicode(FixedString::from_str("⏪").unwrap()))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(state.to_custom_id(ComponentIds::TagListPrevious))
+            .emoji(ReactionType::Unicode(FixedString::from_str("◀").unwrap()))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(state.to_custom_id(ComponentIds::TagListNext))
+            .emoji(ReactionType::Unicode(FixedString::from_str("▶").unwrap()))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(state.to_custom_id(ComponentIds::TagListEnd))
+            .emoji(ReactionType::Unicode(FixedString::from_str("⏩").unwrap()))
+            .style(ButtonStyle::Primary),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(search: Option<&str>) -> TagListState {
+        TagListState {
+            invoker: UserId::new(123_456_789_012_345_678),
+            page: 3,
+            sort: TagListSort::Usage,
+            search: search.map(str::to_owned),
+        }
+    }
+
+    /// Splits off the button ID like the component handler does
+    fn round_trip(state: &TagListState) -> (String, TagListState) {
+        let custom_id = state.to_custom_id(ComponentIds::TagListNext);
+        let (button_id, button_state) = custom_id.split_once(':').unwrap();
+
+        (
+            button_id.to_owned(),
+            TagListState::from_custom_id(button_state).unwrap(),
+        )
+    }
+
+    #[test]
+    fn custom_id_round_trips() {
+        let (button_id, parsed) = round_trip(&state(Some("hello")));
+
+        assert_eq!(button_id, ComponentIds::TagListNext.to_string());
+        assert_eq!(parsed.invoker, UserId::new(123_456_789_012_345_678));
+        assert_eq!(parsed.page, 3);
+        assert_eq!(parsed.sort, TagListSort::Usage);
+        assert_eq!(parsed.search.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn search_can_be_empty_or_contain_colons() {
+        assert_eq!(round_trip(&state(None)).1.search, None);
+        assert_eq!(
+            round_trip(&state(Some("a:b:c"))).1.search.as_deref(),
+            Some("a:b:c")
+        );
+    }
+
+    #[test]
+    fn longest_custom_id_fits_discords_limit() {
+        let search = "w".repeat(usize::from(MAX_SEARCH_LENGTH));
+        let state = TagListState {
+            invoker: UserId::new(u64::MAX - 1),
+            page: 99_999,
+            sort: TagListSort::Creator,
+            search: Some(search),
+        };
+
+        assert!(
+            state
+                .to_custom_id(ComponentIds::TagListPrevious)
+                .chars()
+                .count()
+                <= 100
+        );
+    }
+
+    #[test]
+    fn malformed_state_is_rejected() {
+        assert!(TagListState::from_custom_id("").is_none());
+        assert!(TagListState::from_custom_id("abc:1:name:").is_none());
+        assert!(TagListState::from_custom_id("1:x:name:").is_none());
+        assert!(TagListState::from_custom_id("1:1:unknown:").is_none());
+        assert!(TagListState::from_custom_id("1:1:name").is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use rand::seq::SliceRandom;
+use serenity::{
+    all::{Context, Message},
+    prelude::Mentionable,
+};
+
+use crate::client::markov::generate_sentence;
+
+/// Renders the placeholders in a tag response:
+///
+/// - `{user}` the name of whoever tripped off the tag
+/// - `{user.mention}` a mention of whoever tripped off the tag
+/// - `{channel}` the channel the tag was tripped off in
+/// - `{server}` the name of the server
+/// - `{args}` the text of the message after the listener
+/// - `{random:a|b|c}` one of the options picked at random
+/// - `{markov}` a sentence generated by the bot
+///
+/// Anything else in braces is left as it is.
+/// The rendered text is escaped as a whole so placeholders next to each other can't be
+/// combined into a mention, `{user.mention}` is only put in after that.
+pub async fn render_response(ctx: &Context, msg: &Message, template: &str, args: &str) -> String {
+    let mut pieces = vec![];
+
+    for segment in parse_template(template) {
+        match segment {
+            Segment::Text(text) => pieces.push(Piece::Text(text.to_owned())),
+            Segment::Placeholder(placeholder) => {
+                match render_placeholder(ctx, msg, placeholder, args).await {
+                    Some(piece) => pieces.push(piece),
+                    None => pieces.push(Piece::Text(format!("{{{placeholder}}}"))),
+                }
+            }
+        }
+    }
+
+    join_pieces(pieces)
+}
+
+/// A part of a response template
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    /// The text between braces
+    Placeholder(&'a str),
+}
+
+/// A rendered part of a response
+#[derive(Debug)]
+enum Piece {
+    Text(String),
+    /// A mention that's allowed to ping
+    Mention(String),
+}
+
+fn parse_template(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        segments.push(Segment::Placeholder(&rest[start + 1..end]));
+
+        rest = &rest[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+
+    segments
+}
+
+/// Escapes the text between the mentions in one go and splices the mentions in
+fn join_pieces(pieces: Vec<Piece>) -> String {
+    let mut response = String::new();
+    let mut text = String::new();
+
+    for piece in pieces {
+        match piece {
+            Piece::Text(rendered) => text.push_str(&rendered),
+            Piece::Mention(mention) => {
+                response.push_str(&escape_mentions(&std::mem::take(&mut text)));
+                response.push_str(&mention);
+            }
+        }
+    }
+    response.push_str(&escape_mentions(&text));
+
+    response
+}
+
+async fn render_placeholder(
+    ctx: &Context,
+    msg: &Message,
+    placeholder: &str,
+    args: &str,
+) -> Option<Piece> {
+    let rendered = match placeholder {
+        "user.mention" => return Some(Piece::Mention(msg.author.mention().to_string())),
+        "user" => msg.author.display_name().to_owned(),
+        "channel" => msg.channel_id.mention().to_string(),
+        "server" => guild_name(ctx, msg),
+        "args" => args.to_owned(),
+        "markov" => generate_sentence(ctx, None).await,
+        _ => pick_random_option(placeholder.strip_prefix("random:")?)?,
+    };
+
+    Some(Piece::Text(rendered))
+}
+
+// The cache guard and the rng can't be held across the awaits of the other placeholders
+fn guild_name(ctx: &Context, msg: &Message) -> String {
+    msg.guild(&ctx.cache)
+        .map(|g| g.name.to_string())
+        .unwrap_or_default()
+}
+
+fn pick_random_option(options: &str) -> Option<String> {
+    let options = options.split('|').collect::<Vec<_>>();
+
+    options
+        .choose(&mut rand::thread_rng())
+        .map(|o| (*o).to_owned())
+}
+
+/// Breaks up mentions with a zero width space so they're shown as text
+fn escape_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+        .replace("<@", "<@\u{200B}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_text_and_placeholders() {
+        assert_eq!(
+            parse_template("hi {user}, {random:a|b}!"),
+            vec![
+                Segment::Text("hi "),
+                Segment::Placeholder("user"),
+                Segment::Text(", "),
+                Segment::Placeholder("random:a|b"),
+                Segment::Text("!"),
+            ]
+        );
+        assert_eq!(
+            parse_template("{user}{args}"),
+            vec![Segment::Placeholder("user"), Segment::Placeholder("args")]
+        );
+    }
+
+    #[test]
+    fn unclosed_braces_are_text() {
+        assert_eq!(
+            parse_template("a {user} {b"),
+            vec![
+                Segment::Text("a "),
+                Segment::Placeholder("user"),
+                Segment::Text(" {b"),
+            ]
+        );
+        assert_eq!(
+            parse_template("no placeholders"),
+            vec![Segment::Text("no placeholders")]
+        );
+        assert!(parse_template("").is_empty());
+    }
+
+    #[test]
+    fn escapes_mass_mentions_in_rendered_text() {
+        assert_eq!(
+            join_pieces(vec![Piece::Text("hi @everyone and @here".to_owned())]),
+            "hi @\u{200B}everyone and @\u{200B}here"
+        );
+    }
+
+    #[test]
+    fn escapes_mentions_split_across_placeholders() {
+        // Escaping the pieces one by one would leave the mention they make up together
+        assert_eq!(
+            join_pieces(vec![
+                Piece::Text("<".to_owned()),
+                Piece::Text("@".to_owned()),
+                Piece::Text("123>".to_owned()),
+            ]),
+            "<@\u{200B}123>"
+        );
+        assert_eq!(
+            join_pieces(vec![
+                Piece::Text("@every".to_owned()),
+                Piece::Text("one".to_owned()),
+            ]),
+            "@\u{200B}everyone"
+        );
+    }
+
+    #[test]
+    fn leaves_allowed_mentions_alone() {
+        assert_eq!(
+            join_pieces(vec![
+                Piece::Text("hi <@1> ".to_owned()),
+                Piece::Mention("<@2>".to_owned()),
+                Piece::Text(" <@3>".to_owned()),
+            ]),
+            "hi <@\u{200B}1> <@2> <@\u{200B}3>"
+        );
+    }
+
+    #[test]
+    fn picks_one_of_the_random_options() {
+        let picked = pick_random_option("a|b|c").unwrap();
+
+        assert!(["a", "b", "c"].contains(&picked.as_str()));
+        assert_eq!(pick_random_option("only"), Some("only".to_owned()));
+    }
+}
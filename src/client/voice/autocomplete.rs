@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use serenity::all::{AutocompleteChoice, Context, GuildId};
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::client::{autocomplete::MAX_AUTOCOMPLETE_CHOICES, global_data::GetBotState};
+
+use super::MyAuxMetadata;
+
+/// Discord doesn't accept longer names for a choice
+const MAX_CHOICE_NAME_LENGTH: usize = 100;
+/// Discord gives up on the choices after 3 seconds, so the call isn't waited on for long
+const CALL_LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Suggests the tracks in the queue whose position or title matches what the user typed so far
+///
+/// The value of a choice is the position of the track, the same number `/queue` shows
+pub async fn track_choices(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    typed: &str,
+) -> Vec<AutocompleteChoice<'static>> {
+    let Some(guild_id) = guild_id else {
+        return Vec::new();
+    };
+
+    let manager = ctx.bot_state().read().await.songbird.clone();
+    let Some(call_lock) = manager.get(guild_id) else {
+        return Vec::new();
+    };
+    let Ok(call) = timeout(CALL_LOCK_TIMEOUT, call_lock.lock()).await else {
+        warn!("Timed out waiting for the call, suggesting no tracks");
+        return Vec::new();
+    };
+    let queue = call.queue().current_queue();
+    drop(call);
+
+    let typed = typed.trim().to_lowercase();
+
+    queue
+        .iter()
+        .enumerate()
+        .filter_map(|(i, track)| {
+            let position = i + 1;
+            let title = track
+                .data::<MyAuxMetadata>()
+                .aux_metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| "Unknown".to_string());
+
+            if !position.to_string().starts_with(&typed) && !title.to_lowercase().contains(&typed) {
+                return None;
+            }
+
+            let name = format!("{position}. {title}")
+                .chars()
+                .take(MAX_CHOICE_NAME_LENGTH)
+                .collect::<String>();
+
+            Some(AutocompleteChoice::new(name, position as i64))
+        })
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .collect()
+}
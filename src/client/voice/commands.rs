@@ -1,9 +1,10 @@
 use serenity::{
-    all::{CommandOptionType, CommandType},
+    all::{CommandOptionType, CommandType, InteractionContext},
     builder::{CreateCommand, CreateCommandOption},
 };
+use strum::EnumProperty;
 
-use crate::client::slash_commands::UserCommand;
+use crate::client::{slash_commands::UserCommand, voice::playlists::MAX_PLAYLISTS_PER_USER};
 
 pub fn create_voice_commands() -> Vec<CreateCommand<'static>> {
     vec![
@@ -19,6 +20,7 @@ pub fn create_voice_commands() -> Vec<CreateCommand<'static>> {
         CreateCommand::new(UserCommand::queue_shuffle.to_string())
             .description("shuffle all the songs in the queue"),
         CreateCommand::new(UserCommand::loop_song.to_string()).description("loop the current song"),
+        create_playlist_command(),
     ]
 }
 
@@ -48,7 +50,8 @@ fn create_skip_command() -> CreateCommand<'static> {
                 "number",
                 "skip the requested song in the queue",
             )
-            .required(false),
+            .required(false)
+            .set_autocomplete(true),
         )
         .add_option(
             CreateCommandOption::new(
@@ -56,7 +59,8 @@ fn create_skip_command() -> CreateCommand<'static> {
                 "until",
                 "skip all the songs before the requested song",
             )
-            .required(false),
+            .required(false)
+            .set_autocomplete(true),
         )
 }
 
@@ -69,7 +73,8 @@ fn create_swap_command() -> CreateCommand<'static> {
                 "first-track",
                 "The first track to swap",
             )
-            .required(true),
+            .required(true)
+            .set_autocomplete(true),
         )
         .add_option(
             CreateCommandOption::new(
@@ -77,6 +82,101 @@ fn create_swap_command() -> CreateCommand<'static> {
                 "second-track",
                 "The second track to swap",
             )
-            .required(true),
+            .required(true)
+            .set_autocomplete(true),
+        )
+}
+
+fn create_playlist_command() -> CreateCommand<'static> {
+    CreateCommand::new("playlist")
+        .description("Save the queue and play it again later")
+        .add_context(InteractionContext::Guild)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::playlist_save.get_str("SubCommand").unwrap(),
+                format!(
+                    "Save the current queue as one of your playlists, you can have {MAX_PLAYLISTS_PER_USER}"
+                ),
+            )
+            .add_sub_option(create_playlist_name_option(
+                "The name of the playlist, saving over one of your playlists replaces it",
+                false,
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::playlist_load.get_str("SubCommand").unwrap(),
+                "Add the tracks of a playlist to the queue",
+            )
+            .add_sub_option(create_playlist_name_option(
+                "Your playlist or one shared with this server",
+                true,
+            ))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "owner",
+                    "Whose playlist to load if more than one has the name",
+                )
+                .required(false),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            UserCommand::playlist_list.get_str("SubCommand").unwrap(),
+            "List your playlists and the ones shared with this server",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::playlist_delete.get_str("SubCommand").unwrap(),
+                "Delete one of your playlists",
+            )
+            .add_sub_option(create_playlist_name_option("The playlist to delete", true)),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::playlist_rename.get_str("SubCommand").unwrap(),
+                "Rename one of your playlists",
+            )
+            .add_sub_option(create_playlist_name_option("The playlist to rename", true))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "new-name",
+                    "The new name of the playlist",
+                )
+                .max_length(32)
+                .required(true),
+            ),
         )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                UserCommand::playlist_share.get_str("SubCommand").unwrap(),
+                "Let everyone in this server load one of your playlists",
+            )
+            .add_sub_option(create_playlist_name_option("The playlist to share", true))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "shared",
+                    "Turn it off to stop sharing the playlist, on by default",
+                )
+                .required(false),
+            ),
+        )
+}
+
+fn create_playlist_name_option(
+    description: &'static str,
+    autocomplete: bool,
+) -> CreateCommandOption<'static> {
+    CreateCommandOption::new(CommandOptionType::String, "name", description)
+        .max_length(32)
+        .required(true)
+        .set_autocomplete(autocomplete)
 }
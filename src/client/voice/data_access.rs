@@ -0,0 +1,316 @@
+use sqlx::PgPool;
+
+use super::model::{Playlist, PlaylistListEntry, SavedQueue, SavedTrack};
+
+/// Replaces the saved queue of the server with the given tracks
+///
+/// The row of the queue is written first so saves of the same server wait for each other
+pub async fn save_queue(queue: &SavedQueue, tracks: &[SavedTrack], pool: &PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO voice_queues
+            ( server_id, voice_channel_id, text_channel_id, position_ms, playing_source_url, updated_at )
+        VALUES ( $1, $2, $3, $4, $5, now() )
+        ON CONFLICT (server_id) DO UPDATE
+        SET voice_channel_id = EXCLUDED.voice_channel_id,
+            text_channel_id = EXCLUDED.text_channel_id,
+            position_ms = EXCLUDED.position_ms,
+            playing_source_url = EXCLUDED.playing_source_url,
+            updated_at = EXCLUDED.updated_at
+        "#,
+        queue.server_id,
+        queue.voice_channel_id,
+        queue.text_channel_id,
+        queue.position_ms,
+        queue.playing_source_url
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM voice_queue_tracks
+        WHERE server_id = $1
+        "#,
+        queue.server_id
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    for track in tracks {
+        sqlx::query!(
+            r#"
+            INSERT INTO voice_queue_tracks
+                ( server_id, position, source_url, title, artist, channel, duration_ms, thumbnail, queued_by )
+            VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9 )
+            "#,
+            queue.server_id,
+            track.position,
+            track.source_url,
+            track.title,
+            track.artist,
+            track.channel,
+            track.duration_ms,
+            track.thumbnail,
+            track.queued_by
+        )
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    }
+
+    tx.commit().await.unwrap();
+}
+
+/// Only updates how far into the playing track the bot is, for when the tracks haven't changed
+pub async fn update_saved_queue_position(server_id: i64, position_ms: i64, pool: &PgPool) {
+    sqlx::query!(
+        r#"
+        UPDATE voice_queues
+        SET position_ms = $2, updated_at = now()
+        WHERE server_id = $1
+        "#,
+        server_id,
+        position_ms
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Deletes the saved queue of the server together with its tracks
+pub async fn delete_saved_queue(server_id: i64, pool: &PgPool) {
+    sqlx::query!(
+        r#"
+        DELETE FROM voice_queues
+        WHERE server_id = $1
+        "#,
+        server_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+pub async fn get_saved_queue(server_id: i64, pool: &PgPool) -> Option<SavedQueue> {
+    sqlx::query_as!(
+        SavedQueue,
+        r#"
+        SELECT server_id, voice_channel_id, text_channel_id, position_ms, playing_source_url
+        FROM voice_queues
+        WHERE server_id = $1
+        "#,
+        server_id
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_saved_tracks(server_id: i64, pool: &PgPool) -> Vec<SavedTrack> {
+    sqlx::query_as!(
+        SavedTrack,
+        r#"
+        SELECT position, source_url, title, artist, channel, duration_ms, thumbnail, queued_by
+        FROM voice_queue_tracks
+        WHERE server_id = $1
+        ORDER BY position
+        "#,
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_playlist(owner_id: i64, name: &str, pool: &PgPool) -> Option<Playlist> {
+    sqlx::query_as!(
+        Playlist,
+        r#"
+        SELECT id, shared_server_id FROM playlists
+        WHERE owner_id = $1 AND name = $2
+        "#,
+        owner_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+/// The playlists with the name that their owners shared with the server
+pub async fn get_shared_playlists_by_name(
+    server_id: i64,
+    name: &str,
+    pool: &PgPool,
+) -> Vec<Playlist> {
+    sqlx::query_as!(
+        Playlist,
+        r#"
+        SELECT id, shared_server_id FROM playlists
+        WHERE shared_server_id = $1 AND name = $2
+        ORDER BY created_at
+        "#,
+        server_id,
+        name
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+/// The playlists of the user and the ones shared with the server
+pub async fn get_playlists_for_user(
+    owner_id: i64,
+    server_id: i64,
+    pool: &PgPool,
+) -> Vec<PlaylistListEntry> {
+    sqlx::query_as!(
+        PlaylistListEntry,
+        r#"
+        SELECT p.name, p.owner_id, p.shared_server_id, COUNT(t.position) AS "track_count!"
+        FROM playlists p
+        LEFT JOIN playlist_tracks t ON t.playlist_id = p.id
+        WHERE p.owner_id = $1 OR p.shared_server_id = $2
+        GROUP BY p.id
+        ORDER BY p.owner_id = $1 DESC, p.name
+        "#,
+        owner_id,
+        server_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_playlist_count(owner_id: i64, pool: &PgPool) -> i64 {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM playlists
+        WHERE owner_id = $1
+        "#,
+        owner_id
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_playlist_track_urls(playlist_id: i32, pool: &PgPool) -> Vec<String> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT source_url FROM playlist_tracks
+        WHERE playlist_id = $1
+        ORDER BY position
+        "#,
+        playlist_id
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+/// Creates the playlist or replaces the tracks of the user's playlist with the same name
+pub async fn save_playlist(owner_id: i64, name: &str, source_urls: &[String], pool: &PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+
+    let playlist_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO playlists ( owner_id, name )
+        VALUES ( $1, $2 )
+        ON CONFLICT (owner_id, name) DO UPDATE
+        SET created_at = now()
+        RETURNING id
+        "#,
+        owner_id,
+        name
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM playlist_tracks
+        WHERE playlist_id = $1
+        "#,
+        playlist_id
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO playlist_tracks ( playlist_id, position, source_url )
+        SELECT $1, (t.position - 1)::INT, t.source_url
+        FROM UNNEST($2::TEXT[]) WITH ORDINALITY AS t(source_url, position)
+        "#,
+        playlist_id,
+        source_urls
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    tx.commit().await.unwrap();
+}
+
+pub async fn delete_playlist(owner_id: i64, name: &str, pool: &PgPool) -> u64 {
+    sqlx::query!(
+        r#"
+        DELETE FROM playlists
+        WHERE owner_id = $1 AND name = $2
+        "#,
+        owner_id,
+        name
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+pub async fn rename_playlist(owner_id: i64, name: &str, new_name: &str, pool: &PgPool) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE playlists
+        SET name = $3
+        WHERE owner_id = $1 AND name = $2
+        "#,
+        owner_id,
+        name,
+        new_name
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+/// Shares the playlist with the server, or stops sharing it if the server is [`None`]
+pub async fn set_playlist_shared_server(
+    owner_id: i64,
+    name: &str,
+    shared_server_id: Option<i64>,
+    pool: &PgPool,
+) -> u64 {
+    sqlx::query!(
+        r#"
+        UPDATE playlists
+        SET shared_server_id = $3
+        WHERE owner_id = $1 AND name = $2
+        "#,
+        owner_id,
+        name,
+        shared_server_id
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected()
+}
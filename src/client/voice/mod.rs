@@ -1,11 +1,15 @@
+pub mod autocomplete;
 pub mod commands;
 pub mod component_interactions;
+mod data_access;
 pub mod helper_funcs;
 pub mod loop_song;
 pub mod model;
+pub mod persistence;
 pub mod play;
 pub mod play_from_attachment;
 pub mod playing;
+pub mod playlists;
 pub mod queue;
 pub mod skip;
 pub mod stop;
@@ -87,6 +87,45 @@ pub async fn is_last_message_in_channel(message: &Message, ctx: &Context) -> boo
         .is_empty()
 }
 
+/// The queue of a server as it was last saved, see [`super::persistence`]
+#[derive(Debug)]
+pub struct SavedQueue {
+    pub server_id: i64,
+    pub voice_channel_id: i64,
+    pub text_channel_id: i64,
+    pub position_ms: i64,
+    /// The track `position_ms` belongs to, [`None`] if it couldn't be saved
+    pub playing_source_url: Option<String>,
+}
+
+/// A track of a [`SavedQueue`] in the order of the queue
+#[derive(Debug)]
+pub struct SavedTrack {
+    pub position: i32,
+    pub source_url: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub channel: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub thumbnail: Option<String>,
+    pub queued_by: String,
+}
+
+/// A queue a user saved with `/playlist save`
+#[derive(Debug)]
+pub struct Playlist {
+    pub id: i32,
+    /// The server everyone can load the playlist in, [`None`] if only the owner can load it
+    pub shared_server_id: Option<i64>,
+}
+
+pub struct PlaylistListEntry {
+    pub name: String,
+    pub owner_id: i64,
+    pub shared_server_id: Option<i64>,
+    pub track_count: i64,
+}
+
 pub enum LastMessageType {
     NowPlaying(Message),
     PositionInQueue(Message),
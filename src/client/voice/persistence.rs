@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use reqwest::Client;
+use serenity::{
+    all::{Context, GenericChannelId, Guild, GuildId},
+    async_trait,
+    model::id::ChannelId,
+};
+use songbird::{
+    EventHandler,
+    input::{AuxMetadata, Input, YoutubeDl},
+    tracks::{Track, TrackHandle},
+};
+use sqlx::PgPool;
+use tokio::{sync::Mutex, time::timeout};
+use tracing::{Instrument, info, info_span, warn};
+use uuid::Uuid;
+
+use crate::client::global_data::GetBotState;
+
+use super::{
+    MyAuxMetadata,
+    data_access::{
+        delete_saved_queue, get_saved_queue, get_saved_tracks, save_queue,
+        update_saved_queue_position,
+    },
+    model::{SavedQueue, SavedTrack},
+    play::{add_queue_events, fill_queue},
+};
+
+/// How often the queue of a call is compared with the saved one
+pub const QUEUE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Saves the queue of the call so it can be restored with [`restore_queue`] after a restart
+///
+/// The tracks are only written again when they changed since the last save,
+/// otherwise only the position in the playing track is updated.
+/// Tracks without a source URL, like attachments, can't be fetched again so they aren't saved.
+pub struct SaveQueueHandler {
+    voice_text_channel: GenericChannelId,
+    guild_id: GuildId,
+    ctx: Context,
+    pool: PgPool,
+    saved_tracks: Mutex<Vec<Uuid>>,
+}
+
+impl SaveQueueHandler {
+    pub fn new(
+        voice_text_channel: GenericChannelId,
+        guild_id: GuildId,
+        ctx: Context,
+        pool: PgPool,
+    ) -> Self {
+        Self {
+            voice_text_channel,
+            guild_id,
+            ctx,
+            pool,
+            saved_tracks: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for SaveQueueHandler {
+    async fn act(&self, _ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+        let songbird = self.ctx.bot_state().read().await.songbird.clone();
+        let call_lock = songbird.get(self.guild_id)?;
+        let Ok(call) = timeout(Duration::from_secs(30), call_lock.lock()).await else {
+            warn!("Timed out waiting for the call, skipping this save of the queue");
+            return None;
+        };
+
+        let queue = call.queue().current_queue();
+        let voice_channel = call.current_channel();
+        drop(call);
+
+        let voice_channel = voice_channel?;
+        let mut saved_tracks = self.saved_tracks.lock().await;
+
+        if queue.is_empty() {
+            if !saved_tracks.is_empty() {
+                delete_saved_queue(self.guild_id.get() as i64, &self.pool).await;
+                saved_tracks.clear();
+            }
+            return None;
+        }
+
+        let playing_source_url = queue[0]
+            .data::<MyAuxMetadata>()
+            .aux_metadata
+            .source_url
+            .clone();
+        let position_ms = get_position_ms(&queue[0]).await;
+        let tracks = queue.iter().map(TrackHandle::uuid).collect::<Vec<_>>();
+
+        if *saved_tracks == tracks {
+            update_saved_queue_position(self.guild_id.get() as i64, position_ms, &self.pool).await;
+            return None;
+        }
+
+        let saved_queue = SavedQueue {
+            server_id: self.guild_id.get() as i64,
+            voice_channel_id: voice_channel.get() as i64,
+            text_channel_id: self.voice_text_channel.get() as i64,
+            position_ms,
+            playing_source_url,
+        };
+
+        save_queue(&saved_queue, &to_saved_tracks(&queue), &self.pool)
+            .instrument(info_span!("Saving the queue"))
+            .await;
+
+        *saved_tracks = tracks;
+
+        None
+    }
+}
+
+/// How far into the playing track the bot is, 0 when the track can't be saved
+async fn get_position_ms(playing: &TrackHandle) -> i64 {
+    if playing
+        .data::<MyAuxMetadata>()
+        .aux_metadata
+        .source_url
+        .is_none()
+    {
+        return 0;
+    }
+
+    playing
+        .get_info()
+        .await
+        .map_or(0, |state| state.position.as_millis() as i64)
+}
+
+fn to_saved_tracks(queue: &[TrackHandle]) -> Vec<SavedTrack> {
+    queue
+        .iter()
+        .map(|track| track.data::<MyAuxMetadata>())
+        .filter_map(|metadata| {
+            let source_url = metadata.aux_metadata.source_url.clone()?;
+            Some((source_url, metadata))
+        })
+        .enumerate()
+        .map(|(position, (source_url, metadata))| SavedTrack {
+            position: position as i32,
+            source_url,
+            title: metadata.aux_metadata.title.clone(),
+            artist: metadata.aux_metadata.artist.clone(),
+            channel: metadata.aux_metadata.channel.clone(),
+            duration_ms: metadata
+                .aux_metadata
+                .duration
+                .map(|duration| duration.as_millis() as i64),
+            thumbnail: metadata.aux_metadata.thumbnail.clone(),
+            queued_by: metadata.queued_by.clone(),
+        })
+        .collect()
+}
+
+fn to_track_metadata(track: &SavedTrack) -> MyAuxMetadata {
+    MyAuxMetadata {
+        aux_metadata: AuxMetadata {
+            source_url: Some(track.source_url.clone()),
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            channel: track.channel.clone(),
+            duration: track
+                .duration_ms
+                .map(|duration| Duration::from_millis(duration as u64)),
+            thumbnail: track.thumbnail.clone(),
+            ..Default::default()
+        },
+        queued_by: track.queued_by.clone(),
+    }
+}
+
+/// Rejoins the voice channel the bot was playing in before it restarted and queues the saved tracks
+///
+/// The saved queue is forgotten if nobody is left in the voice channel.
+/// Nothing happens if the bot is still playing in the guild, like when it only reconnected to Discord.
+#[tracing::instrument(skip(ctx, guild, pool), fields(guild.id = %guild.id))]
+pub async fn restore_queue(ctx: &Context, guild: &Guild, pool: &PgPool) {
+    let Some(saved_queue) = get_saved_queue(guild.id.get() as i64, pool).await else {
+        return;
+    };
+
+    let manager = ctx.bot_state().read().await.songbird.clone();
+
+    if let Some(call_lock) = manager.get(guild.id)
+        && !call_lock.lock().await.queue().is_empty()
+    {
+        return;
+    }
+
+    let voice_channel_id = ChannelId::new(saved_queue.voice_channel_id as u64);
+    let tracks = get_saved_tracks(saved_queue.server_id, pool).await;
+
+    if tracks.is_empty() || !has_listeners(ctx, guild, voice_channel_id) {
+        info!("Nobody is listening anymore, forgetting the queue");
+        delete_saved_queue(saved_queue.server_id, pool).await;
+        return;
+    }
+
+    // The inputs are made before joining so the call isn't held up by them
+    let client = Client::new();
+    let mut inputs = tracks
+        .iter()
+        .map(|track| {
+            let input: Input = YoutubeDl::new(client.clone(), track.source_url.clone()).into();
+            (input, track.queued_by.clone())
+        })
+        .collect::<VecDeque<_>>();
+    let Some((playing_input, _)) = inputs.pop_front() else {
+        return;
+    };
+    let playing = &tracks[0];
+
+    let Ok(call_lock) = manager
+        .join(guild.id, voice_channel_id)
+        .instrument(info_span!("Joining channel"))
+        .await
+    else {
+        warn!("Couldn't rejoin the voice channel");
+        return;
+    };
+
+    // The first track keeps its saved metadata so it can be shown right away
+    let handle = {
+        let Ok(mut call) = timeout(Duration::from_secs(30), call_lock.lock()).await else {
+            warn!("Timed out waiting for the call, couldn't restore the queue");
+            return;
+        };
+
+        add_queue_events(
+            &mut call,
+            GenericChannelId::new(saved_queue.text_channel_id as u64),
+            guild.id,
+            ctx,
+            pool,
+        );
+
+        let track = Track::new_with_data(playing_input, Arc::new(to_track_metadata(playing)));
+        call.enqueue(track).await
+    };
+
+    // The position is only for the track that was playing, which wasn't saved if it was an attachment
+    if saved_queue.position_ms > 0
+        && saved_queue.playing_source_url.as_ref() == Some(&playing.source_url)
+    {
+        let position = Duration::from_millis(saved_queue.position_ms as u64);
+        tokio::spawn(
+            async move {
+                // The seek is done once the track is ready to play
+                if let Err(err) = handle.seek_async(position).await {
+                    warn!("Couldn't seek to where the track was: {err:?}");
+                }
+            }
+            .instrument(info_span!("Seeking restored track")),
+        );
+    }
+
+    info!("Restoring {} tracks", tracks.len());
+
+    fill_queue(inputs, call_lock, ctx, guild.id).await;
+}
+
+/// Whether anyone besides the bot is in the voice channel
+fn has_listeners(ctx: &Context, guild: &Guild, voice_channel_id: ChannelId) -> bool {
+    let bot_id = ctx.cache.current_user().id;
+
+    guild
+        .voice_states
+        .iter()
+        .any(|state| state.channel_id == Some(voice_channel_id) && state.user_id != bot_id)
+}
+
+/// Forgets the saved queue of the guild, has to be called when the bot leaves the voice channel
+pub async fn forget_queue(guild_id: GuildId, pool: &PgPool) {
+    delete_saved_queue(guild_id.get() as i64, pool).await;
+}
@@ -9,6 +9,7 @@ use super::{
     helper_funcs::{
         get_voice_channel_of_user, is_bot_in_another_voice_channel, voice_channel_not_same_response,
     },
+    persistence::{QUEUE_SAVE_INTERVAL, SaveQueueHandler},
     queue::update_queue_message::update_queue_message,
 };
 use file_format::{FileFormat, Kind};
@@ -23,7 +24,10 @@ use rspotify::{
     prelude::BaseClient,
 };
 use serenity::{
-    all::{Colour, CommandDataOptionValue, CommandInteraction, Context, CreateComponent, GuildId},
+    all::{
+        Colour, CommandDataOptionValue, CommandInteraction, Context, CreateComponent,
+        GenericChannelId, GuildId,
+    },
     builder::{CreateActionRow, CreateEmbed, EditInteractionResponse},
     prelude::Mutex,
 };
@@ -32,14 +36,15 @@ use songbird::{
     input::{AuxMetadata, Input, YoutubeDl},
     tracks::{Track, TrackQueue},
 };
+use sqlx::PgPool;
 use std::{borrow::Cow, cmp::min, collections::VecDeque, sync::Arc, time::Duration};
 use tokio::time::timeout;
 use tracing::{Instrument, error, info, info_span, warn};
 use url::Url;
 
 ///play song from youtube
-#[tracing::instrument(skip(ctx))]
-pub async fn play(ctx: &Context, command: &CommandInteraction) {
+#[tracing::instrument(skip(ctx, pool))]
+pub async fn play(ctx: &Context, command: &CommandInteraction, pool: &PgPool) {
     // command
     //     .create_response(
     //         &ctx.http,
@@ -52,11 +57,41 @@ pub async fn play(ctx: &Context, command: &CommandInteraction) {
     //     .unwrap();
     // return;
 
-    let guild_id = command.guild_id.expect("Couldn't get guild ID");
     let query = get_query(command);
 
     command.defer(&ctx.http).await.unwrap();
 
+    let Some(call_lock) = join_voice_channel_of_user(ctx, command, pool).await else {
+        return;
+    };
+
+    //get source from YouTube
+    let source = get_source(query).await;
+
+    match source {
+        Some(SourceType::Video(input, metadata)) => {
+            handle_video(input, metadata, command, ctx, &call_lock).await;
+        }
+        Some(SourceType::Playlist(inputs)) => {
+            handle_playlist(inputs, command, ctx, call_lock).await;
+        }
+        None => {
+            info!("no sourcetype");
+            invalid_link_response(command, ctx).await;
+        }
+    }
+}
+
+/// Joins the voice channel of the user who used the command and sets up the events of the call
+///
+/// Responds to the deferred command and returns [`None`] if the bot can't play for the user
+pub async fn join_voice_channel_of_user(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> Option<Arc<Mutex<songbird::Call>>> {
+    let guild_id = command.guild_id.expect("Couldn't get guild ID");
+
     let guild = &ctx
         .cache
         .guild(guild_id)
@@ -65,7 +100,7 @@ pub async fn play(ctx: &Context, command: &CommandInteraction) {
 
     let Some(voice_channel_id) = get_voice_channel_of_user(guild, command.user.id) else {
         voice_channel_not_found_response(command, ctx).await;
-        return;
+        return None;
     };
 
     let manager = ctx.bot_state().read().await.songbird.clone();
@@ -80,7 +115,7 @@ pub async fn play(ctx: &Context, command: &CommandInteraction) {
         && !queue.expect("Should never fail").is_empty()
     {
         voice_channel_not_same_response(command, ctx).await;
-        return;
+        return None;
     }
 
     //join voice channel
@@ -90,7 +125,7 @@ pub async fn play(ctx: &Context, command: &CommandInteraction) {
         .await
     else {
         voice_channel_not_found_response(command, ctx).await;
-        return;
+        return None;
     };
 
     {
@@ -98,23 +133,10 @@ pub async fn play(ctx: &Context, command: &CommandInteraction) {
             .await
             .unwrap();
 
-        add_track_start_event(&mut call, command, ctx);
+        add_track_start_event(&mut call, command, ctx, pool);
     }
-    //get source from YouTube
-    let source = get_source(query).await;
 
-    match source {
-        Some(SourceType::Video(input, metadata)) => {
-            handle_video(input, metadata, command, ctx, &call_lock).await;
-        }
-        Some(SourceType::Playlist(inputs)) => {
-            handle_playlist(inputs, command, ctx, call_lock).await;
-        }
-        None => {
-            info!("no sourcetype");
-            invalid_link_response(command, ctx).await;
-        }
-    }
+    Some(call_lock)
 }
 
 #[tracing::instrument(skip(input, metadata, command, ctx, call_lock))]
@@ -139,7 +161,7 @@ pub async fn handle_video(
 }
 
 #[tracing::instrument(skip(inputs, command, ctx, call_lock), fields(inputs.length=inputs.len()))]
-async fn handle_playlist(
+pub async fn handle_playlist(
     mut inputs: VecDeque<Input>,
     command: &CommandInteraction,
     ctx: &Context,
@@ -172,23 +194,26 @@ async fn handle_playlist(
         .await;
     }
 
+    let queued_by = command.user.name.to_string();
     fill_queue(
-        inputs,
+        inputs
+            .into_iter()
+            .map(|input| (input, queued_by.clone()))
+            .collect(),
         call_lock,
         ctx,
         command.guild_id.unwrap(),
-        command.user.name.to_string(),
     )
     .await;
 }
 
+/// Queues the inputs in batches while fetching their metadata, each with the name of who queued it
 #[tracing::instrument(skip(inputs, call_lock, ctx), fields(inputs.length=inputs.len()))]
-async fn fill_queue(
-    mut inputs: VecDeque<Input>,
+pub(super) async fn fill_queue(
+    mut inputs: VecDeque<(Input, String)>,
     call_lock: Arc<Mutex<songbird::Call>>,
     ctx: &Context,
     guild_id: GuildId,
-    queued_by: String,
 ) {
     let state_lock = &ctx.bot_state();
 
@@ -202,7 +227,7 @@ async fn fill_queue(
     let length = inputs.len();
 
     let mut fetch_aux_metadata_futures: Vec<_> = vec![];
-    for i in 0..(inputs.len() - 1) {
+    for i in 0..length {
         let shuffle_queue = state_lock
             .read()
             .await
@@ -267,7 +292,10 @@ async fn fill_queue(
                 .remove(&guild_id);
         }
 
-        let mut input = inputs.pop_front().unwrap();
+        // Skipping can take the inputs that were left
+        let Some((mut input, queued_by)) = inputs.pop_front() else {
+            break;
+        };
 
         let call_lock = call_lock.clone();
         let queue_data_lock = state_lock.clone();
@@ -307,7 +335,7 @@ async fn fill_queue(
                 .instrument(info_span!("Fetching metadata"))
                 .await
             {
-                Ok(e) => Some((e, input)),
+                Ok(e) => Some((e, input, queued_by)),
                 Err(e) => {
                     warn!("Error when fetching playlist song metadata: {}", e);
                     None
@@ -335,7 +363,7 @@ async fn fill_queue(
                     None
                 });
 
-            for (metadata, input) in task_results {
+            for (metadata, input, queued_by) in task_results {
                 let queue_filling_stopped = !queue_data_lock
                     .read()
                     .await
@@ -350,7 +378,7 @@ async fn fill_queue(
 
                 let my_metadata = MyAuxMetadata {
                     aux_metadata: metadata,
-                    queued_by: queued_by.clone(),
+                    queued_by,
                 };
                 let track = Track::new_with_data(input, Arc::new(my_metadata));
                 call_lock.lock().await.enqueue(track).await;
@@ -384,28 +412,51 @@ pub fn add_track_start_event(
     call: &mut tokio::sync::MutexGuard<songbird::Call>,
     command: &CommandInteraction,
     ctx: &Context,
+    pool: &PgPool,
 ) {
     if call.queue().is_empty() {
-        call.remove_all_global_events();
-        call.add_global_event(
-            songbird::Event::Track(TrackEvent::Play),
-            TrackStartHandler {
-                voice_text_channel: command.channel_id,
-                guild_id: command.guild_id.unwrap(),
-                ctx: ctx.clone(),
-            },
-        );
-
-        call.add_global_event(
-            songbird::Event::Periodic(Duration::from_secs(60), None),
-            PeriodicHandler {
-                guild_id: command.guild_id.unwrap(),
-                ctx: ctx.clone(),
-            },
+        add_queue_events(
+            call,
+            command.channel_id,
+            command.guild_id.unwrap(),
+            ctx,
+            pool,
         );
     }
 }
 
+/// Replaces the events of the call with the ones that keep its messages and saved queue up to date
+pub fn add_queue_events(
+    call: &mut tokio::sync::MutexGuard<songbird::Call>,
+    voice_text_channel: GenericChannelId,
+    guild_id: GuildId,
+    ctx: &Context,
+    pool: &PgPool,
+) {
+    call.remove_all_global_events();
+    call.add_global_event(
+        songbird::Event::Track(TrackEvent::Play),
+        TrackStartHandler {
+            voice_text_channel,
+            guild_id,
+            ctx: ctx.clone(),
+        },
+    );
+
+    call.add_global_event(
+        songbird::Event::Periodic(Duration::from_secs(60), None),
+        PeriodicHandler {
+            guild_id,
+            ctx: ctx.clone(),
+        },
+    );
+
+    call.add_global_event(
+        songbird::Event::Periodic(QUEUE_SAVE_INTERVAL, None),
+        SaveQueueHandler::new(voice_text_channel, guild_id, ctx.clone(), pool.clone()),
+    );
+}
+
 fn get_query(command: &CommandInteraction) -> String {
     let query = command
         .data
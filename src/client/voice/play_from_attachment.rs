@@ -10,13 +10,14 @@ use serenity::builder::EditInteractionResponse;
 use serenity::prelude::Context;
 use serenity::small_fixed_array::FixedString;
 use songbird::tracks::Track;
+use sqlx::PgPool;
 use tracing::info_span;
 use tracing::{self, Instrument};
 
 use super::model::MyAuxMetadata;
 
-#[tracing::instrument(skip(ctx))]
-pub async fn play_from_attachment(ctx: &Context, command: &CommandInteraction) {
+#[tracing::instrument(skip(ctx, pool))]
+pub async fn play_from_attachment(ctx: &Context, command: &CommandInteraction, pool: &PgPool) {
     // command
     //     .create_response(
     //         &ctx.http,
@@ -119,7 +120,7 @@ pub async fn play_from_attachment(ctx: &Context, command: &CommandInteraction) {
     let call_lock = call_lock.unwrap();
 
     {
-        add_track_start_event(&mut call_lock.lock().await, command, ctx);
+        add_track_start_event(&mut call_lock.lock().await, command, ctx, pool);
 
         let mut call = call_lock.lock().await;
 
@@ -0,0 +1,428 @@
+use std::{collections::VecDeque, sync::LazyLock};
+
+use anyhow::Context as _;
+use regex::Regex;
+use reqwest::Client;
+use serenity::all::{AutocompleteChoice, CommandInteraction, Context, GuildId, UserId};
+use songbird::input::{Input, YoutubeDl};
+use sqlx::PgPool;
+
+use crate::client::{
+    autocomplete::MAX_AUTOCOMPLETE_CHOICES,
+    get_option_from_command::GetOptionFromCommand,
+    global_data::GetBotState,
+    helper_funcs::{command_response, ephemeral_command_response},
+};
+
+use super::{
+    MyAuxMetadata,
+    data_access::{
+        delete_playlist, get_playlist, get_playlist_count, get_playlist_track_urls,
+        get_playlists_for_user, get_shared_playlists_by_name, rename_playlist, save_playlist,
+        set_playlist_shared_server,
+    },
+    model::Playlist,
+    play::{handle_playlist, join_voice_channel_of_user},
+};
+
+pub const MAX_PLAYLISTS_PER_USER: i64 = 25;
+pub const MAX_PLAYLIST_TRACKS: usize = 500;
+pub const MAX_PLAYLIST_NAME_LENGTH: usize = 32;
+
+static PLAYLIST_NAME_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z0-9 _-]+$").expect("Invalid regular expression"));
+
+/// Lowercases the name and checks that it can be shown in code blocks
+fn validate_playlist_name(name: &str) -> Result<String, String> {
+    let name = name.trim().to_lowercase();
+
+    if name.is_empty() || name.len() > MAX_PLAYLIST_NAME_LENGTH {
+        return Err(format!(
+            "A playlist name has to be between 1 and {MAX_PLAYLIST_NAME_LENGTH} characters long"
+        ));
+    }
+
+    if !PLAYLIST_NAME_REGEX.is_match(&name) {
+        return Err(
+            "A playlist name can only contain letters, numbers, spaces, dashes and underscores"
+                .to_owned(),
+        );
+    }
+
+    Ok(name)
+}
+
+/// Saves the tracks of the current queue as a playlist of the user
+///
+/// Saving with the name of one of the user's playlists replaces its tracks.
+/// Tracks without a source URL, like attachments, can't be fetched again so they aren't saved.
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn save_playlist_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+
+    let name = match validate_playlist_name(&command.data.get_string("name")) {
+        Ok(name) => name,
+        Err(response) => {
+            ephemeral_command_response(ctx, command, response).await?;
+            return Ok(());
+        }
+    };
+
+    let manager = ctx.bot_state().read().await.songbird.clone();
+    let queue = match manager.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.queue().current_queue(),
+        None => Vec::new(),
+    };
+
+    let mut source_urls = queue
+        .iter()
+        .filter_map(|track| {
+            track
+                .data::<MyAuxMetadata>()
+                .aux_metadata
+                .source_url
+                .clone()
+        })
+        .collect::<Vec<_>>();
+
+    if source_urls.is_empty() {
+        ephemeral_command_response(
+            ctx,
+            command,
+            "There's nothing in the queue that can be saved".to_owned(),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let owner_id = command.user.id.get() as i64;
+
+    if get_playlist(owner_id, &name, pool).await.is_none()
+        && get_playlist_count(owner_id, pool).await >= MAX_PLAYLISTS_PER_USER
+    {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("You can't have more than {MAX_PLAYLISTS_PER_USER} playlists. Delete one with ``/playlist delete`` or save over one of them"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let left_out = source_urls.len().saturating_sub(MAX_PLAYLIST_TRACKS);
+    source_urls.truncate(MAX_PLAYLIST_TRACKS);
+
+    save_playlist(owner_id, &name, &source_urls, pool).await;
+
+    let mut response = format!(
+        "Saved {} tracks to the playlist ``{name}``, play it with ``/playlist load name:{name}``",
+        source_urls.len()
+    );
+    if left_out > 0 {
+        response.push_str(&format!(
+            "\nA playlist can't have more than {MAX_PLAYLIST_TRACKS} tracks so the last {left_out} were left out"
+        ));
+    }
+
+    command_response(ctx, command, response).await
+}
+
+/// Queues the tracks of one of the user's playlists or of a playlist shared with the server
+///
+/// The tracks are queued the same way as a youtube playlist from `/play`
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn load_playlist_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+    let name = command.data.get_string("name").trim().to_lowercase();
+    let owner = command.data.get_optional_user("owner");
+
+    let playlist = match find_loadable_playlist(command.user.id, owner, guild_id, &name, pool).await
+    {
+        Ok(playlist) => playlist,
+        Err(response) => {
+            ephemeral_command_response(ctx, command, response).await?;
+            return Ok(());
+        }
+    };
+
+    let source_urls = get_playlist_track_urls(playlist.id, pool).await;
+
+    if source_urls.is_empty() {
+        ephemeral_command_response(ctx, command, format!("The playlist ``{name}`` is empty"))
+            .await?;
+        return Ok(());
+    }
+
+    command.defer(&ctx.http).await?;
+
+    let Some(call_lock) = join_voice_channel_of_user(ctx, command, pool).await else {
+        return Ok(());
+    };
+
+    let client = Client::new();
+    let inputs = source_urls
+        .into_iter()
+        .map(|source_url| YoutubeDl::new(client.clone(), source_url).into())
+        .collect::<VecDeque<Input>>();
+
+    handle_playlist(inputs, command, ctx, call_lock).await;
+
+    Ok(())
+}
+
+/// The user's own playlist with the name comes first, then the ones shared with the server
+async fn find_loadable_playlist(
+    user_id: UserId,
+    owner: Option<UserId>,
+    guild_id: GuildId,
+    name: &str,
+    pool: &PgPool,
+) -> Result<Playlist, String> {
+    let owner_id = owner.unwrap_or(user_id).get() as i64;
+
+    if let Some(playlist) = get_playlist(owner_id, name, pool).await
+        && (owner_id == user_id.get() as i64
+            || playlist.shared_server_id == Some(guild_id.get() as i64))
+    {
+        return Ok(playlist);
+    }
+
+    if owner.is_some() {
+        return Err(format!(
+            "<@{owner_id}> didn't share a playlist called ``{name}`` with this server"
+        ));
+    }
+
+    let mut shared_playlists =
+        get_shared_playlists_by_name(guild_id.get() as i64, name, pool).await;
+
+    match shared_playlists.len() {
+        0 => Err(format!(
+            "There's no playlist called ``{name}``, see which ones you can load with ``/playlist list``"
+        )),
+        1 => Ok(shared_playlists.remove(0)),
+        _ => Err(format!(
+            "More than one playlist called ``{name}`` was shared with this server, choose whose to load with the owner option"
+        )),
+    }
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn list_playlists_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+    let user_id = command.user.id.get() as i64;
+
+    let playlists = get_playlists_for_user(user_id, guild_id.get() as i64, pool).await;
+
+    let response = if playlists.is_empty() {
+        "You don't have any playlists and none were shared with this server. Save the queue as one with ``/playlist save``".to_owned()
+    } else {
+        playlists
+            .iter()
+            .map(|p| {
+                let shared = if p.owner_id != user_id {
+                    format!(" by <@{}>", p.owner_id)
+                } else if p.shared_server_id == Some(guild_id.get() as i64) {
+                    ", shared with this server".to_owned()
+                } else if p.shared_server_id.is_some() {
+                    ", shared with another server".to_owned()
+                } else {
+                    String::new()
+                };
+
+                format!("``{}``{shared}, {} tracks", p.name, p.track_count)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    command_response(ctx, command, response).await
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn delete_playlist_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let name = command.data.get_string("name").trim().to_lowercase();
+
+    if delete_playlist(command.user.id.get() as i64, &name, pool).await == 0 {
+        ephemeral_command_response(ctx, command, no_playlist_response(&name)).await?;
+        return Ok(());
+    }
+
+    command_response(ctx, command, format!("Deleted the playlist ``{name}``")).await
+}
+
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn rename_playlist_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let owner_id = command.user.id.get() as i64;
+    let name = command.data.get_string("name").trim().to_lowercase();
+
+    let new_name = match validate_playlist_name(&command.data.get_string("new-name")) {
+        Ok(new_name) => new_name,
+        Err(response) => {
+            ephemeral_command_response(ctx, command, response).await?;
+            return Ok(());
+        }
+    };
+
+    if get_playlist(owner_id, &new_name, pool).await.is_some() {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("You already have a playlist called ``{new_name}``"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if rename_playlist(owner_id, &name, &new_name, pool).await == 0 {
+        ephemeral_command_response(ctx, command, no_playlist_response(&name)).await?;
+        return Ok(());
+    }
+
+    command_response(
+        ctx,
+        command,
+        format!("Renamed the playlist ``{name}`` to ``{new_name}``"),
+    )
+    .await
+}
+
+/// A playlist can be shared with one server at a time, sharing it with another one stops sharing it with the first.
+/// It can only be unshared in the server it's shared with.
+#[tracing::instrument(err, skip(ctx, command, pool))]
+pub async fn share_playlist_command(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let guild_id = command
+        .guild_id
+        .context("Command should only run in guilds")?;
+    let name = command.data.get_string("name").trim().to_lowercase();
+    let shared = command.data.get_optional_bool("shared").unwrap_or(true);
+
+    let owner_id = command.user.id.get() as i64;
+    let Some(playlist) = get_playlist(owner_id, &name, pool).await else {
+        ephemeral_command_response(ctx, command, no_playlist_response(&name)).await?;
+        return Ok(());
+    };
+
+    if !shared && playlist.shared_server_id != Some(guild_id.get() as i64) {
+        ephemeral_command_response(
+            ctx,
+            command,
+            format!("The playlist ``{name}`` isn't shared with this server"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let shared_server_id = shared.then_some(guild_id.get() as i64);
+
+    if set_playlist_shared_server(owner_id, &name, shared_server_id, pool).await == 0 {
+        ephemeral_command_response(ctx, command, no_playlist_response(&name)).await?;
+        return Ok(());
+    }
+
+    let response = if shared {
+        format!(
+            "Shared the playlist ``{name}`` with this server, everyone here can play it with ``/playlist load``"
+        )
+    } else {
+        format!("Stopped sharing the playlist ``{name}``")
+    };
+
+    command_response(ctx, command, response).await
+}
+
+/// Suggests the user's playlists whose name contains what they typed so far
+///
+/// Playlists shared with the server are suggested too if `include_shared` is set
+pub async fn playlist_choices(
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    typed: &str,
+    include_shared: bool,
+    pool: &PgPool,
+) -> Vec<AutocompleteChoice<'static>> {
+    let Some(guild_id) = guild_id else {
+        return Vec::new();
+    };
+
+    let typed = typed.trim().to_lowercase();
+
+    get_playlists_for_user(user_id.get() as i64, guild_id.get() as i64, pool)
+        .await
+        .into_iter()
+        .filter(|p| include_shared || p.owner_id == user_id.get() as i64)
+        .filter(|p| p.name.contains(&typed))
+        .map(|p| {
+            let label = if p.owner_id == user_id.get() as i64 {
+                p.name.clone()
+            } else {
+                format!("{} (shared)", p.name)
+            };
+
+            AutocompleteChoice::new(label, p.name)
+        })
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .collect()
+}
+
+fn no_playlist_response(name: &str) -> String {
+    format!("You don't have a playlist called ``{name}``")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercases_and_trims_names() {
+        assert_eq!(
+            validate_playlist_name("  My Mix_2-b "),
+            Ok("my mix_2-b".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_long_names() {
+        assert!(validate_playlist_name("   ").is_err());
+        assert!(validate_playlist_name(&"a".repeat(MAX_PLAYLIST_NAME_LENGTH)).is_ok());
+        assert!(validate_playlist_name(&"a".repeat(MAX_PLAYLIST_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn rejects_names_that_break_code_blocks() {
+        assert!(validate_playlist_name("mix`").is_err());
+        assert!(validate_playlist_name("mix\nnext").is_err());
+        assert!(validate_playlist_name("mïx").is_err());
+        assert!(validate_playlist_name("<@123>").is_err());
+    }
+}